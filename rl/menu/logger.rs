@@ -1,9 +1,47 @@
 use std::{
+    borrow::Cow,
     collections::HashMap,
-    io::{self, Write},
+    env,
+    io::{self, IsTerminal, Write},
+    sync::atomic::{AtomicBool, Ordering},
 };
 
-#[allow(dead_code)]
+static COLOR_DISABLED: AtomicBool = AtomicBool::new(false);
+
+// For `--no-color`
+pub fn disable_color() {
+    COLOR_DISABLED.store(true, Ordering::Relaxed);
+}
+
+// Color is only used on a terminal, and never after `--no-color` or with `NO_COLOR` set
+pub fn should_color() -> bool {
+    let no_color_var = env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+    !COLOR_DISABLED.load(Ordering::Relaxed) && !no_color_var && io::stdout().is_terminal()
+}
+
+// `text` without its ANSI escape codes when color is off
+pub fn styled(text: &str) -> Cow<'_, str> {
+    if should_color() {
+        Cow::Borrowed(text)
+    } else {
+        Cow::Owned(strip_ansi(text))
+    }
+}
+
+fn strip_ansi(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            // Color codes all end in 'm'
+            chars.by_ref().find(|&c| c == 'm');
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
 #[derive(Hash, PartialEq, Eq)]
 #[repr(u8)]
 pub enum LoggerType {
@@ -49,25 +87,41 @@ impl<'a> Logger<'a> {
     }
 
     pub fn log_msg(&mut self, msg: &'a str, kind: LoggerType) {
-        self.logs.entry(kind).or_insert_with(Vec::new).push(msg);
+        self.logs.entry(kind).or_default().push(msg);
     }
 
+    // Errors go to stderr, everything else to stdout
     pub fn print_logs(self) {
         let mut stdout = io::stdout();
+        let mut stderr = io::stderr();
         self.logs.into_iter().for_each(|(item, messages)| {
             for msg in messages {
                 match (&item, msg) {
                     (LoggerType::Info, msg) => {
-                        writeln!(stdout, "\x1b[1;36m[#] \x1b[0;36m{}\x1b[0m", msg).unwrap()
+                        let line = format!("\x1b[1;36m[#] \x1b[0;36m{}\x1b[0m", msg);
+                        writeln!(stdout, "{}", styled(&line)).unwrap()
                     }
                     (LoggerType::Error, msg) => {
-                        writeln!(stdout, "\x1b[1;31m[*] \x1b[0;31m{}\x1b[0m", msg).unwrap()
+                        let line = format!("\x1b[1;31m[*] \x1b[0;31m{}\x1b[0m", msg);
+                        writeln!(stderr, "{}", styled(&line)).unwrap()
                     }
                     (LoggerType::Message, msg) => {
-                        writeln!(stdout, "\x1b[1;32m[+] \x1b[0;32m{}\x1b[0m", msg).unwrap()
+                        let line = format!("\x1b[1;32m[+] \x1b[0;32m{}\x1b[0m", msg);
+                        writeln!(stdout, "{}", styled(&line)).unwrap()
                     }
                 }
             }
         });
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strips_color_codes() {
+        assert_eq!(strip_ansi("\x1b[1;36m[#] \x1b[0;36mhi\x1b[0m"), "[#] hi");
+        assert_eq!(strip_ansi("plain"), "plain");
+    }
+}
@@ -0,0 +1,127 @@
+use std::path::{Path, PathBuf};
+
+// Only the most recent lines are written back, so the file can't grow forever
+const MAX_SAVED: usize = 1000;
+
+#[derive(Default)]
+pub struct History {
+    entries: Vec<String>,
+}
+
+impl History {
+    // `~/.rl_history`, or nothing when there is no home directory to put it in
+    pub fn default_path() -> Option<PathBuf> {
+        std::env::var_os("HOME").map(|home| Path::new(&home).join(".rl_history"))
+    }
+
+    // A missing or unreadable file just means starting without history
+    pub fn load(path: &Path) -> Self {
+        let entries = std::fs::read_to_string(path)
+            .map(|contents| contents.lines().map(unescape).collect())
+            .unwrap_or_default();
+        Self { entries }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let start = self.entries.len().saturating_sub(MAX_SAVED);
+        let lines: Vec<String> = self.entries[start..].iter().map(|e| escape(e)).collect();
+        let mut contents = lines.join("\n");
+        contents.push('\n');
+        std::fs::write(path, contents)
+            .map_err(|err| format!("Could not save history to '{}': {}", path.display(), err))
+    }
+
+    pub fn record(&mut self, line: &str) {
+        let line = line.trim_end();
+        if !line.trim().is_empty() {
+            self.entries.push(line.to_string());
+        }
+    }
+
+    // Numbered from 1 like shell history, with the later lines of a
+    // multi-line entry indented under its first
+    pub fn numbered(&self) -> String {
+        let width = self.entries.len().to_string().len();
+        let indent = format!("\n{:width$}  ", "");
+        self.entries
+            .iter()
+            .enumerate()
+            .map(|(i, entry)| format!("{:>width$}  {}", i + 1, entry.replace('\n', &indent)))
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+}
+
+// The file holds one entry per line, so entries read over several lines are
+// saved with their newlines escaped
+fn escape(entry: &str) -> String {
+    entry.replace('\\', "\\\\").replace('\n', "\\n")
+}
+
+fn unescape(line: &str) -> String {
+    let mut entry = String::with_capacity(line.len());
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match (c, chars.clone().next()) {
+            ('\\', Some('n')) => {
+                entry.push('\n');
+                chars.next();
+            }
+            ('\\', Some('\\')) => {
+                entry.push('\\');
+                chars.next();
+            }
+            _ => entry.push(c),
+        }
+    }
+    entry
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn records_non_empty_lines() {
+        let mut history = History::default();
+        history.record("var a = 1;\n");
+        history.record("   \n");
+        history.record("print a;");
+        assert_eq!(history.entries, ["var a = 1;", "print a;"]);
+        assert_eq!(history.numbered(), "1  var a = 1;\n2  print a;");
+    }
+
+    #[test]
+    fn saves_and_loads() {
+        let path = std::env::temp_dir().join(format!("rl_history_test_{}", std::process::id()));
+        let mut history = History::default();
+        for i in 0..MAX_SAVED + 5 {
+            history.record(&format!("print {i};"));
+        }
+        history.save(&path).unwrap();
+
+        let loaded = History::load(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.entries.len(), MAX_SAVED);
+        assert_eq!(loaded.entries[0], "print 5;");
+        assert!(History::load(&path).entries.is_empty());
+    }
+
+    #[test]
+    fn keeps_multi_line_entries_whole() {
+        let path =
+            std::env::temp_dir().join(format!("rl_history_multi_test_{}", std::process::id()));
+        let mut history = History::default();
+        history.record("fn f() {\n  print \"a\\\\nb\";\n}\n");
+        history.record("print 1;");
+        assert_eq!(
+            history.numbered(),
+            "1  fn f() {\n     print \"a\\\\nb\";\n   }\n2  print 1;"
+        );
+        history.save(&path).unwrap();
+
+        let loaded = History::load(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.entries, history.entries);
+    }
+}
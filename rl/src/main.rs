@@ -1,40 +1,133 @@
-use menu_lib::logger::{Logger, LoggerType};
-use rlang::{interpreter::Interpreter, run, run_file, run_string};
+use history::History;
+use menu_lib::logger::{self, Logger, LoggerType, styled};
+use rlang::{
+    check, environment::LogLevel, expr::LiteralValue, feed, interpreter::Interpreter, is_complete,
+    lex, run, run_file_with, run_last,
+};
 use std::{
     env,
     io::{self, BufRead, BufReader, Write},
     process::exit,
+    rc::Rc,
 };
 
+mod history;
 #[path = "../menu/menu.rs"]
 mod menu_lib;
 
-const DISPLAY_FUNC: &dyn Fn() -> () = &|| {
+const DISPLAY_FUNC: &dyn Fn() = &|| {
     println!(
-        "\x1b[1;36mRL:
+        "{}",
+        styled(
+            "\x1b[1;36mRL:
 \x1b[0;32mhelp\x1b[1;35m:\t\t\x1b[0;36mShows this
 \x1b[0;32mquit \x1b[1;35m| \x1b[0;32mq \x1b[1;35m| \x1b[0;32mexit\x1b[1;35m:\t\x1b[0;36mQuits the Interpreter
-\x1b[0;32mhelp\x1b[1;35m:\t\t\x1b[0;36mShows this"
+\x1b[0;32m:reset\x1b[1;35m:\t\x1b[0;36mClears all user definitions
+\x1b[0;32m:history\x1b[1;35m:\t\x1b[0;36mLists the lines entered so far
+\x1b[0;32m:load <path>\x1b[1;35m:\t\x1b[0;36mRuns a file into the current session"
+        )
     );
 };
 
-fn run_prompt() -> Result<(), String> {
-    let mut interpreter = Interpreter::new();
+const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+// Tools embedding the REPL can rebrand it through `RL_PROMPT` and `RL_BANNER`
+struct ReplConfig {
+    prompt: String,
+    banner: String,
+}
+
+impl ReplConfig {
+    fn from_vars(var: impl Fn(&str) -> Option<String>) -> Self {
+        Self {
+            prompt: var("RL_PROMPT").unwrap_or_else(|| "> ".into()),
+            banner: var("RL_BANNER")
+                .unwrap_or_else(|| format!("RL Script Interpreter [V {VERSION}]")),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Input {
+    Line,
+    Eof,
+    Failed(String),
+}
+
+// Appends the next line to `buffer`, a read of 0 bytes means stdin was closed
+fn read_input(reader: &mut impl BufRead, buffer: &mut String) -> Input {
+    match reader.read_line(buffer) {
+        Ok(0) => Input::Eof,
+        Ok(_) => Input::Line,
+        Err(err) => Input::Failed(format!("Could not read input: {}", err)),
+    }
+}
+
+// Scripts calling `log` print the same way as the REPL's own messages
+fn log_to_logger(level: LogLevel, msg: &str) {
+    let kind = match level {
+        LogLevel::Info => LoggerType::Info,
+        LogLevel::Error => LoggerType::Error,
+        LogLevel::Message => LoggerType::Message,
+    };
+    let mut logger = Logger::new();
+    logger.log_msg(msg, kind);
+    logger.print_logs();
+}
+
+fn logging(mut interpreter: Interpreter) -> Interpreter {
+    interpreter.set_log_sink(Rc::new(log_to_logger));
+    interpreter
+}
+
+fn run_prompt(config: ReplConfig) -> Result<(), String> {
+    let mut interpreter = logging(Interpreter::new());
     let stdin = io::stdin();
     let mut buffer = String::new();
     let mut reader = BufReader::new(stdin);
+    let history_path = History::default_path();
+    let mut history = history_path
+        .as_deref()
+        .map(History::load)
+        .unwrap_or_default();
 
     let mut logger = Logger::new();
-    logger.log_msg("RL Script Interpreter [V 0.1]", LoggerType::Info);
+    logger.log_msg(&config.banner, LoggerType::Info);
 
     logger.print_logs();
 
     loop {
-        print!("\x1b[1;36m> ");
-        print!("\x1b[0;32m ");
+        // Lines with an open brace or string keep adding to the same input
+        let prompt = if buffer.is_empty() {
+            config.prompt.as_str()
+        } else {
+            "... "
+        };
+        print!("{}", styled(&format!("\x1b[1;36m{}\x1b[0;32m ", prompt)));
         io::stdout().flush().unwrap();
-        reader.read_line(&mut buffer).unwrap();
+        match read_input(&mut reader, &mut buffer) {
+            Input::Line => (),
+            Input::Eof => {
+                println!("{}", styled("\x1b[0m"));
+                break;
+            }
+            Input::Failed(msg) => {
+                println!("{}", styled(&format!("\x1b[0;31mError: {}\x1b[0m", msg)));
+                break;
+            }
+        }
+        if !is_complete(&buffer) {
+            continue;
+        }
+        history.record(&buffer);
 
+        if let Some(path) = buffer.trim().strip_prefix(":load ") {
+            if let Err(msg) = load_file(&mut interpreter, path.trim()) {
+                println!("{}", styled(&format!("\x1b[0;31m{}\x1b[0m", msg)));
+            }
+            buffer.clear();
+            continue;
+        }
         match buffer.to_lowercase().trim() {
             "exit" | "quit" | "q" => break,
             "help" | "?" => {
@@ -42,43 +135,242 @@ fn run_prompt() -> Result<(), String> {
                 buffer.clear();
                 continue;
             }
+            ":history" => {
+                println!("{}", history.numbered());
+                buffer.clear();
+                continue;
+            }
+            ":reset" => {
+                interpreter.reset();
+                buffer.clear();
+                continue;
+            }
             _ => (),
         }
 
-        match run(&mut interpreter, &buffer) {
-            Ok(_) => (),
-            Err(msg) => println!("\x1b[0;31m{}\x1b[0m", msg),
+        match feed(&mut interpreter, &buffer) {
+            Ok(Some(LiteralValue::Nil)) | Ok(None) => (),
+            Ok(Some(value)) => println!("{}", value),
+            Err(msg) => println!("{}", styled(&format!("\x1b[0;31m{}\x1b[0m", msg))),
         }
-        print!("\x1b[0m ");
+        print!("{}", styled("\x1b[0m "));
         buffer.clear();
     }
 
+    if let Some(path) = history_path {
+        history.save(&path)?;
+    }
     Ok(())
 }
 
-fn main() {
-    let args: Vec<String> = env::args().collect();
+// Runs a file in the REPL's interpreter so its definitions stay around
+fn load_file(interpreter: &mut Interpreter, path: &str) -> Result<(), String> {
+    run_file_with(interpreter, path).map_err(|err| err.to_string())
+}
+
+// What the command line asked for, see `USAGE`
+#[derive(Debug, PartialEq)]
+enum Command {
+    Repl,
+    Version,
+    Run(String),
+    Check(String),
+    Tokens(String),
+    Eval(String),
+    PrintLast(String),
+    Out { output: String, script: String },
+}
+
+const USAGE: &str = "Usage: rl [--no-color] [script]
+       rl run <script>
+       rl check <script>
+       rl tokens <script>
+       rl eval <source>
+       rl --print-last <script>
+       rl --out <file> <script>
+       rl --version";
 
-    if args.len() == 3 && args[1].chars().nth(0) == Some('e') {
-        match run_string(&args[2]) {
-            Ok(_) => (),
-            Err(msg) => println!("Error: {}", msg),
+// `args` excludes the program name. The older `--check` and `--tokens`
+// spellings and `e` for eval keep working.
+fn parse_args(args: &[String]) -> Result<Command, String> {
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    let command = match args.as_slice() {
+        [] => Command::Repl,
+        ["--version"] => Command::Version,
+        ["run", script] => Command::Run(script.to_string()),
+        ["check" | "--check", script] => Command::Check(script.to_string()),
+        ["tokens" | "--tokens", script] => Command::Tokens(script.to_string()),
+        ["eval" | "--eval" | "e", source] => Command::Eval(source.to_string()),
+        ["--print-last", script] => Command::PrintLast(script.to_string()),
+        ["--out", output, script] => Command::Out {
+            output: output.to_string(),
+            script: script.to_string(),
+        },
+        [option, ..] if option.starts_with('-') => {
+            return Err(format!("Unknown option '{option}'"));
+        }
+        [script] => Command::Run(script.to_string()),
+        [command, ..] => return Err(format!("Unexpected arguments for '{command}'")),
+    };
+    Ok(command)
+}
+
+fn read_script(path: &str) -> Result<String, String> {
+    std::fs::read_to_string(path).map_err(|err| format!("Could not read '{}': {}", path, err))
+}
+
+// Runs `command` and returns the exit code, errors go to stderr
+fn run_command(command: Command) -> i32 {
+    let result = match command {
+        Command::Repl => {
+            if let Err(msg) = run_prompt(ReplConfig::from_vars(|name| env::var(name).ok())) {
+                println!("Error: {}", msg);
+            }
+            Ok(())
+        }
+        Command::Version => {
+            println!("rl {VERSION}");
+            Ok(())
+        }
+        Command::Run(script) => {
+            run_file_with(&mut logging(Interpreter::new()), &script).map_err(|err| err.to_string())
+        }
+        Command::Check(script) => read_script(&script).and_then(|contents| check(&contents)),
+        Command::Tokens(script) => read_script(&script)
+            .and_then(|contents| lex(&contents))
+            .map(|tokens| {
+                for token in tokens.iter() {
+                    println!("{}", token.describe());
+                }
+            }),
+        Command::Eval(source) => run(&mut logging(Interpreter::new()), &source),
+        Command::PrintLast(script) => {
+            let mut interpreter = logging(Interpreter::new());
+            read_script(&script)
+                .and_then(|contents| run_last(&mut interpreter, &contents))
+                .map(|last| {
+                    if let Some(value) = last {
+                        println!("{value}");
+                    }
+                })
+        }
+        Command::Out { output, script } => std::fs::File::create(&output)
+            .map_err(|err| format!("Could not create '{}': {}", output, err))
+            .and_then(|file| {
+                let mut interpreter = logging(Interpreter::with_output(file));
+                run_file_with(&mut interpreter, &script).map_err(|err| err.to_string())
+            }),
+    };
+
+    match result {
+        Ok(()) => 0,
+        Err(msg) => {
+            eprintln!("Error: {}", msg);
+            1
         }
-        return;
     }
+}
 
-    match args.len().cmp(&2) {
-        std::cmp::Ordering::Greater => {
-            eprintln!("Usage: rl [script]");
+fn main() {
+    let mut args: Vec<String> = env::args().skip(1).collect();
+    // Allowed before or after any command
+    if args.iter().any(|arg| arg == "--no-color") {
+        args.retain(|arg| arg != "--no-color");
+        logger::disable_color();
+    }
+    match parse_args(&args) {
+        Ok(command) => exit(run_command(command)),
+        Err(msg) => {
+            eprintln!("Error: {msg}\n{USAGE}");
             exit(-1);
         }
-        std::cmp::Ordering::Equal => match run_file(&args[1]) {
-            Err(msg) => println!("Error: {}", msg),
-            Ok(_) => exit(0),
-        },
-        _ => match run_prompt() {
-            Ok(_) => (),
-            Err(msg) => println!("Error: {}", msg),
-        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_input_stops_at_eof() {
+        let mut reader = io::Cursor::new("print 1;\n{");
+        let mut buffer = String::new();
+
+        assert_eq!(read_input(&mut reader, &mut buffer), Input::Line);
+        assert_eq!(buffer, "print 1;\n");
+        assert_eq!(read_input(&mut reader, &mut buffer), Input::Line);
+        assert_eq!(buffer, "print 1;\n{");
+        assert_eq!(read_input(&mut reader, &mut buffer), Input::Eof);
+        assert_eq!(read_input(&mut reader, &mut buffer), Input::Eof);
+    }
+
+    #[test]
+    fn read_input_reports_errors() {
+        let mut reader = BufReader::new(&[0xff, b'\n'][..]);
+        let mut buffer = String::new();
+
+        match read_input(&mut reader, &mut buffer) {
+            Input::Failed(msg) => assert!(msg.starts_with("Could not read input"), "{msg}"),
+            other => panic!("Expected a failed read, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn load_file_keeps_definitions() {
+        let path = env::temp_dir().join(format!("rl-load-{}.rl", std::process::id()));
+        std::fs::write(&path, "var base = 40; fn add(x) { return base + x; }").unwrap();
+
+        let mut interpreter = Interpreter::with_output(io::sink());
+        run(&mut interpreter, "var kept = 2;").unwrap();
+        load_file(&mut interpreter, &path.display().to_string()).unwrap();
+        run(&mut interpreter, "assert_eq(add(kept), 42);").unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let err = load_file(&mut interpreter, &path.display().to_string()).unwrap_err();
+        assert!(err.contains("Could not read"), "{err}");
+        run(&mut interpreter, "assert_eq(add(1), 41);").unwrap();
+    }
+
+    #[test]
+    fn repl_config_reads_vars() {
+        let config = ReplConfig::from_vars(|_| None);
+        assert_eq!(config.prompt, "> ");
+        assert_eq!(config.banner, "RL Script Interpreter [V 0.1.0]");
+
+        let config = ReplConfig::from_vars(|name| (name == "RL_PROMPT").then(|| "rl$ ".into()));
+        assert_eq!(config.prompt, "rl$ ");
+        assert_eq!(config.banner, "RL Script Interpreter [V 0.1.0]");
+    }
+
+    #[test]
+    fn parses_commands() {
+        let parse = |args: &[&str]| {
+            let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+            parse_args(&args)
+        };
+
+        assert_eq!(parse(&[]), Ok(Command::Repl));
+        assert_eq!(parse(&["main.rl"]), Ok(Command::Run("main.rl".into())));
+        assert_eq!(
+            parse(&["run", "main.rl"]),
+            Ok(Command::Run("main.rl".into()))
+        );
+        assert_eq!(
+            parse(&["--check", "a.rl"]),
+            Ok(Command::Check("a.rl".into()))
+        );
+        assert_eq!(
+            parse(&["e", "print 1;"]),
+            Ok(Command::Eval("print 1;".into()))
+        );
+        assert_eq!(
+            parse(&["--out", "out.txt", "a.rl"]),
+            Ok(Command::Out {
+                output: "out.txt".into(),
+                script: "a.rl".into()
+            })
+        );
+        assert_eq!(parse(&["--nope"]), Err("Unknown option '--nope'".into()));
+        assert!(parse(&["a.rl", "b.rl"]).is_err());
     }
 }
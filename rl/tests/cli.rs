@@ -0,0 +1,221 @@
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use std::process::{Command, Stdio};
+
+    #[test]
+    fn missing_file_reports_path() {
+        let output = Command::new(env!("CARGO_BIN_EXE_rl"))
+            .arg("does_not_exist.rl")
+            .output()
+            .unwrap();
+
+        let stderr = std::str::from_utf8(output.stderr.as_slice()).unwrap();
+
+        assert_eq!(output.status.code(), Some(1));
+        assert!(
+            stderr.contains("Could not read 'does_not_exist.rl'"),
+            "Stderr: '{}'",
+            stderr
+        );
+    }
+
+    #[test]
+    fn check_reports_resolver_error() {
+        let output = Command::new(env!("CARGO_BIN_EXE_rl"))
+            .args(["--check", "../rlang/cases/top_level_return.rl"])
+            .output()
+            .unwrap();
+
+        let stdout = std::str::from_utf8(output.stdout.as_slice()).unwrap();
+        let stderr = std::str::from_utf8(output.stderr.as_slice()).unwrap();
+
+        assert_eq!(output.status.code(), Some(1));
+        assert!(stdout.is_empty(), "Stdout: '{}'", stdout);
+        assert!(
+            stderr.contains("Can't return from top-level code"),
+            "Stderr: '{}'",
+            stderr
+        );
+    }
+
+    #[test]
+    fn check_reports_every_resolver_error() {
+        let output = Command::new(env!("CARGO_BIN_EXE_rl"))
+            .args(["check", "../rlang/cases/top_level_returns.rl"])
+            .output()
+            .unwrap();
+
+        let stderr = std::str::from_utf8(output.stderr.as_slice()).unwrap();
+
+        assert_eq!(output.status.code(), Some(1));
+        assert!(stderr.contains("at line 1"), "Stderr: '{}'", stderr);
+        assert!(stderr.contains("at line 2"), "Stderr: '{}'", stderr);
+    }
+
+    #[test]
+    fn check_accepts_bare_expression_files() {
+        let output = Command::new(env!("CARGO_BIN_EXE_rl"))
+            .args(["check", "../rlang/cases/bare_expression.rl"])
+            .output()
+            .unwrap();
+
+        let stderr = std::str::from_utf8(output.stderr.as_slice()).unwrap();
+        assert_eq!(output.status.code(), Some(0), "Stderr: '{}'", stderr);
+    }
+
+    #[test]
+    fn error_logs_go_to_stderr() {
+        let output = Command::new(env!("CARGO_BIN_EXE_rl"))
+            .args(["eval", "log(\"error\", \"bad\"); log(\"info\", \"fine\");"])
+            .output()
+            .unwrap();
+
+        let stdout = std::str::from_utf8(output.stdout.as_slice()).unwrap();
+        let stderr = std::str::from_utf8(output.stderr.as_slice()).unwrap();
+
+        assert_eq!(output.status.code(), Some(0), "Stderr: '{}'", stderr);
+        assert_eq!(stderr, "[*] bad\n");
+        assert!(!stdout.contains("bad"), "Stdout: '{}'", stdout);
+        assert!(stdout.contains("fine"), "Stdout: '{}'", stdout);
+    }
+
+    #[test]
+    fn out_writes_prints_to_file() {
+        let out = std::env::temp_dir().join(format!("rl_out_{}.txt", std::process::id()));
+        let output = Command::new(env!("CARGO_BIN_EXE_rl"))
+            .args([
+                "--out",
+                &out.display().to_string(),
+                "../rlang/cases/fn_default.rl",
+            ])
+            .output()
+            .unwrap();
+
+        let stdout = std::str::from_utf8(output.stdout.as_slice()).unwrap();
+        let contents = std::fs::read_to_string(&out).unwrap();
+        std::fs::remove_file(&out).unwrap();
+
+        assert_eq!(output.status.code(), Some(0));
+        assert!(stdout.is_empty(), "Stdout: '{}'", stdout);
+        assert_eq!(contents, "Hello, x Hello!\nHi, x Hi!\nHi, x ?\n");
+    }
+
+    #[test]
+    fn print_last_prints_final_expression() {
+        let path = "../rlang/cases/print_last.rl";
+        let output = Command::new(env!("CARGO_BIN_EXE_rl"))
+            .args(["--print-last", path])
+            .output()
+            .unwrap();
+        let stdout = std::str::from_utf8(output.stdout.as_slice()).unwrap();
+        assert_eq!(output.status.code(), Some(0));
+        assert_eq!(stdout, "2\n");
+
+        let output = Command::new(env!("CARGO_BIN_EXE_rl"))
+            .arg(path)
+            .output()
+            .unwrap();
+        assert!(output.stdout.is_empty());
+    }
+
+    #[test]
+    fn version_prints_package_version() {
+        let output = Command::new(env!("CARGO_BIN_EXE_rl"))
+            .arg("--version")
+            .output()
+            .unwrap();
+
+        let stdout = std::str::from_utf8(output.stdout.as_slice()).unwrap();
+
+        assert_eq!(output.status.code(), Some(0));
+        assert_eq!(stdout, format!("rl {}\n", env!("CARGO_PKG_VERSION")));
+    }
+
+    #[test]
+    fn subcommands_dispatch() {
+        let output = Command::new(env!("CARGO_BIN_EXE_rl"))
+            .args(["eval", "print 1 + 2;"])
+            .output()
+            .unwrap();
+        let stdout = std::str::from_utf8(output.stdout.as_slice()).unwrap();
+        assert_eq!(output.status.code(), Some(0));
+        assert_eq!(stdout, "3\n");
+
+        let output = Command::new(env!("CARGO_BIN_EXE_rl"))
+            .args(["check", "../rlang/cases/top_level_return.rl"])
+            .output()
+            .unwrap();
+        assert_eq!(output.status.code(), Some(1));
+
+        let output = Command::new(env!("CARGO_BIN_EXE_rl"))
+            .args(["run", "../rlang/cases/int_exact.rl"])
+            .output()
+            .unwrap();
+        let stdout = std::str::from_utf8(output.stdout.as_slice()).unwrap();
+        assert_eq!(output.status.code(), Some(0));
+        assert!(
+            stdout.starts_with("9007199254740993\n"),
+            "Stdout: '{}'",
+            stdout
+        );
+
+        let output = Command::new(env!("CARGO_BIN_EXE_rl"))
+            .arg("--bogus")
+            .output()
+            .unwrap();
+        let stderr = std::str::from_utf8(output.stderr.as_slice()).unwrap();
+        assert_ne!(output.status.code(), Some(0));
+        assert!(stderr.contains("Usage: rl"), "Stderr: '{}'", stderr);
+    }
+
+    #[test]
+    fn repl_exits_at_end_of_input() {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_rl"))
+            .env("HOME", std::env::temp_dir())
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        child
+            .stdin
+            .take()
+            .unwrap()
+            .write_all(b"print 40 + 2;\n")
+            .unwrap();
+
+        let output = child.wait_with_output().unwrap();
+        let stdout = std::str::from_utf8(output.stdout.as_slice()).unwrap();
+
+        assert_eq!(output.status.code(), Some(0));
+        assert!(stdout.contains("42\n"), "Stdout: '{}'", stdout);
+    }
+
+    #[test]
+    fn piped_repl_output_has_no_color() {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_rl"))
+            .env("HOME", std::env::temp_dir())
+            .env_remove("NO_COLOR")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        child
+            .stdin
+            .take()
+            .unwrap()
+            .write_all(b"help\nprint 1;\nprint nope;\n")
+            .unwrap();
+
+        let output = child.wait_with_output().unwrap();
+        let stdout = std::str::from_utf8(output.stdout.as_slice()).unwrap();
+
+        assert!(
+            stdout.contains("RL Script Interpreter"),
+            "Stdout: '{}'",
+            stdout
+        );
+        assert!(stdout.contains("Shows this"), "Stdout: '{}'", stdout);
+        assert!(!stdout.contains('\x1b'), "Stdout: '{:?}'", stdout);
+    }
+}
@@ -0,0 +1,76 @@
+use criterion::{Criterion, criterion_group, criterion_main};
+use rlang::parser::Parser;
+use std::hint::black_box;
+
+const FACTORIAL: &str = "
+var times = 10;
+var product = 1;
+
+while (times) {
+    product = product * times;
+    times = times - 1;
+}
+";
+
+const FIBONACCI: &str = "
+fn fib(n) {
+    if (n < 2) return n;
+    return fib(n - 1) + fib(n - 2);
+}
+
+var result = fib(15);
+";
+
+// Every `sign` lookup starts a few scopes below the globals it lives in
+const NATIVE_LOOP: &str = "
+fn run(n) {
+    var total = 0;
+    for (var i = 0; i < n; i = i + 1) {
+        {
+            total = total + sign(-i);
+        }
+    }
+    return total;
+}
+
+var result = run(2000);
+";
+
+fn large_source() -> String {
+    let mut source = String::new();
+    for i in 0..500 {
+        source.push_str(&format!(
+            "var v{i} = {i} * 2 + (3 - {i}) / 4;\nif (v{i} > 10) {{ v{i} = v{i} - 1; }}\n"
+        ));
+    }
+    source
+}
+
+fn bench_lex(c: &mut Criterion) {
+    let source = large_source();
+    c.bench_function("lex large source", |b| {
+        b.iter(|| rlang::lex(black_box(&source)).unwrap())
+    });
+}
+
+fn bench_parse(c: &mut Criterion) {
+    let tokens = rlang::lex(&large_source()).unwrap();
+    c.bench_function("parse large source", |b| {
+        b.iter(|| Parser::new(black_box(tokens.to_vec())).parse().unwrap())
+    });
+}
+
+fn bench_interpret(c: &mut Criterion) {
+    c.bench_function("interpret factorial", |b| {
+        b.iter(|| rlang::run_string(black_box(FACTORIAL)).unwrap())
+    });
+    c.bench_function("interpret fibonacci", |b| {
+        b.iter(|| rlang::run_string(black_box(FIBONACCI)).unwrap())
+    });
+    c.bench_function("interpret native calls in a loop", |b| {
+        b.iter(|| rlang::run_string(black_box(NATIVE_LOOP)).unwrap())
+    });
+}
+
+criterion_group!(benches, bench_lex, bench_parse, bench_interpret);
+criterion_main!(benches);
@@ -0,0 +1,2 @@
+// A file that is a single expression prints its value
+6 * 7
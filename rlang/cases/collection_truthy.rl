@@ -0,0 +1,5 @@
+if ([]) print "truthy"; else print "falsy";
+if ([1]) print "truthy"; else print "falsy";
+if ({}) print "truthy"; else print "falsy";
+if ({"a": 1}) print "truthy"; else print "falsy";
+print ![];
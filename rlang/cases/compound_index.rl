@@ -0,0 +1,23 @@
+var calls = 0;
+
+fn idx() {
+    calls = calls + 1;
+    return 0;
+}
+
+var arr = [1, 2];
+arr[idx()] += 10;
+print arr;
+print calls;
+
+var m = {"k": 1};
+m["k"] += 1;
+m["new"] = 5;
+print m;
+
+var x = 1;
+x += 2;
+x *= 3;
+x -= 1;
+x /= 2;
+print x;
@@ -0,0 +1,11 @@
+var greeting = "hi";
+
+fn outer(a) {
+    var b = a + 1;
+    fn inner() {
+        dump_env();
+    }
+    inner();
+}
+
+outer(1);
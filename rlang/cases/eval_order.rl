@@ -0,0 +1,23 @@
+// Every call records its name in the next free slot of `order`
+var order = [nil, nil, nil, nil, nil, nil, nil];
+var next = 0;
+
+fn record(name, value) {
+    order[next] = name;
+    next = next + 1;
+    return value;
+}
+
+fn g() { return record("g", 1); }
+fn h() { return record("h", 2); }
+fn f(a, b) { return record("f", a + b); }
+fn callee() { return record("callee", f); }
+
+assert_eq(f(g(), h()), 3);
+assert_eq(order, ["g", "h", "f", nil, nil, nil, nil]);
+
+// The callee is evaluated before any argument
+callee()(h(), g());
+assert_eq(order, ["g", "h", "f", "callee", "h", "g", "f"]);
+
+print order;
@@ -0,0 +1,26 @@
+fn make_counter() {
+    var count = 0;
+    fn increment() {
+        count = count + 1;
+        return count;
+    }
+    fn add(n) {
+        count = count + n;
+        return count;
+    }
+
+    return [increment, add];
+}
+
+var counter = make_counter();
+var increment = counter[0];
+var add = counter[1];
+
+print increment();
+print add(10);
+print increment();
+print add(5);
+
+var other = make_counter();
+print other[0]();
+print increment();
@@ -0,0 +1,8 @@
+
+fn greet(name, greeting = "Hello", punct = greeting + "!") {
+    print greeting + ", " + name + " " + punct;
+}
+
+greet("x");
+greet("x", "Hi");
+greet("x", "Hi", "?");
@@ -0,0 +1,36 @@
+fn outer(a) {
+    var local = a * 2;
+    fn inner(b) {
+        return a + local + b;
+    }
+
+    return inner(1);
+}
+
+print outer(3);
+
+fn make_adder(n) {
+    fn add(m) {
+        return n + m;
+    }
+
+    return add;
+}
+
+var add5 = make_adder(5);
+var n = 100;
+print add5(1);
+
+fn twice(x) {
+    fn apply() {
+        fn again() {
+            return x * 2;
+        }
+
+        return again() + x;
+    }
+
+    return apply();
+}
+
+print twice(4);
@@ -0,0 +1,5 @@
+var ok = 1;
+print ok;
+
+var items = [1, 2];
+print items[5];
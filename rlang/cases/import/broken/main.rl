@@ -0,0 +1,3 @@
+import "lib.rl";
+
+print "unreachable";
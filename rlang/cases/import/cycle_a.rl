@@ -0,0 +1,2 @@
+import "cycle_b.rl";
+print "unreachable";
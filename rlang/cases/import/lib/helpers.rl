@@ -0,0 +1,5 @@
+var greeting = "hello from helpers";
+
+fn twice(x) {
+    return x * 2;
+}
@@ -0,0 +1,9 @@
+import "helpers.rl";
+
+fn square(x) {
+    return x * x;
+}
+
+fn double_square(x) {
+    return twice(square(x));
+}
@@ -0,0 +1,5 @@
+import "lib/math.rl";
+
+print square(4);
+print double_square(3);
+print greeting;
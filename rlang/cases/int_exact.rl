@@ -0,0 +1,6 @@
+print 9007199254740993;
+print 9007199254740992 + 1;
+print 7 / 2;
+print 6 / 3;
+print 1 + 0.5;
+print 9223372036854775807 + 1;
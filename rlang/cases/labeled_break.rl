@@ -0,0 +1,18 @@
+outer: for (var i = 0; i < 3; i = i + 1) {
+    for (var j = 0; j < 3; j = j + 1) {
+        if (j == 1) continue outer;
+        if (i == 2) break outer;
+        print i + j * 10;
+    }
+}
+
+var n = 0;
+outer: while (true) {
+    while (true) {
+        n = n + 1;
+        if (n < 3) continue;
+        break outer;
+    }
+    print "not printed";
+}
+print n;
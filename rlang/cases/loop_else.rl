@@ -0,0 +1,26 @@
+var i = 0;
+while (i < 3) {
+    i = i + 1;
+} else {
+    print "completed";
+}
+
+while (true) {
+    break;
+} else {
+    print "not printed";
+}
+
+for (var j = 0; j < 5; j = j + 1) {
+    if (j == 2) break;
+    print j;
+} else {
+    print "not printed";
+}
+
+for (var k = 0; k < 3; k = k + 1) {
+    if (k == 1) continue;
+    print k;
+} else {
+    print "done";
+}
@@ -0,0 +1,13 @@
+var squares = {1: 1, 2: 4, 3: 9};
+squares[4] = 16;
+squares[2] += 1;
+
+print squares[4];
+print squares[2];
+print keys(squares);
+
+var flags = {true: "on", false: "off", 0.5: "half", "1": "string one"};
+print flags[true];
+print flags[0.5];
+print flags["1"];
+print flags;
@@ -0,0 +1,5 @@
+var m = {"b": 2, "a": 1, "c": 3};
+
+print keys(m);
+print values(m);
+print m["a"];
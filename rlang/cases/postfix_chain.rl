@@ -0,0 +1,13 @@
+var tools = {
+    "ops": [fn (x) { return x + 1; }, fn (x) { return x * 2; }],
+    "make": fn () { return {"items": [10, 20]}; }
+};
+
+print tools.ops[0](1);
+print tools.ops[1](5);
+print tools["ops"][1](tools.ops[0](2));
+print tools.make().items[1];
+
+var nested = {"inner": {"value": 3}};
+nested.inner["value"] += 1;
+print nested.inner.value;
@@ -0,0 +1,7 @@
+var config = {"name": "rl", "tags": ["a", "b"], "nested": {"ok": false, "empty": []}};
+print pretty(config);
+
+var looped = [1, 2];
+looped[1] = looped;
+print pretty(looped);
+print pretty(3);
@@ -0,0 +1,7 @@
+var x = 3;
+var name = "rl";
+print "x = {}", x;
+print "{} + {} = {}", 1, 2, 1 + 2;
+print "hello {}!", name;
+print "{}{}", [1, 2], nil;
+print "no placeholders";
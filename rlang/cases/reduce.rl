@@ -0,0 +1,4 @@
+var numbers = [1, 2, 3, 4, 5];
+
+print reduce(numbers, 0, fn (acc, x) { return acc + x; });
+print reduce(numbers, 1, fn (acc, x) { return acc * x; });
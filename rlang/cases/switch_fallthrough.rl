@@ -0,0 +1,32 @@
+fn describe(n) {
+    var kind = "";
+    switch (n) {
+        case 1:
+        case 2:
+            kind = "small";
+            break;
+        case 3:
+            kind = "three, ";
+        case 4:
+            kind = kind + "medium";
+            break;
+        default:
+            kind = "large";
+    }
+    return kind;
+}
+
+print describe(1);
+print describe(2);
+print describe(3);
+print describe(4);
+print describe(10);
+
+for (var i = 0; i < 3; i = i + 1) {
+    switch (i) {
+        case 1:
+            continue;
+        default:
+            print i;
+    }
+}
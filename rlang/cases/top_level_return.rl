@@ -0,0 +1,6 @@
+fn add(a, b) {
+    return a + b;
+}
+
+print add(1, 2);
+return 3;
@@ -0,0 +1,28 @@
+try {
+    throw "boom";
+} catch (e) {
+    print e;
+}
+
+fn check(n) {
+    if (n < 0) {
+        throw "negative " + n;
+    }
+    return n;
+}
+
+try {
+    print check(2);
+    print check(-1);
+    print "unreachable";
+} catch (err) {
+    print err;
+}
+
+try {
+    print [1, 2][3];
+} catch (e) {
+    print "caught index error";
+}
+
+print "done";
@@ -0,0 +1,5 @@
+print typeof 5;
+print typeof "five";
+print typeof nil;
+print typeof [1] + "!";
+print typeof clock;
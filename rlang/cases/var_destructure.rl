@@ -0,0 +1,19 @@
+var point = [3, 4];
+var [x, y] = point;
+print x * x + y * y;
+
+var [head, ...tail] = [1, 2, 3];
+print head;
+print tail;
+
+var [only, ...none] = ["a"];
+print none;
+
+fn swap(pair) {
+    var [a, b] = pair;
+    return [b, a];
+}
+print swap([1, 2]);
+
+var [first] = [[5, 6]], [p, q] = first;
+print q;
@@ -0,0 +1,5 @@
+var a = 1, b = a + 1, c;
+
+print a;
+print b;
+print c;
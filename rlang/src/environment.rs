@@ -1,20 +1,203 @@
-use crate::expr::LiteralValue;
-use std::{cell::RefCell, collections::HashMap, rc::Rc};
+use crate::{
+    expr::{Expr, LiteralValue},
+    interpreter::OverflowMode,
+};
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    io::Write,
+    rc::Rc,
+};
+
+pub type Output = Rc<RefCell<dyn Write>>;
+pub type LogSink = Rc<dyn Fn(LogLevel, &str)>;
+
+// The levels of the `log` native
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LogLevel {
+    Info,
+    Error,
+    Message,
+}
+
+// What `run_collect` counts. Every interpreter sharing the config the stats
+// are set on adds to them, function bodies included.
+#[derive(Default, Debug)]
+pub struct RunStats {
+    pub statements: Cell<usize>,
+    pub functions: Cell<usize>,
+}
+
+// The settings of a run. Each interpreter makes one for its globals and every
+// scope nested inside shares it, so reading a setting doesn't walk the chain.
+#[derive(Default)]
+pub struct RunConfig {
+    output: RefCell<Option<Output>>,
+    log_sink: RefCell<Option<LogSink>>,
+    int_overflow: Cell<OverflowMode>,
+    strict_bool: Cell<bool>,
+    stats: RefCell<Option<Rc<RunStats>>>,
+    // Variables the resolver found no declaration of in any scope around them,
+    // keyed by address with the depth of the scope they are read in. Run from
+    // that depth they can skip the scopes in between and read the globals.
+    locals: RefCell<HashMap<*const Expr, usize>>,
+    // The value of the last `throw`, kept apart from the error it raises so
+    // `catch` gets it without what function calls prefix on the way out
+    thrown: RefCell<Option<String>>,
+    // What was printed last, by `print` statements and the `print` native alike
+    last_print: RefCell<Option<LiteralValue>>,
+}
+
+impl RunConfig {
+    // Where `print` goes, stdout if there is none
+    pub fn output(&self) -> Option<Output> {
+        self.output.borrow().clone()
+    }
+
+    pub fn set_output(&self, output: Output) {
+        self.output.replace(Some(output));
+    }
+
+    // Swaps the output, for capturing it for a while
+    pub fn replace_output(&self, output: Option<Output>) -> Option<Output> {
+        self.output.replace(output)
+    }
+
+    pub fn log_sink(&self) -> Option<LogSink> {
+        self.log_sink.borrow().clone()
+    }
+
+    pub fn set_log_sink(&self, sink: LogSink) {
+        self.log_sink.replace(Some(sink));
+    }
+
+    pub fn int_overflow(&self) -> OverflowMode {
+        self.int_overflow.get()
+    }
+
+    pub fn set_int_overflow(&self, mode: OverflowMode) {
+        self.int_overflow.set(mode);
+    }
+
+    // Whether `and` and `or` give `true` or `false` rather than an operand
+    pub fn strict_bool(&self) -> bool {
+        self.strict_bool.get()
+    }
+
+    pub fn set_strict_bool(&self, strict: bool) {
+        self.strict_bool.set(strict);
+    }
+
+    pub fn stats(&self) -> Option<Rc<RunStats>> {
+        self.stats.borrow().clone()
+    }
+
+    pub fn set_stats(&self, stats: Option<Rc<RunStats>>) {
+        self.stats.replace(stats);
+    }
+
+    pub fn last_print(&self) -> Option<LiteralValue> {
+        self.last_print.borrow().clone()
+    }
+
+    pub fn set_last_print(&self, value: Option<LiteralValue>) {
+        self.last_print.replace(value);
+    }
+
+    pub fn set_thrown(&self, value: String) {
+        self.thrown.replace(Some(value));
+    }
+
+    pub fn take_thrown(&self) -> Option<String> {
+        self.thrown.take()
+    }
+
+    pub fn global_depth(&self, expr: &Expr) -> Option<usize> {
+        self.locals.borrow().get(&(expr as *const Expr)).copied()
+    }
+
+    pub fn record_global(&self, expr: &Expr, depth: usize) {
+        self.locals.borrow_mut().insert(expr, depth);
+    }
+
+    // Must run before the resolved statements are dropped, or another
+    // expression allocated at the same address would pick up the entry
+    pub fn clear_globals(&self) {
+        self.locals.borrow_mut().clear();
+    }
+}
 
 #[derive(Clone)]
 pub struct Environment {
     values: HashMap<String, LiteralValue>,
+    // Set through `set_enclosing`, which also shares the run config
     pub enclosing: Option<Rc<RefCell<Environment>>>,
+    config: Rc<RunConfig>,
+    // The outermost scope of the chain and how far below it this one sits,
+    // kept so neither has to be found by walking the chain
+    globals: Option<Rc<RefCell<Environment>>>,
+    depth: usize,
 }
 
 impl Environment {
     pub fn new() -> Self {
+        Self::with_config(Rc::default())
+    }
+
+    pub fn with_config(config: Rc<RunConfig>) -> Self {
         Self {
             values: HashMap::<String, LiteralValue>::new(),
             enclosing: None,
+            config,
+            globals: None,
+            depth: 0,
+        }
+    }
+
+    // A scope nested in `parent`
+    pub fn enclosed(parent: Rc<RefCell<Environment>>) -> Self {
+        let mut env = Self::new();
+        env.set_enclosing(parent);
+        env
+    }
+
+    pub fn set_enclosing(&mut self, parent: Rc<RefCell<Environment>>) {
+        {
+            let parent_env = parent.borrow();
+            self.config = parent_env.config.clone();
+            self.globals = Some(parent_env.globals.clone().unwrap_or(parent.clone()));
+            self.depth = parent_env.depth + 1;
+        }
+        self.enclosing = Some(parent);
+    }
+
+    pub fn config(&self) -> &Rc<RunConfig> {
+        &self.config
+    }
+
+    // Without a sink errors go to stderr and everything else to the output
+    pub fn log(&self, level: LogLevel, msg: &str) -> Result<(), String> {
+        match self.config.log_sink() {
+            Some(sink) => {
+                sink(level, msg);
+                Ok(())
+            }
+            None if level == LogLevel::Error => {
+                eprintln!("{msg}");
+                Ok(())
+            }
+            None => self.write_line(msg),
         }
     }
 
+    pub fn write_line(&self, line: impl std::fmt::Display) -> Result<(), String> {
+        let result = match self.config.output() {
+            Some(output) => writeln!(output.borrow_mut(), "{line}"),
+            None => writeln!(std::io::stdout(), "{line}"),
+        };
+        result.map_err(|err| format!("Could not write output: {err}"))
+    }
+
     pub fn define_top_level(&mut self, name: String, value: LiteralValue) {
         match &self.enclosing {
             None => self.define(name, value),
@@ -36,6 +219,57 @@ impl Environment {
         }
     }
 
+    // Reads a variable the resolver recorded with `record_global`. It goes
+    // straight to the globals when run from the depth it was resolved at,
+    // since no scope in between declares it.
+    pub fn get_global(&self, expr: &Expr, name: &str) -> Option<LiteralValue> {
+        match &self.globals {
+            Some(globals) if self.config.global_depth(expr) == Some(self.depth) => {
+                globals.borrow().values.get(name).cloned()
+            }
+            _ => self.get(name),
+        }
+    }
+
+    // `assign` for a variable recorded with `record_global`
+    pub fn assign_global(&mut self, expr: &Expr, name: &str, value: LiteralValue) -> bool {
+        match &self.globals {
+            Some(globals) if self.config.global_depth(expr) == Some(self.depth) => {
+                globals.borrow_mut().assign(name, value)
+            }
+            _ => self.assign(name, value),
+        }
+    }
+
+    // How many enclosing scopes sit above this one, 0 for the globals
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    pub fn is_defined(&self, name: &str) -> bool {
+        self.is_defined_local(name)
+            || self
+                .enclosing
+                .as_ref()
+                .is_some_and(|env| env.borrow().is_defined(name))
+    }
+
+    // The variables of this scope sorted by name, not the ones enclosing it
+    pub fn local_values(&self) -> Vec<(String, LiteralValue)> {
+        let mut values: Vec<(String, LiteralValue)> = self
+            .values
+            .iter()
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect();
+        values.sort_by(|(a, _), (b, _)| a.cmp(b));
+        values
+    }
+
+    // Only this scope, not the ones enclosing it
+    pub fn is_defined_local(&self, name: &str) -> bool {
+        self.values.contains_key(name)
+    }
+
     pub fn assign(&mut self, name: &str, value: LiteralValue) -> bool {
         let old_value = self.values.get(name);
         match (old_value, &mut self.enclosing) {
@@ -48,3 +282,84 @@ impl Environment {
         }
     }
 }
+
+impl Default for Environment {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn is_defined_walks_the_chain() {
+        let globals = Rc::new(RefCell::new(Environment::new()));
+        globals
+            .borrow_mut()
+            .define("outer".into(), LiteralValue::Int(1));
+
+        let mut local = Environment::enclosed(globals.clone());
+        local.define("inner".into(), LiteralValue::Nil);
+
+        assert!(local.is_defined("inner"));
+        assert!(local.is_defined_local("inner"));
+        assert!(local.is_defined("outer"));
+        assert!(!local.is_defined_local("outer"));
+        assert!(!local.is_defined("missing"));
+        assert!(!globals.borrow().is_defined("inner"));
+    }
+
+    #[test]
+    fn depth_counts_enclosing_scopes() {
+        let globals = Rc::new(RefCell::new(Environment::new()));
+        let function = Rc::new(RefCell::new(Environment::enclosed(globals.clone())));
+        let block = Environment::enclosed(function.clone());
+
+        assert_eq!(globals.borrow().depth(), 0);
+        assert_eq!(function.borrow().depth(), 1);
+        assert_eq!(block.depth(), 2);
+    }
+
+    #[test]
+    fn recorded_globals_skip_the_scopes_between() {
+        let globals = Rc::new(RefCell::new(Environment::new()));
+        globals
+            .borrow_mut()
+            .define("x".into(), LiteralValue::Int(1));
+        let function = Rc::new(RefCell::new(Environment::enclosed(globals.clone())));
+        function
+            .borrow_mut()
+            .define("x".into(), LiteralValue::Int(2));
+        let block = Environment::enclosed(function.clone());
+
+        let read = Expr::Literal {
+            value: LiteralValue::Nil,
+            span: None,
+        };
+        assert!(block.get_global(&read, "x") == Some(LiteralValue::Int(2)));
+        block.config().record_global(&read, 2);
+        assert!(block.get_global(&read, "x") == Some(LiteralValue::Int(1)));
+        // Run at another depth it is looked up like any variable
+        assert!(function.borrow().get_global(&read, "x") == Some(LiteralValue::Int(2)));
+    }
+
+    #[test]
+    fn nested_scopes_share_the_config() {
+        let globals = Rc::new(RefCell::new(Environment::new()));
+        let function = Rc::new(RefCell::new(Environment::enclosed(globals.clone())));
+        let block = Environment::enclosed(function.clone());
+
+        globals
+            .borrow()
+            .config()
+            .set_int_overflow(OverflowMode::Error);
+        assert_eq!(block.config().int_overflow(), OverflowMode::Error);
+        assert!(Rc::ptr_eq(block.config(), globals.borrow().config()));
+        assert_eq!(
+            Environment::new().config().int_overflow(),
+            OverflowMode::Promote
+        );
+    }
+}
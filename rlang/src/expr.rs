@@ -1,22 +1,91 @@
 use crate::{
     environment::Environment,
-    interpreter::Interpreter,
-    lexer::{self, Token, TokenType},
+    interpreter::{Interpreter, OverflowMode},
+    lexer::{self, Span, Token, TokenType},
     stmt::Stmt,
 };
-use std::{borrow::Cow, cell::RefCell, hash::Hash, rc::Rc};
+use std::{borrow::Cow, cell::RefCell, rc::Rc};
+
+pub type NativeFn = Rc<dyn Fn(&[LiteralValue]) -> Result<LiteralValue, String>>;
+// Natives that also look at the scope they are called from
+pub type ScopedFn = fn(&Environment, &[LiteralValue]) -> Result<LiteralValue, String>;
+pub type ArrayRef = Rc<RefCell<Vec<LiteralValue>>>;
+// Maps keep their entries in insertion order so iteration is deterministic
+pub type MapRef = Rc<RefCell<Vec<(HashableValue, LiteralValue)>>>;
+
+// The values that can key a map. Whole Numbers key as the Int they equal, so
+// `m[1]` and `m[1.0]` find the same entry. Other Numbers compare by their
+// bits, so NaN finds itself.
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub enum HashableValue {
+    Int(i64),
+    Number(u64),
+    StringValue(String),
+    Bool(bool),
+}
+
+impl HashableValue {
+    pub fn from_value(value: &LiteralValue) -> Result<Self, String> {
+        match value {
+            LiteralValue::Int(x) => Ok(Self::Int(*x)),
+            // i64::MAX as f64 rounds up to 2^63, which no i64 can hold
+            LiteralValue::Number(x)
+                if x.fract() == 0.0 && *x >= i64::MIN as f64 && *x < i64::MAX as f64 =>
+            {
+                Ok(Self::Int(*x as i64))
+            }
+            LiteralValue::Number(x) => Ok(Self::Number(x.to_bits())),
+            LiteralValue::StringValue(s) => Ok(Self::StringValue(s.clone())),
+            LiteralValue::True => Ok(Self::Bool(true)),
+            LiteralValue::False => Ok(Self::Bool(false)),
+            other => Err(format!(
+                "Map keys must be a Number, String or Bool, got {}",
+                other.as_ref()
+            )),
+        }
+    }
+
+    pub fn to_value(&self) -> LiteralValue {
+        match self {
+            Self::Int(x) => LiteralValue::Int(*x),
+            Self::Number(bits) => LiteralValue::Number(f64::from_bits(*bits)),
+            Self::StringValue(s) => LiteralValue::StringValue(s.clone()),
+            Self::Bool(true) => LiteralValue::True,
+            Self::Bool(false) => LiteralValue::False,
+        }
+    }
+}
+
+impl std::fmt::Display for HashableValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.to_value())
+    }
+}
 
 #[derive(Clone)]
 pub enum LiteralValue {
     Number(f64),
+    // Integer literals and integer arithmetic stay exact until a float enters
+    // or the result overflows, both have the type Number
+    Int(i64),
     StringValue(String),
     True,
     False,
     Nil,
+    Array(ArrayRef),
+    Map(MapRef),
     Callable {
         name: String,
+        // Callables accept between `min_arity` and `arity` arguments, the
+        // missing trailing ones are filled in from default values
+        min_arity: usize,
         arity: usize,
-        fun: Rc<dyn Fn(&Vec<LiteralValue>) -> LiteralValue>,
+        // Empty for natives
+        param_names: Vec<String>,
+        fun: NativeFn,
+        // Set for scoped natives, calls go here with the calling environment
+        // and the arguments
+        scoped: Option<ScopedFn>,
     },
 }
 
@@ -24,107 +93,107 @@ impl PartialEq for LiteralValue {
     fn eq(&self, other: &Self) -> bool {
         match (self, other) {
             (LiteralValue::Number(x), LiteralValue::Number(y)) => x == y,
-            (
-                LiteralValue::Callable {
-                    name,
-                    arity,
-                    fun: _,
-                },
-                Self::Callable {
-                    name: name2,
-                    arity: arity2,
-                    fun: _,
-                },
-            ) => name == name2 && arity == arity2,
+            (LiteralValue::Int(x), LiteralValue::Int(y)) => x == y,
+            (LiteralValue::Int(x), LiteralValue::Number(y))
+            | (LiteralValue::Number(y), LiteralValue::Int(x)) => *x as f64 == *y,
+            // Only the same function object, however it was copied around
+            (LiteralValue::Callable { fun, .. }, Self::Callable { fun: fun2, .. }) => {
+                Rc::ptr_eq(fun, fun2)
+            }
             (LiteralValue::StringValue(x), LiteralValue::StringValue(y)) => x == y,
             (LiteralValue::True, LiteralValue::True) => true,
             (LiteralValue::False, LiteralValue::False) => true,
             (LiteralValue::Nil, LiteralValue::Nil) => true,
+            (LiteralValue::Array(x), LiteralValue::Array(y)) => *x.borrow() == *y.borrow(),
+            (LiteralValue::Map(x), LiteralValue::Map(y)) => *x.borrow() == *y.borrow(),
             _ => false,
         }
     }
 }
 
 impl LiteralValue {
-    pub fn is_falsy(&self) -> LiteralValue {
+    pub fn as_f64(&self) -> Option<f64> {
         match self {
-            Self::Number(x) => {
-                if *x == 0.0 {
-                    Self::True
-                } else {
-                    Self::False
-                }
-            }
-            Self::StringValue(s) => {
-                if s.len() == 0 {
-                    Self::True
-                } else {
-                    Self::False
-                }
-            }
-            Self::True => Self::False,
-            Self::False => Self::True,
-            Self::Nil => Self::True,
-            Self::Callable {
-                name: _,
-                arity: _,
-                fun: _,
-            } => panic!("Cannot use callable as a falsy value"),
+            Self::Number(x) => Some(*x),
+            Self::Int(x) => Some(*x as f64),
+            _ => None,
         }
     }
-    pub fn is_truthy(&self) -> LiteralValue {
-        match self {
-            Self::Callable {
-                name: _,
-                arity: _,
-                fun: _,
-            } => panic!("Cannot use callable as a truthy value"),
-            Self::Number(x) => {
-                if *x == 0.0 as f64 {
-                    Self::False
-                } else {
-                    Self::True
-                }
-            }
-            Self::StringValue(s) => {
-                if s.len() == 0 {
-                    Self::False
-                } else {
-                    Self::True
-                }
+
+    // Empty strings, arrays and maps, zero, `false` and `nil` are falsy.
+    // Callables count as truthy here, conditions go through `truthiness`
+    // which rejects them instead.
+    pub fn is_truthy(&self) -> bool {
+        self.truthiness().unwrap_or(true)
+    }
+
+    pub fn is_falsy(&self) -> bool {
+        !self.is_truthy()
+    }
+
+    pub fn truthiness(&self) -> Result<bool, String> {
+        Ok(match self {
+            Self::Callable { name, .. } => {
+                return Err(format!("Cannot use callable {name} as a condition"));
             }
-            Self::True => Self::True,
-            Self::False => Self::False,
-            Self::Nil => Self::False,
-        }
+            Self::Array(elements) => !elements.borrow().is_empty(),
+            Self::Map(entries) => !entries.borrow().is_empty(),
+            Self::Number(x) => *x != 0.0,
+            Self::Int(x) => *x != 0,
+            Self::StringValue(s) => !s.is_empty(),
+            Self::True => true,
+            Self::False | Self::Nil => false,
+        })
     }
 }
 
 impl std::fmt::Display for LiteralValue {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let s: Cow<str> = match self {
-            Self::Callable {
-                name,
-                arity,
-                fun: _,
-            } => Cow::Owned(format!("{name}{arity}")),
+            Self::Callable { name, arity, .. } => Cow::Owned(format!("{name}{arity}")),
             Self::Number(x) => Cow::Owned(x.to_string()),
+            Self::Int(x) => Cow::Owned(x.to_string()),
             Self::StringValue(x) => Cow::Borrowed(x),
             Self::True => Cow::Borrowed("true"),
             Self::False => Cow::Borrowed("false"),
             Self::Nil => Cow::Borrowed("nil"),
+            Self::Array(elements) => Cow::Owned(format!(
+                "[{}]",
+                elements
+                    .borrow()
+                    .iter()
+                    .map(|value| value.to_string())
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            )),
+            Self::Map(entries) => Cow::Owned(format!(
+                "{{{}}}",
+                entries
+                    .borrow()
+                    .iter()
+                    .map(|(key, value)| format!("{key}: {value}"))
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            )),
         };
 
         write!(f, "{}", s)
     }
 }
 
-#[derive(Clone)]
+// Equality is structural, tokens included, so two parses only compare equal
+// when the sources line up token for token. Literals compare like values do,
+// which makes `1` and `1.0` equal.
+#[derive(Clone, PartialEq)]
 pub enum Expr {
     AnonFunction {
         paren: Token,
         arguments: Vec<Token>,
-        body: Vec<Box<Stmt>>,
+        // Shared with the function values, like `Stmt::Function`'s body
+        body: Rc<[Box<Stmt>]>,
+    },
+    Array {
+        elements: Vec<Expr>,
     },
     Assign {
         name: Token,
@@ -140,9 +209,28 @@ pub enum Expr {
         paren: Token,
         arguments: Vec<Expr>,
     },
+    // `object.name`, the same as `object["name"]` on a map
+    Get {
+        object: Box<Expr>,
+        name: Token,
+    },
     Grouping {
         expression: Box<Expr>,
     },
+    Index {
+        object: Box<Expr>,
+        bracket: Token,
+        index: Box<Expr>,
+    },
+    // `object[index] = value`, or `object[index] op= value` when an operator
+    // is present, which reads the element and stores the combined result
+    SetIndex {
+        object: Box<Expr>,
+        bracket: Token,
+        index: Box<Expr>,
+        operator: Option<Token>,
+        value: Box<Expr>,
+    },
     Logical {
         left: Box<Expr>,
         operator: Token,
@@ -150,6 +238,11 @@ pub enum Expr {
     },
     Literal {
         value: LiteralValue,
+        // Where the literal was written, `None` for ones the parser made up
+        span: Option<Span>,
+    },
+    Map {
+        entries: Vec<(Expr, Expr)>,
     },
     Unary {
         operator: Token,
@@ -160,23 +253,144 @@ pub enum Expr {
     },
 }
 
-impl Hash for Expr {
-    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
-        std::ptr::hash(self, state)
-    }
+// One method per variant, so a pass over the tree only has to say what it
+// does with each node. `visit_assign` and `visit_variable` also get the node
+// itself so passes can key side tables on it.
+pub trait ExprVisitor<T> {
+    fn visit_anon_function(&mut self, paren: &Token, arguments: &[Token], body: &[Box<Stmt>]) -> T;
+    fn visit_array(&mut self, elements: &[Expr]) -> T;
+    fn visit_assign(&mut self, expr: &Expr, name: &Token, value: &Expr) -> T;
+    fn visit_binary(&mut self, left: &Expr, operator: &Token, right: &Expr) -> T;
+    fn visit_call(&mut self, callee: &Expr, paren: &Token, arguments: &[Expr]) -> T;
+    fn visit_get(&mut self, object: &Expr, name: &Token) -> T;
+    fn visit_grouping(&mut self, expression: &Expr) -> T;
+    fn visit_index(&mut self, object: &Expr, bracket: &Token, index: &Expr) -> T;
+    fn visit_set_index(
+        &mut self,
+        object: &Expr,
+        bracket: &Token,
+        index: &Expr,
+        operator: Option<&Token>,
+        value: &Expr,
+    ) -> T;
+    fn visit_logical(&mut self, left: &Expr, operator: &Token, right: &Expr) -> T;
+    fn visit_literal(&mut self, value: &LiteralValue) -> T;
+    fn visit_map(&mut self, entries: &[(Expr, Expr)]) -> T;
+    fn visit_unary(&mut self, operator: &Token, right: &Expr) -> T;
+    fn visit_variable(&mut self, expr: &Expr, name: &Token) -> T;
 }
 
-impl PartialEq for Expr {
-    fn eq(&self, other: &Self) -> bool {
-        let ptr = std::ptr::addr_of!(self);
-        let ptr2 = std::ptr::addr_of!(other);
-        ptr == ptr2
+impl Expr {
+    pub fn accept<T>(&self, visitor: &mut dyn ExprVisitor<T>) -> T {
+        match self {
+            Expr::AnonFunction {
+                paren,
+                arguments,
+                body,
+            } => visitor.visit_anon_function(paren, arguments, body),
+            Expr::Array { elements } => visitor.visit_array(elements),
+            Expr::Assign { name, value } => visitor.visit_assign(self, name, value),
+            Expr::Binary {
+                left,
+                operator,
+                right,
+            } => visitor.visit_binary(left, operator, right),
+            Expr::Call {
+                callee,
+                paren,
+                arguments,
+            } => visitor.visit_call(callee, paren, arguments),
+            Expr::Get { object, name } => visitor.visit_get(object, name),
+            Expr::Grouping { expression } => visitor.visit_grouping(expression),
+            Expr::Index {
+                object,
+                bracket,
+                index,
+            } => visitor.visit_index(object, bracket, index),
+            Expr::SetIndex {
+                object,
+                bracket,
+                index,
+                operator,
+                value,
+            } => visitor.visit_set_index(object, bracket, index, operator.as_ref(), value),
+            Expr::Logical {
+                left,
+                operator,
+                right,
+            } => visitor.visit_logical(left, operator, right),
+            Expr::Literal { value, .. } => visitor.visit_literal(value),
+            Expr::Map { entries } => visitor.visit_map(entries),
+            Expr::Unary { operator, right } => visitor.visit_unary(operator, right),
+            Expr::Variable { name } => visitor.visit_variable(self, name),
+        }
     }
 }
 
-impl Eq for Expr {}
-
 impl Expr {
+    // The source bytes the expression covers, as far as its tokens tell.
+    // Literals keep no token, so `1 + 2` only spans the `+`, and `None` means
+    // nothing in the expression has a position.
+    pub fn span(&self) -> Option<Span> {
+        let token = |token: &Token| Some(token.span);
+        match self {
+            Expr::AnonFunction { paren, .. } => token(paren),
+            Expr::Array { elements } => elements.iter().map(Expr::span).fold(None, join_spans),
+            Expr::Assign { name, value } => join_spans(token(name), value.span()),
+            Expr::Binary {
+                left,
+                operator,
+                right,
+            }
+            | Expr::Logical {
+                left,
+                operator,
+                right,
+            } => [left.span(), token(operator), right.span()]
+                .into_iter()
+                .fold(None, join_spans),
+            Expr::Call {
+                callee,
+                paren,
+                arguments,
+            } => arguments
+                .iter()
+                .map(Expr::span)
+                .fold(join_spans(callee.span(), token(paren)), join_spans),
+            Expr::Get { object, name } => join_spans(object.span(), token(name)),
+            Expr::Grouping { expression } => expression.span(),
+            Expr::Index {
+                object,
+                bracket,
+                index,
+            } => [object.span(), token(bracket), index.span()]
+                .into_iter()
+                .fold(None, join_spans),
+            Expr::SetIndex {
+                object,
+                bracket,
+                index,
+                operator,
+                value,
+            } => [
+                object.span(),
+                token(bracket),
+                index.span(),
+                operator.as_ref().and_then(token),
+                value.span(),
+            ]
+            .into_iter()
+            .fold(None, join_spans),
+            Expr::Literal { span, .. } => *span,
+            Expr::Map { entries } => entries
+                .iter()
+                .map(|(key, value)| join_spans(key.span(), value.span()))
+                .fold(None, join_spans),
+            Expr::Unary { operator, right } => join_spans(token(operator), right.span()),
+            Expr::Variable { name } => token(name),
+        }
+    }
+
     pub fn evaluate(&self, environment: Rc<RefCell<Environment>>) -> Result<LiteralValue, String> {
         match self {
             Expr::AnonFunction {
@@ -187,50 +401,72 @@ impl Expr {
                 let arity = arguments.len();
                 let env = environment.clone();
                 let arguments: Vec<Token> = arguments.iter().map(|t| (*t).clone()).collect();
-                let body: Vec<Box<Stmt>> = body.iter().map(|b| (*b).clone()).collect();
+                let param_names = arguments.iter().map(|arg| arg.lexme.clone()).collect();
+                let body = body.clone();
                 let paren = paren.clone();
 
-                let fun_impl: Rc<dyn Fn(&Vec<LiteralValue>) -> LiteralValue> =
-                    Rc::new(move |args: &Vec<LiteralValue>| {
-                        let mut anon_int = Interpreter::for_anon(env.clone());
-                        for (i, arg) in args.iter().enumerate() {
-                            anon_int
-                                .environment
-                                .borrow_mut()
-                                .define(arguments[i].lexme.clone(), (*arg).clone());
-                        }
+                let fun_impl: NativeFn = Rc::new(move |args: &[LiteralValue]| {
+                    let mut anon_int = Interpreter::for_anon(env.clone());
+                    for (i, arg) in args.iter().enumerate() {
+                        anon_int
+                            .environment
+                            .borrow_mut()
+                            .define(arguments[i].lexme.clone(), (*arg).clone());
+                    }
 
-                        for i in 0..(body.len()) {
-                            anon_int.interpret(vec![&body[i]]).expect(&format!(
-                                "Evaluating failed inside anon function at line {}",
-                                paren.line_number,
-                            ));
+                    for stmt in body.iter() {
+                        anon_int.interpret(vec![stmt]).map_err(|msg| {
+                            format!(
+                                "Evaluating failed inside anon function at line {}: {}",
+                                paren.line_number, msg
+                            )
+                        })?;
 
-                            if let Some(value) = anon_int.specials.borrow().get("return") {
-                                return value;
-                            }
+                        if let Some(value) = anon_int.specials.borrow().get("return") {
+                            return Ok(value);
                         }
+                    }
 
-                        LiteralValue::Nil
-                    });
+                    Ok(LiteralValue::Nil)
+                });
 
                 Ok(LiteralValue::Callable {
                     name: "anon_function".to_string(),
+                    min_arity: arity,
                     arity,
+                    param_names,
                     fun: fun_impl,
+                    scoped: None,
                 })
             }
             Expr::Call {
                 callee,
-                paren: _,
+                paren,
                 arguments,
             } => {
                 let callable = (*callee).evaluate(environment.clone())?;
                 match callable {
-                    LiteralValue::Callable { name, arity, fun } => {
-                        if arguments.len() != arity {
+                    LiteralValue::Callable {
+                        name,
+                        min_arity,
+                        arity,
+                        param_names,
+                        fun,
+                        scoped,
+                    } => {
+                        if arguments.len() < min_arity || arguments.len() > arity {
+                            let expected = if min_arity == arity {
+                                arity.to_string()
+                            } else {
+                                format!("{min_arity} to {arity}")
+                            };
+                            let params = if param_names.is_empty() {
+                                String::new()
+                            } else {
+                                format!(" (parameters: {})", param_names.join(", "))
+                            };
                             return Err(format!(
-                                "Callable {name} expected {arity} arguments got {}",
+                                "Callable {name} expected {expected} arguments got {}{params}",
                                 arguments.len()
                             ));
                         }
@@ -239,59 +475,163 @@ impl Expr {
                             let val = arg.evaluate(environment.clone())?;
                             args.push(val)
                         }
-                        return Ok(fun(&args));
+                        if let Some(scoped) = scoped {
+                            return scoped(&environment.borrow(), &args);
+                        }
+                        return fun(&args);
                     }
-                    other => Err(format!("{} is not callable", other.as_ref()))?,
+                    other => Err(format!(
+                        "Line {}: value of type {} is not callable",
+                        paren.line_number,
+                        other.as_ref()
+                    ))?,
                 }
                 todo!()
             }
+            Expr::Array { elements } => {
+                let mut values = vec![];
+                for element in elements {
+                    values.push(element.evaluate(environment.clone())?);
+                }
+                Ok(LiteralValue::Array(Rc::new(RefCell::new(values))))
+            }
+            Expr::Map { entries } => {
+                let mut values: Vec<(HashableValue, LiteralValue)> = vec![];
+                for (key, value) in entries {
+                    let key = HashableValue::from_value(&key.evaluate(environment.clone())?)?;
+                    let value = value.evaluate(environment.clone())?;
+                    match values.iter_mut().find(|(k, _)| *k == key) {
+                        Some(entry) => entry.1 = value,
+                        None => values.push((key, value)),
+                    }
+                }
+                Ok(LiteralValue::Map(Rc::new(RefCell::new(values))))
+            }
+            Expr::Get { object, name } => match object.evaluate(environment)? {
+                map @ LiteralValue::Map(_) => {
+                    index_value(&map, &LiteralValue::StringValue(name.lexme.clone()), name)
+                }
+                other => Err(format!(
+                    "Line {}: cannot get '{}' from {}, only maps have properties",
+                    name.line_number,
+                    name.lexme,
+                    other.as_ref()
+                )),
+            },
+            Expr::Index {
+                object,
+                bracket,
+                index,
+            } => {
+                let object = object.evaluate(environment.clone())?;
+                let index = index.evaluate(environment)?;
+                index_value(&object, &index, bracket)
+            }
+            Expr::SetIndex {
+                object,
+                bracket,
+                index,
+                operator,
+                value,
+            } => {
+                let object = object.evaluate(environment.clone())?;
+                let index = index.evaluate(environment.clone())?;
+                let current = match operator {
+                    Some(_) => Some(index_value(&object, &index, bracket)?),
+                    None => None,
+                };
+                let overflow = environment.borrow().config().int_overflow();
+                let mut new_value = value.evaluate(environment)?;
+                if let (Some(operator), Some(current)) = (operator, current) {
+                    new_value = binary_operation(&current, operator, &new_value, overflow)?;
+                }
+
+                match (&object, &index) {
+                    (
+                        LiteralValue::Array(elements),
+                        LiteralValue::Number(_) | LiteralValue::Int(_),
+                    ) => {
+                        let mut elements = elements.borrow_mut();
+                        let i = array_index(&index, elements.len(), "array", bracket)?;
+                        elements[i] = new_value.clone();
+                    }
+                    (LiteralValue::Map(entries), index) => {
+                        let key = map_key(index, bracket)?;
+                        let mut entries = entries.borrow_mut();
+                        match entries.iter_mut().find(|(k, _)| *k == key) {
+                            Some(entry) => entry.1 = new_value.clone(),
+                            None => entries.push((key, new_value.clone())),
+                        }
+                    }
+                    (object, index) => {
+                        return Err(format!(
+                            "Line {}: cannot index {} with {}",
+                            bracket.line_number,
+                            object.as_ref(),
+                            index.as_ref()
+                        ));
+                    }
+                }
+
+                Ok(new_value)
+            }
             Expr::Assign { name, value } => {
                 let new_value = (*value).evaluate(environment.clone())?;
-                let assign_success = environment
-                    .borrow_mut()
-                    .assign(&name.lexme, new_value.clone());
+                let assign_success =
+                    environment
+                        .borrow_mut()
+                        .assign_global(self, &name.lexme, new_value.clone());
                 if assign_success {
                     Ok(new_value)
                 } else {
                     Err(format!("Variable {} has not been declared", name.lexme))
                 }
             }
-            Expr::Variable { name } => match environment.borrow().get(&name.lexme) {
-                Some(val) => Ok(val.clone()),
+            Expr::Variable { name } => match environment.borrow().get_global(self, &name.lexme) {
+                Some(val) => Ok(val),
                 None => Err(format!("Variable '{}' has not been declared", name.lexme)),
             },
-            Expr::Literal { value } => Ok((*value).clone()),
+            Expr::Literal { value, .. } => Ok((*value).clone()),
             Expr::Logical {
                 left,
                 operator,
                 right,
-            } => match operator.token_t {
-                TokenType::Or => {
-                    let lhs_val = left.evaluate(environment.clone())?;
-                    let lhs_true = left.evaluate(environment.clone())?.is_truthy();
-                    if lhs_true == LiteralValue::True {
-                        Ok(lhs_val)
-                    } else {
-                        right.evaluate(environment)
-                    }
-                }
-                TokenType::And => {
-                    let lhs_val = left.evaluate(environment.clone())?;
-                    let lhs_true = lhs_val.is_truthy();
-                    if lhs_true == LiteralValue::False {
-                        Ok(lhs_true)
-                    } else {
-                        right.evaluate(environment)
-                    }
+            } => {
+                let strict = environment.borrow().config().strict_bool();
+                let lhs_val = left.evaluate(environment.clone())?;
+                let lhs_true = lhs_val.truthiness()?;
+                let value = match operator.token_t {
+                    TokenType::Or if lhs_true => lhs_val,
+                    TokenType::And if !lhs_true => LiteralValue::False,
+                    TokenType::Or | TokenType::And => right.evaluate(environment)?,
+                    ty => return Err(format!("Invalid token in logical expression: {}", ty)),
+                };
+                if strict {
+                    Ok(LiteralValue::from(value.truthiness()?))
+                } else {
+                    Ok(value)
                 }
-                ty => Err(format!("Invalid token in logical expression: {}", ty)),
-            },
+            }
             Expr::Grouping { expression } => expression.evaluate(environment),
             Expr::Unary { operator, right } => {
+                let overflow = environment.borrow().config().int_overflow();
                 match ((*right).evaluate(environment)?, operator.token_t) {
                     (LiteralValue::Number(x), TokenType::Minus) => Ok(LiteralValue::Number(-x)),
+                    // Only the smallest Int has no negation, what happens then
+                    // is up to the overflow mode like in `int_operation`
+                    (LiteralValue::Int(x), TokenType::Minus) => match x.checked_neg() {
+                        Some(x) => Ok(LiteralValue::Int(x)),
+                        None if overflow == OverflowMode::Error => Err(format!(
+                            "Line {}: integer overflow in -({})",
+                            operator.line_number, x
+                        )),
+                        None => Ok(LiteralValue::Number(-(x as f64))),
+                    },
                     (_, TokenType::Minus) => Err(format!("Minus not implemented for {}", right)),
-                    (any, TokenType::Bang) => Ok(any.is_falsy()),
+                    (any, TokenType::Bang) => Ok(LiteralValue::from(!any.truthiness()?)),
+                    (any, TokenType::Typeof) => {
+                        Ok(LiteralValue::StringValue(any.as_ref().to_string()))
+                    }
                     (_, ttype) => Err(format!("{} is not valid unary operator", ttype)),
                 }
             }
@@ -300,73 +640,235 @@ impl Expr {
                 operator,
                 right,
             } => {
+                let overflow = environment.borrow().config().int_overflow();
                 let left = left.evaluate(environment.clone())?;
                 let right = right.evaluate(environment)?;
 
-                match (&left, operator.token_t, &right) {
-                    (LiteralValue::Number(x), TokenType::Plus, LiteralValue::Number(y)) => {
-                        Ok(LiteralValue::Number(x + y))
-                    }
-                    (LiteralValue::Number(x), TokenType::Minus, LiteralValue::Number(y)) => {
-                        Ok(LiteralValue::Number(x - y))
-                    }
-                    (LiteralValue::Number(x), TokenType::Slash, LiteralValue::Number(y)) => {
-                        Ok(LiteralValue::Number(x / y))
-                    }
-                    (LiteralValue::Number(x), TokenType::Less, LiteralValue::Number(y)) => {
-                        Ok(LiteralValue::from(x < y))
-                    }
-                    (LiteralValue::Number(x), TokenType::LessEqual, LiteralValue::Number(y)) => {
-                        Ok(LiteralValue::from(x <= y))
-                    }
-                    (LiteralValue::Number(x), TokenType::Greater, LiteralValue::Number(y)) => {
-                        Ok(LiteralValue::from(x > y))
-                    }
-                    (LiteralValue::Number(x), TokenType::Star, LiteralValue::Number(y)) => {
-                        Ok(LiteralValue::Number(x * y))
-                    }
-                    (LiteralValue::StringValue(s), TokenType::Plus, LiteralValue::Number(x)) => {
-                        Ok(LiteralValue::StringValue(format!("{}{}", &s, &x)))
+                binary_operation(&left, operator, &right, overflow).map_err(|err| {
+                    let at = operator.span;
+                    match self.span().filter(|whole| *whole != at) {
+                        Some(whole) => format!(
+                            "{err} (operator at bytes {}..{} of the expression at {}..{})",
+                            at.start, at.end, whole.start, whole.end
+                        ),
+                        None => format!("{err} (operator at bytes {}..{})", at.start, at.end),
                     }
+                })
+            }
+        }
+    }
+}
 
-                    (LiteralValue::Number(_), op, LiteralValue::StringValue(_)) => {
-                        Err(format!("{} is not defined for String and Number", op))
-                    }
-                    (
-                        LiteralValue::StringValue(s1),
-                        TokenType::Plus,
-                        LiteralValue::StringValue(s2),
-                    ) => Ok(LiteralValue::StringValue((*s1).clone() + s2)),
-                    (x, TokenType::BangEqual, y) => Ok(LiteralValue::from(x != y)),
-                    (x, TokenType::EqualEqual, y) => Ok(LiteralValue::from(x == y)),
+fn join_spans(a: Option<Span>, b: Option<Span>) -> Option<Span> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(Span {
+            start: a.start.min(b.start),
+            end: a.end.max(b.end),
+        }),
+        (a, b) => a.or(b),
+    }
+}
 
-                    (
-                        LiteralValue::StringValue(s1),
-                        TokenType::Greater,
-                        LiteralValue::StringValue(s2),
-                    ) => Ok(LiteralValue::from(s1 > s2)),
-                    (
-                        LiteralValue::StringValue(s1),
-                        TokenType::GreaterEqual,
-                        LiteralValue::StringValue(s2),
-                    ) => Ok(LiteralValue::from(s1 >= s2)),
-                    (
-                        LiteralValue::StringValue(s1),
-                        TokenType::Less,
-                        LiteralValue::StringValue(s2),
-                    ) => Ok(LiteralValue::from(s1 < s2)),
-                    (
-                        LiteralValue::StringValue(s1),
-                        TokenType::LessEqual,
-                        LiteralValue::StringValue(s2),
-                    ) => Ok(LiteralValue::from(s1 <= s2)),
-                    (x, ttype, y) => Err(format!(
-                        "{} is not implemented for the operands `{}` and `{}`",
-                        ttype, x, y
-                    )),
-                }
+fn array_index(
+    index: &LiteralValue,
+    len: usize,
+    kind: &str,
+    bracket: &Token,
+) -> Result<usize, String> {
+    let in_range = match index {
+        LiteralValue::Int(i) => usize::try_from(*i).is_ok_and(|i| i < len),
+        LiteralValue::Number(i) => i.fract() == 0.0 && *i >= 0.0 && (*i as usize) < len,
+        _ => false,
+    };
+    if !in_range {
+        return Err(format!(
+            "Line {}: index {} out of range for {} of length {}",
+            bracket.line_number, index, kind, len
+        ));
+    }
+    Ok(index.as_f64().unwrap_or_default() as usize)
+}
+
+fn index_value(
+    object: &LiteralValue,
+    index: &LiteralValue,
+    bracket: &Token,
+) -> Result<LiteralValue, String> {
+    match (object, index) {
+        (LiteralValue::Array(elements), LiteralValue::Number(_) | LiteralValue::Int(_)) => {
+            let elements = elements.borrow();
+            let i = array_index(index, elements.len(), "array", bracket)?;
+            Ok(elements[i].clone())
+        }
+        // Strings index by character, so a multi-byte one comes back whole
+        (LiteralValue::StringValue(s), LiteralValue::Number(_) | LiteralValue::Int(_)) => {
+            let i = array_index(index, s.chars().count(), "string", bracket)?;
+            let c = s.chars().nth(i).unwrap_or_default();
+            Ok(LiteralValue::StringValue(c.to_string()))
+        }
+        (LiteralValue::Map(entries), index) => {
+            let key = map_key(index, bracket)?;
+            entries
+                .borrow()
+                .iter()
+                .find(|(k, _)| *k == key)
+                .map(|(_, value)| value.clone())
+                .ok_or_else(|| {
+                    format!(
+                        "Line {}: key '{}' not found in map",
+                        bracket.line_number, key
+                    )
+                })
+        }
+        (object, index) => Err(format!(
+            "Line {}: cannot index {} with {}",
+            bracket.line_number,
+            object.as_ref(),
+            index.as_ref()
+        )),
+    }
+}
+
+fn map_key(index: &LiteralValue, bracket: &Token) -> Result<HashableValue, String> {
+    HashableValue::from_value(index).map_err(|msg| format!("Line {}: {}", bracket.line_number, msg))
+}
+
+fn binary_operation(
+    left: &LiteralValue,
+    operator: &Token,
+    right: &LiteralValue,
+    overflow: OverflowMode,
+) -> Result<LiteralValue, String> {
+    let promoted;
+    let (left, right) = match (left, right) {
+        (LiteralValue::Int(x), LiteralValue::Int(y)) => {
+            if let Some(value) = int_operation(*x, operator, *y, overflow)? {
+                return Ok(value);
             }
+            (left, right)
+        }
+        // Mixing in a float turns the integer side into one too
+        (LiteralValue::Int(x), LiteralValue::Number(_)) => {
+            promoted = LiteralValue::Number(*x as f64);
+            (&promoted, right)
+        }
+        (LiteralValue::Number(_), LiteralValue::Int(y)) => {
+            promoted = LiteralValue::Number(*y as f64);
+            (left, &promoted)
+        }
+        _ => (left, right),
+    };
+
+    match (left, operator.token_t, right) {
+        (LiteralValue::Number(x), TokenType::Plus, LiteralValue::Number(y)) => {
+            Ok(LiteralValue::Number(x + y))
+        }
+        (LiteralValue::Number(x), TokenType::Minus, LiteralValue::Number(y)) => {
+            Ok(LiteralValue::Number(x - y))
+        }
+        (LiteralValue::Number(x), TokenType::Slash, LiteralValue::Number(y)) => {
+            Ok(LiteralValue::Number(x / y))
+        }
+        (LiteralValue::Number(x), TokenType::Star, LiteralValue::Number(y)) => {
+            Ok(LiteralValue::Number(x * y))
+        }
+        (LiteralValue::Number(x), TokenType::StarStar, LiteralValue::Number(y)) => {
+            Ok(LiteralValue::Number(x.powf(*y)))
+        }
+        (LiteralValue::StringValue(s), TokenType::Plus, LiteralValue::Number(x)) => {
+            Ok(LiteralValue::StringValue(format!("{}{}", s, x)))
         }
+        (LiteralValue::StringValue(s), TokenType::Plus, LiteralValue::Int(x)) => {
+            Ok(LiteralValue::StringValue(format!("{}{}", s, x)))
+        }
+        (LiteralValue::StringValue(s1), TokenType::Plus, LiteralValue::StringValue(s2)) => {
+            Ok(LiteralValue::StringValue((*s1).clone() + s2))
+        }
+        (x, TokenType::BangEqual, y) => Ok(LiteralValue::from(x != y)),
+        (x, TokenType::EqualEqual, y) => Ok(LiteralValue::from(x == y)),
+
+        (LiteralValue::Number(x), op, LiteralValue::Number(y)) if is_relational(op) => {
+            Ok(LiteralValue::from(compare(op, x, y)))
+        }
+        (LiteralValue::StringValue(s1), op, LiteralValue::StringValue(s2)) if is_relational(op) => {
+            Ok(LiteralValue::from(compare(op, s1, s2)))
+        }
+        (x, _, y) => Err(format!(
+            "Line {}: {} is not defined for operands of type {} and {}",
+            operator.line_number,
+            operator.lexme,
+            x.as_ref(),
+            y.as_ref()
+        )),
+    }
+}
+
+// Exact integer arithmetic, falling back to floats for divisions that don't
+// come out even, negative powers, and overflows unless `overflow` makes those
+// an error. `None` for operators it doesn't cover.
+fn int_operation(
+    x: i64,
+    operator: &Token,
+    y: i64,
+    overflow: OverflowMode,
+) -> Result<Option<LiteralValue>, String> {
+    let op = operator.token_t;
+    let (exact, overflowed) = match op {
+        TokenType::Plus => (x.checked_add(y), true),
+        TokenType::Minus => (x.checked_sub(y), true),
+        TokenType::Star => (x.checked_mul(y), true),
+        TokenType::StarStar => (
+            u32::try_from(y).ok().and_then(|y| x.checked_pow(y)),
+            y >= 0 && !(-1..=1).contains(&x),
+        ),
+        // Only `i64::MIN / -1` overflows, other inexact divisions just aren't whole
+        TokenType::Slash => (
+            x.checked_rem(y)
+                .filter(|rem| *rem == 0)
+                .and(x.checked_div(y)),
+            y == -1,
+        ),
+        op if is_relational(op) => return Ok(Some(LiteralValue::from(compare(op, &x, &y)))),
+        _ => return Ok(None),
+    };
+
+    let value = match exact {
+        Some(value) => LiteralValue::Int(value),
+        None if overflowed && overflow == OverflowMode::Error => {
+            return Err(format!(
+                "Line {}: integer overflow in {} {} {}",
+                operator.line_number, x, operator.lexme, y
+            ));
+        }
+        None => {
+            let (x, y) = (x as f64, y as f64);
+            LiteralValue::Number(match op {
+                TokenType::Plus => x + y,
+                TokenType::Minus => x - y,
+                TokenType::Star => x * y,
+                TokenType::StarStar => x.powf(y),
+                _ => x / y,
+            })
+        }
+    };
+    Ok(Some(value))
+}
+
+fn is_relational(op: TokenType) -> bool {
+    matches!(
+        op,
+        TokenType::Less | TokenType::LessEqual | TokenType::Greater | TokenType::GreaterEqual
+    )
+}
+
+// Numbers compare by value, strings lexicographically by their bytes
+fn compare<T: PartialOrd + ?Sized>(op: TokenType, x: &T, y: &T) -> bool {
+    match op {
+        TokenType::Less => x < y,
+        TokenType::LessEqual => x <= y,
+        TokenType::Greater => x > y,
+        TokenType::GreaterEqual => x >= y,
+        _ => unreachable!("{} is not a relational operator", op),
     }
 }
 
@@ -377,12 +879,12 @@ impl std::fmt::Debug for Expr {
 }
 
 trait LiteralValueExt {
-    fn unwrap_as_string(&self) -> Cow<str>;
+    fn unwrap_as_string(&self) -> Cow<'_, str>;
     fn unwrap_as_f64(&self) -> f64;
 }
 
 impl LiteralValueExt for Option<lexer::LiteralValue> {
-    fn unwrap_as_string(&self) -> Cow<str> {
+    fn unwrap_as_string(&self) -> Cow<'_, str> {
         match self {
             Some(lexer::LiteralValue::StringValue(s)) => Cow::Borrowed(s),
             Some(lexer::LiteralValue::IdentifierValue(s)) => Cow::Borrowed(s),
@@ -392,7 +894,7 @@ impl LiteralValueExt for Option<lexer::LiteralValue> {
     fn unwrap_as_f64(&self) -> f64 {
         match self {
             Some(lexer::LiteralValue::IntValue(s)) => *s as f64,
-            Some(lexer::LiteralValue::FloatValue(s)) => *s as f64,
+            Some(lexer::LiteralValue::FloatValue(s)) => *s,
             _ => panic!("Could not unwrap as f32"),
         }
     }
@@ -402,7 +904,10 @@ impl From<Token> for LiteralValue {
     fn from(value: Token) -> Self {
         match value.token_t {
             TokenType::String => Self::StringValue(value.literal.unwrap_as_string().to_string()),
-            TokenType::Number => Self::Number(value.literal.unwrap_as_f64()),
+            TokenType::Number => match value.literal {
+                Some(lexer::LiteralValue::IntValue(x)) => Self::Int(x),
+                literal => Self::Number(literal.unwrap_as_f64()),
+            },
 
             TokenType::False => Self::False,
             TokenType::True => Self::True,
@@ -422,12 +927,10 @@ impl AsRef<str> for LiteralValue {
     fn as_ref(&self) -> &str {
         match self {
             &LiteralValue::StringValue(_) => "String",
-            &LiteralValue::Number(_) => "Number",
-            &LiteralValue::Callable {
-                name: _,
-                arity: _,
-                fun: _,
-            } => "Callable",
+            &LiteralValue::Number(_) | &LiteralValue::Int(_) => "Number",
+            &LiteralValue::Callable { .. } => "Callable",
+            &LiteralValue::Array(_) => "Array",
+            &LiteralValue::Map(_) => "Map",
             &LiteralValue::True | &LiteralValue::False => "Boolean",
             &LiteralValue::Nil => "nil",
         }
@@ -454,6 +957,38 @@ impl std::fmt::Display for Expr {
                 operator,
                 right,
             } => format!("({} {} {})", operator, left, right),
+            Self::Array { elements } => format!(
+                "(array {})",
+                elements
+                    .iter()
+                    .map(|element| element.to_string())
+                    .collect::<Vec<String>>()
+                    .join(" ")
+            ),
+            Self::Map { entries } => format!(
+                "(map {})",
+                entries
+                    .iter()
+                    .map(|(key, value)| format!("{key}: {value}"))
+                    .collect::<Vec<String>>()
+                    .join(" ")
+            ),
+            Self::Get { object, name } => format!("(get {object} {})", name.lexme),
+            Self::Index {
+                object,
+                bracket: _,
+                index,
+            } => format!("(index {object} {index})"),
+            Self::SetIndex {
+                object,
+                bracket: _,
+                index,
+                operator,
+                value,
+            } => match operator {
+                Some(operator) => format!("((index {object} {index}) {}= {value})", operator.lexme),
+                None => format!("((index {object} {index}) = {value})"),
+            },
             Self::Assign { name, value } => format!("({name} = {value})"),
             Self::Binary {
                 left,
@@ -461,8 +996,15 @@ impl std::fmt::Display for Expr {
                 right,
             } => format!("({} {} {})", operator.lexme, left, right),
 
-            Self::Grouping { expression } => format!("(group {})", expression.as_ref()),
-            Self::Literal { value } => format!("{}", value),
+            Self::Grouping { expression } => {
+                // `((x))` renders like `(x)`, evaluation still goes through every level
+                let mut inner = expression.as_ref();
+                while let Expr::Grouping { expression } = inner {
+                    inner = expression;
+                }
+                format!("(group {})", inner)
+            }
+            Self::Literal { value, .. } => format!("{}", value),
             Self::Unary { operator, right } => {
                 let operator_str = &operator.lexme;
                 // let right_str = (*right).to_string();
@@ -477,23 +1019,28 @@ impl std::fmt::Display for Expr {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::lexer::Span;
     use std::collections::HashMap;
 
     #[test]
-    fn expr_is_hashable() {
+    // Side tables such as `Interpreter::locals` key expressions by address
+    fn expr_keys_by_address() {
         let mut map = HashMap::new();
         let minus_token = Token {
             token_t: TokenType::Minus,
             lexme: "-".to_string(),
             literal: None,
             line_number: 0,
+            span: Span::default(),
         };
         let one_two_three = Expr::Literal {
             value: LiteralValue::Number(123.0),
+            span: None,
         };
         let group = Expr::Grouping {
             expression: Box::new(Expr::Literal {
                 value: LiteralValue::Number(45.67),
+                span: None,
             }),
         };
         let multi = Token {
@@ -501,6 +1048,7 @@ mod tests {
             lexme: "*".to_string(),
             literal: None,
             line_number: 0,
+            span: Span::default(),
         };
         let expr = Expr::Binary {
             left: Box::from(Expr::Unary {
@@ -511,8 +1059,8 @@ mod tests {
             right: Box::from(group),
         };
         let expr = std::rc::Rc::new(expr);
-        map.insert(expr.clone(), 2);
-        match map.get(&expr) {
+        map.insert(Rc::as_ptr(&expr), 2);
+        match map.get(&Rc::as_ptr(&expr)) {
             Some(_) => (),
             None => panic!("Unable to retrieve value"),
         }
@@ -522,13 +1070,16 @@ mod tests {
             lexme: "-".to_string(),
             literal: None,
             line_number: 0,
+            span: Span::default(),
         };
         let one_two_three = Expr::Literal {
             value: LiteralValue::Number(123.0),
+            span: None,
         };
         let group = Expr::Grouping {
             expression: Box::new(Expr::Literal {
                 value: LiteralValue::Number(45.67),
+                span: None,
             }),
         };
         let multi = Token {
@@ -536,8 +1087,9 @@ mod tests {
             lexme: "*".to_string(),
             literal: None,
             line_number: 0,
+            span: Span::default(),
         };
-        let expr = Expr::Binary {
+        let other = Expr::Binary {
             left: Box::from(Expr::Unary {
                 operator: minus_token,
                 right: Box::from(one_two_three),
@@ -546,12 +1098,73 @@ mod tests {
             right: Box::from(group),
         };
 
-        match map.get(&expr) {
+        // An equal copy elsewhere is a different key
+        assert!(*expr == other);
+        match map.get(&(&other as *const Expr)) {
             None => (),
             Some(_) => panic!("Test"),
         }
     }
 
+    #[test]
+    fn whole_numbers_key_like_ints() {
+        let key = |value| HashableValue::from_value(&value).unwrap();
+        assert!(key(LiteralValue::Number(1.0)) == key(LiteralValue::Int(1)));
+        assert!(key(LiteralValue::Number(-0.0)) == key(LiteralValue::Int(0)));
+        assert!(key(LiteralValue::Number(1.5)) == HashableValue::Number(1.5_f64.to_bits()));
+        assert!(key(LiteralValue::Number(f64::NAN)) == key(LiteralValue::Number(f64::NAN)));
+        for x in [f64::INFINITY, 2f64.powi(63), f64::MAX] {
+            assert!(key(LiteralValue::Number(x)) == HashableValue::Number(x.to_bits()));
+        }
+        assert!(key(LiteralValue::Number(-(2f64.powi(63)))) == key(LiteralValue::Int(i64::MIN)));
+    }
+
+    #[test]
+    fn truthiness_of_each_variant() {
+        let array = |elements| LiteralValue::Array(Rc::new(RefCell::new(elements)));
+        let map = |entries| LiteralValue::Map(Rc::new(RefCell::new(entries)));
+
+        let truthy = [
+            LiteralValue::Int(-1),
+            LiteralValue::Number(0.5),
+            LiteralValue::Number(f64::NAN),
+            LiteralValue::StringValue("0".into()),
+            LiteralValue::True,
+            array(vec![LiteralValue::Nil]),
+            map(vec![(HashableValue::Int(1), LiteralValue::Nil)]),
+        ];
+        let falsy = [
+            LiteralValue::Int(0),
+            LiteralValue::Number(0.0),
+            LiteralValue::Number(-0.0),
+            LiteralValue::StringValue(String::new()),
+            LiteralValue::False,
+            LiteralValue::Nil,
+            array(vec![]),
+            map(vec![]),
+        ];
+        for value in &truthy {
+            assert!(value.is_truthy() && !value.is_falsy(), "{value}");
+            assert_eq!(value.truthiness(), Ok(true));
+        }
+        for value in &falsy {
+            assert!(!value.is_truthy() && value.is_falsy(), "{value}");
+            assert_eq!(value.truthiness(), Ok(false));
+        }
+
+        let callable = LiteralValue::Callable {
+            name: "f".into(),
+            min_arity: 0,
+            arity: 0,
+            param_names: vec![],
+            fun: Rc::new(|_: &[LiteralValue]| Ok(LiteralValue::Nil)),
+            scoped: None,
+        };
+        assert!(callable.is_truthy() && !callable.is_falsy());
+        let err = callable.truthiness().unwrap_err();
+        assert!(err.contains("Cannot use callable f"), "{err}");
+    }
+
     #[test]
     fn pretty_print_ast() {
         let minus_token = Token {
@@ -559,13 +1172,16 @@ mod tests {
             lexme: "-".to_string(),
             literal: None,
             line_number: 0,
+            span: Span::default(),
         };
         let one_two_three = Expr::Literal {
             value: LiteralValue::Number(123.0),
+            span: None,
         };
         let group = Expr::Grouping {
             expression: Box::new(Expr::Literal {
                 value: LiteralValue::Number(45.67),
+                span: None,
             }),
         };
         let multi = Token {
@@ -573,6 +1189,7 @@ mod tests {
             lexme: "*".to_string(),
             literal: None,
             line_number: 0,
+            span: Span::default(),
         };
         let ast = Expr::Binary {
             left: Box::from(Expr::Unary {
@@ -585,4 +1202,154 @@ mod tests {
         let res = ast.to_string();
         assert_eq!(res, "(* (- 123) (group 45.67))");
     }
+
+    #[test]
+    fn nested_groupings_render_once() {
+        let tokens = crate::lexer::Lexer::new("((1 + 2));")
+            .scan_tokens()
+            .unwrap();
+        let stmts = crate::parser::Parser::new(tokens.to_vec()).parse().unwrap();
+        let Stmt::Expression { expression: expr } = stmts[0].as_ref() else {
+            panic!("Expected an expression statement");
+        };
+        assert_eq!(expr.to_string(), "(group (+ 1 2))");
+
+        let environment = Rc::new(RefCell::new(Environment::new()));
+        assert!(expr.evaluate(environment).unwrap() == LiteralValue::Int(3));
+    }
+
+    // Counts every expression node below and including the visited one
+    struct NodeCounter;
+
+    impl ExprVisitor<usize> for NodeCounter {
+        fn visit_anon_function(&mut self, _: &Token, _: &[Token], _: &[Box<Stmt>]) -> usize {
+            1
+        }
+        fn visit_array(&mut self, elements: &[Expr]) -> usize {
+            1 + elements.iter().map(|e| e.accept(self)).sum::<usize>()
+        }
+        fn visit_assign(&mut self, _: &Expr, _: &Token, value: &Expr) -> usize {
+            1 + value.accept(self)
+        }
+        fn visit_binary(&mut self, left: &Expr, _: &Token, right: &Expr) -> usize {
+            1 + left.accept(self) + right.accept(self)
+        }
+        fn visit_call(&mut self, callee: &Expr, _: &Token, arguments: &[Expr]) -> usize {
+            1 + callee.accept(self) + arguments.iter().map(|a| a.accept(self)).sum::<usize>()
+        }
+        fn visit_get(&mut self, object: &Expr, _: &Token) -> usize {
+            1 + object.accept(self)
+        }
+        fn visit_grouping(&mut self, expression: &Expr) -> usize {
+            1 + expression.accept(self)
+        }
+        fn visit_index(&mut self, object: &Expr, _: &Token, index: &Expr) -> usize {
+            1 + object.accept(self) + index.accept(self)
+        }
+        fn visit_set_index(
+            &mut self,
+            object: &Expr,
+            _: &Token,
+            index: &Expr,
+            _: Option<&Token>,
+            value: &Expr,
+        ) -> usize {
+            1 + object.accept(self) + index.accept(self) + value.accept(self)
+        }
+        fn visit_logical(&mut self, left: &Expr, _: &Token, right: &Expr) -> usize {
+            1 + left.accept(self) + right.accept(self)
+        }
+        fn visit_literal(&mut self, _: &LiteralValue) -> usize {
+            1
+        }
+        fn visit_map(&mut self, entries: &[(Expr, Expr)]) -> usize {
+            1 + entries
+                .iter()
+                .map(|(k, v)| k.accept(self) + v.accept(self))
+                .sum::<usize>()
+        }
+        fn visit_unary(&mut self, _: &Token, right: &Expr) -> usize {
+            1 + right.accept(self)
+        }
+        fn visit_variable(&mut self, _: &Expr, _: &Token) -> usize {
+            1
+        }
+    }
+
+    #[test]
+    fn visitor_counts_nodes() {
+        let source = "-123 * (45.67) + f(x, [1, 2]);";
+        let tokens = crate::lexer::Lexer::new(source).scan_tokens().unwrap();
+        let stmts = crate::parser::Parser::new(tokens.to_vec()).parse().unwrap();
+        let Stmt::Expression { expression } = stmts[0].as_ref() else {
+            panic!("Expected an expression statement");
+        };
+
+        // + * - 123 (group 45.67) call f x [1, 2]
+        assert_eq!(expression.accept(&mut NodeCounter), 12);
+    }
+
+    mod relational {
+        use super::*;
+
+        const OPERATORS: [(TokenType, &str); 6] = [
+            (TokenType::Less, "<"),
+            (TokenType::LessEqual, "<="),
+            (TokenType::Greater, ">"),
+            (TokenType::GreaterEqual, ">="),
+            (TokenType::EqualEqual, "=="),
+            (TokenType::BangEqual, "!="),
+        ];
+
+        fn apply(left: LiteralValue, op: (TokenType, &str), right: LiteralValue) -> bool {
+            let operator = Token::new(op.0, op.1.to_string(), None, 0, Span::default());
+            binary_operation(&left, &operator, &right, OverflowMode::Promote).unwrap()
+                == LiteralValue::True
+        }
+
+        // Expected results for `lesser op greater`, `equal op equal` and `greater op lesser`
+        const EXPECTED: [(bool, bool, bool); 6] = [
+            (true, false, false),
+            (true, true, false),
+            (false, false, true),
+            (false, true, true),
+            (false, true, false),
+            (true, false, true),
+        ];
+
+        #[test]
+        fn numbers() {
+            let num = LiteralValue::Number;
+            for (op, expected) in OPERATORS.into_iter().zip(EXPECTED) {
+                assert_eq!(apply(num(1.0), op, num(2.0)), expected.0, "1 {} 2", op.1);
+                assert_eq!(apply(num(2.0), op, num(2.0)), expected.1, "2 {} 2", op.1);
+                assert_eq!(apply(num(2.0), op, num(1.0)), expected.2, "2 {} 1", op.1);
+            }
+        }
+
+        #[test]
+        fn strings() {
+            let string = |s: &str| LiteralValue::StringValue(s.to_string());
+            for (op, expected) in OPERATORS.into_iter().zip(EXPECTED) {
+                assert_eq!(
+                    apply(string("a"), op, string("b")),
+                    expected.0,
+                    "a {} b",
+                    op.1
+                );
+                assert_eq!(
+                    apply(string("b"), op, string("b")),
+                    expected.1,
+                    "b {} b",
+                    op.1
+                );
+                assert_eq!(
+                    apply(string("b"), op, string("a")),
+                    expected.2,
+                    "b {} a",
+                    op.1
+                );
+            }
+        }
+    }
 }
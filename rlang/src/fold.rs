@@ -0,0 +1,303 @@
+use crate::{
+    environment::Environment, expr::Expr, interpreter::OverflowMode, lexer::TokenType, stmt::Stmt,
+};
+use std::{cell::RefCell, rc::Rc};
+
+// Replaces every `Binary`, `Unary` and `Grouping` whose operands are all
+// literals with the literal it evaluates to. Subtrees that fail to evaluate
+// are left alone so they still report their error at runtime. An `if` or
+// `while` whose condition folds to a literal is then replaced by the branch
+// that runs, or an empty block when none does.
+pub fn fold_stmts(stmts: &mut [Box<Stmt>]) {
+    for stmt in stmts {
+        fold_stmt(stmt);
+    }
+}
+
+fn fold_stmt(stmt: &mut Stmt) {
+    match stmt {
+        Stmt::Expression { expression } | Stmt::Print { expression } => fold_expr(expression),
+        Stmt::PrintFormat { arguments, .. } => arguments.iter_mut().for_each(fold_expr),
+        Stmt::Var {
+            name: _,
+            initializer,
+        }
+        | Stmt::VarArray { initializer, .. } => fold_expr(initializer),
+        Stmt::VarGroup { declarations } => fold_stmts(declarations),
+        Stmt::Block { statements } => fold_stmts(statements),
+        Stmt::IfStmt {
+            predicate,
+            then,
+            r#else,
+        } => {
+            fold_expr(predicate);
+            fold_stmt(then);
+            if let Some(r#else) = r#else {
+                fold_stmt(r#else);
+            }
+
+            if let Some(taken) = literal_truthiness(predicate) {
+                let branch = if taken { Some(then) } else { r#else.as_mut() };
+                *stmt = branch.map_or_else(empty_block, take_stmt);
+            }
+        }
+        Stmt::WhileStmt {
+            condition,
+            body,
+            increment,
+            r#else,
+            label: _,
+        } => {
+            fold_expr(condition);
+            fold_stmt(body);
+            if let Some(increment) = increment {
+                fold_expr(increment);
+            }
+            if let Some(r#else) = r#else {
+                fold_stmt(r#else);
+            }
+
+            // A loop that never starts only runs its `else`
+            if literal_truthiness(condition) == Some(false) {
+                *stmt = r#else.as_mut().map_or_else(empty_block, take_stmt);
+            }
+        }
+        Stmt::Function {
+            name: _,
+            params,
+            body,
+        } => {
+            for default in Rc::make_mut(params)
+                .iter_mut()
+                .filter_map(|(_, default)| default.as_mut())
+            {
+                fold_expr(default);
+            }
+            fold_stmts(Rc::make_mut(body));
+        }
+        Stmt::ReturnStmt { keyword: _, value } => {
+            if let Some(value) = value {
+                fold_expr(value);
+            }
+        }
+        Stmt::SwitchStmt { subject, cases } => {
+            fold_expr(subject);
+            for (value, stmts) in cases {
+                if let Some(value) = value {
+                    fold_expr(value);
+                }
+                fold_stmts(stmts);
+            }
+        }
+        Stmt::TryStmt {
+            body,
+            name: _,
+            catch,
+        } => {
+            fold_stmt(body);
+            fold_stmt(catch);
+        }
+        Stmt::ThrowStmt { keyword: _, value } => fold_expr(value),
+        Stmt::BreakStmt { .. } | Stmt::ContinueStmt { .. } | Stmt::Import { .. } => (),
+    }
+}
+
+fn fold_expr(expr: &mut Expr) {
+    let foldable = match expr {
+        Expr::Binary {
+            left,
+            operator,
+            right,
+        } => {
+            fold_expr(left);
+            fold_expr(right);
+            is_literal(left) && is_literal(right) && !divides_by_zero(operator.token_t, right)
+        }
+        Expr::Unary { operator: _, right } => {
+            fold_expr(right);
+            is_literal(right)
+        }
+        Expr::Grouping { expression } => {
+            fold_expr(expression);
+            is_literal(expression)
+        }
+        Expr::AnonFunction { body, .. } => {
+            fold_stmts(Rc::make_mut(body));
+            false
+        }
+        Expr::Array { elements } => {
+            elements.iter_mut().for_each(fold_expr);
+            false
+        }
+        Expr::Map { entries } => {
+            for (key, value) in entries {
+                fold_expr(key);
+                fold_expr(value);
+            }
+            false
+        }
+        Expr::Assign { name: _, value } => {
+            fold_expr(value);
+            false
+        }
+        Expr::Call {
+            callee,
+            paren: _,
+            arguments,
+        } => {
+            fold_expr(callee);
+            arguments.iter_mut().for_each(fold_expr);
+            false
+        }
+        Expr::Get { object, name: _ } => {
+            fold_expr(object);
+            false
+        }
+        Expr::Index {
+            object,
+            bracket: _,
+            index,
+        } => {
+            fold_expr(object);
+            fold_expr(index);
+            false
+        }
+        Expr::SetIndex {
+            object,
+            bracket: _,
+            index,
+            operator: _,
+            value,
+        } => {
+            fold_expr(object);
+            fold_expr(index);
+            fold_expr(value);
+            false
+        }
+        Expr::Logical {
+            left,
+            operator: _,
+            right,
+        } => {
+            fold_expr(left);
+            fold_expr(right);
+            false
+        }
+        Expr::Literal { .. } | Expr::Variable { .. } => false,
+    };
+
+    if foldable {
+        // Literal operands never look anything up, so an empty scope is enough.
+        // Overflows fail here so they are left to whichever mode runs the code.
+        let scope = Environment::new();
+        scope.config().set_int_overflow(OverflowMode::Error);
+        let environment = Rc::new(RefCell::new(scope));
+        if let Ok(value) = expr.evaluate(environment) {
+            let span = expr.span();
+            *expr = Expr::Literal { value, span };
+        }
+    }
+}
+
+fn literal_truthiness(expr: &Expr) -> Option<bool> {
+    match expr {
+        Expr::Literal { value, .. } => value.truthiness().ok(),
+        _ => None,
+    }
+}
+
+fn take_stmt(stmt: &mut Box<Stmt>) -> Stmt {
+    std::mem::replace(stmt.as_mut(), empty_block())
+}
+
+fn empty_block() -> Stmt {
+    Stmt::Block { statements: vec![] }
+}
+
+fn is_literal(expr: &Expr) -> bool {
+    matches!(expr, Expr::Literal { .. })
+}
+
+// Kept for runtime so division by zero stays an error wherever it would be one
+fn divides_by_zero(operator: TokenType, right: &Expr) -> bool {
+    let divisor = match right {
+        Expr::Literal { value, .. } => value.as_f64(),
+        _ => None,
+    };
+    operator == TokenType::Slash && divisor == Some(0.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{interpreter::Interpreter, lexer::Lexer, parser::Parser};
+
+    fn folded(source: &str) -> Vec<Stmt> {
+        let tokens = Lexer::new(source).scan_tokens().unwrap();
+        let mut stmts = Parser::new(tokens.to_vec()).parse().unwrap();
+        fold_stmts(&mut stmts);
+        stmts.into_iter().map(|stmt| *stmt).collect()
+    }
+
+    #[test]
+    fn folds_literal_subtrees() {
+        let stmts = folded("print 2 + 3 * 4; print -(1 + 2) * x; print !(1 > 2);");
+        assert_eq!(stmts[0].to_string(), "(print 14)");
+        assert_eq!(
+            stmts[1].to_string(),
+            "(print (* -3 (var Identifier x None)))"
+        );
+        assert_eq!(stmts[2].to_string(), "(print true)");
+    }
+
+    #[test]
+    fn drops_dead_branches() {
+        let stmts = folded("if (1 > 2) { print 1; } else { print 2; }");
+        assert_eq!(stmts[0].to_string(), "(block (print 2))");
+
+        let stmts = folded(
+            "
+            if (\"\") print 1;
+            if (true) print 3;
+            if (x) print 4;
+            while (false) { print 5; }
+            while (0) { print 6; } else { print 7; }
+            ",
+        );
+        assert_eq!(stmts[0].to_string(), "(block )");
+        assert_eq!(stmts[1].to_string(), "(print 3)");
+        assert_eq!(
+            stmts[2].to_string(),
+            "(if (var Identifier x None) (print 4))"
+        );
+        assert_eq!(stmts[3].to_string(), "(block )");
+        assert_eq!(stmts[4].to_string(), "(block (print 7))");
+    }
+
+    #[test]
+    fn leaves_errors_for_runtime() {
+        let stmts = folded("print 1 / 0; print -\"a\"; print 9223372036854775807 + 1;");
+        assert_eq!(stmts[0].to_string(), "(print (/ 1 0))");
+        assert_eq!(stmts[1].to_string(), "(print (- a))");
+        assert_eq!(stmts[2].to_string(), "(print (+ 9223372036854775807 1))");
+
+        let mut interpreter = Interpreter::new();
+        assert!(interpreter.interpret(vec![&stmts[1]]).is_err());
+    }
+
+    #[test]
+    fn folding_keeps_behavior() {
+        let stmts = folded(
+            "
+            var x = 2;
+            fn f(a = 1 + 1) { return a * (3 - 1); }
+            assert_eq(2 + 3 * 4, 14);
+            assert_eq(\"a\" + 1, \"a1\");
+            assert_eq(f(), 4);
+            assert_eq((1 + 2) * x, 6);
+            ",
+        );
+        let mut interpreter = Interpreter::new();
+        interpreter.interpret(stmts.iter().collect()).unwrap();
+    }
+}
@@ -1,65 +1,257 @@
+use crate::lexer::Token;
 use crate::{
-    environment::Environment,
-    expr::{Expr, LiteralValue},
-    lexer::Token,
+    environment::{Environment, LogSink, RunConfig},
+    expr::{LiteralValue, NativeFn},
+    natives,
     stmt::Stmt,
 };
-use std::{cell::RefCell, collections::HashMap, rc::Rc, time::SystemTime};
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    io::Write,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
 
 pub struct Interpreter {
     pub specials: Rc<RefCell<Environment>>,
     pub environment: Rc<RefCell<Environment>>,
-    pub locals: Rc<RefCell<HashMap<Rc<Expr>, usize>>>,
+    loop_signal: Option<LoopSignal>,
+    // Files being run, the innermost import last
+    files: Vec<PathBuf>,
+    // Set once an error has been prefixed with the imported file it came from
+    error_located: bool,
+    // `print` is a variadic native instead of a statement
+    print_function: bool,
+}
+
+// What integer arithmetic does when the exact result doesn't fit an Int
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub enum OverflowMode {
+    // Carry on with the float result
+    #[default]
+    Promote,
+    // Fail with an "integer overflow" error
+    Error,
 }
 
-fn clock_impl(_args: &Vec<LiteralValue>) -> LiteralValue {
-    let now = SystemTime::now()
-        .duration_since(SystemTime::UNIX_EPOCH)
-        .expect("Could not get system time")
-        .as_secs_f64();
-    LiteralValue::Number(now)
+// The label is the targeted loop, unlabeled signals target the innermost one
+enum LoopSignal {
+    Break(Option<String>),
+    Continue(Option<String>),
 }
 
 impl Interpreter {
     pub fn new() -> Self {
         let mut specials = Environment::new();
-        specials.define("clock".into(), LiteralValue::Callable {
-            name: "clock".into(),
-            arity: 0,
-            fun: Rc::new(clock_impl),
-        });
+        natives::define_natives(&mut specials);
+
         Self {
             specials: Rc::new(RefCell::new(Environment::new())),
             // environment: Rc::new(RefCell::new(Environment::new())),
             environment: Rc::new(RefCell::new(specials)),
-            locals: Rc::new(RefCell::new(HashMap::new())),
+            loop_signal: None,
+            files: vec![],
+            error_located: false,
+            print_function: false,
         }
     }
 
+    // Starts out with `globals` defined next to the natives, which they can
+    // replace. Like other definitions they are gone after a `reset`.
+    pub fn with_globals(globals: HashMap<String, LiteralValue>) -> Self {
+        let mut interpreter = Self::new();
+        for (name, value) in globals {
+            interpreter.define_global(&name, value);
+        }
+        interpreter
+    }
+
+    // Sends every `print` to `output` instead of stdout, including the ones
+    // inside functions, since their scopes share the globals' config
+    pub fn with_output<W: Write + 'static>(output: W) -> Self {
+        let interpreter = Self::new();
+        interpreter
+            .config()
+            .set_output(Rc::new(RefCell::new(output)));
+        interpreter
+    }
+
     fn for_closure(parent: Rc<RefCell<Environment>>) -> Self {
-        let environment = Rc::new(RefCell::new(Environment::new()));
-        environment.borrow_mut().enclosing = Some(parent);
+        let environment = Rc::new(RefCell::new(Environment::enclosed(parent)));
 
         Self {
             specials: Rc::new(RefCell::new(Environment::new())),
             environment,
-            locals: Rc::new(RefCell::new(HashMap::new())),
+            loop_signal: None,
+            files: vec![],
+            error_located: false,
+            print_function: false,
+        }
+    }
+
+    // Drops every user definition while keeping the natives `new` registers
+    // and the run config
+    pub fn reset(&mut self) {
+        let mut globals = Environment::with_config(self.config());
+        natives::define_natives(&mut globals);
+        if self.print_function {
+            natives::define_print(&mut globals);
         }
+
+        self.specials = Rc::new(RefCell::new(Environment::new()));
+        self.environment = Rc::new(RefCell::new(globals));
+        self.loop_signal = None;
+        self.config().set_last_print(None);
+    }
+
+    // What was printed last, inside function bodies too
+    pub fn last_print(&self) -> Option<LiteralValue> {
+        self.config().last_print()
+    }
+
+    // Makes `print` an ordinary function, `print("a", 1)`. Code parsed for this
+    // interpreter with `parse_program_for` can no longer use `print x;`.
+    pub fn set_print_function(&mut self) {
+        self.print_function = true;
+        natives::define_print(&mut self.environment.borrow_mut());
+    }
+
+    pub fn print_function(&self) -> bool {
+        self.print_function
+    }
+
+    // The settings shared by the globals and every scope below them
+    pub fn config(&self) -> Rc<RunConfig> {
+        self.environment.borrow().config().clone()
+    }
+
+    // Receives the messages scripts pass to `log`
+    pub fn set_log_sink(&mut self, sink: LogSink) {
+        self.config().set_log_sink(sink);
+    }
+
+    // Applies to functions too, their scopes share the config
+    pub fn set_int_overflow(&mut self, mode: OverflowMode) {
+        self.config().set_int_overflow(mode);
+    }
+
+    // Off by default, where `1 and 2` is `2`. On, it is `true`.
+    pub fn set_strict_bool(&mut self, strict: bool) {
+        self.config().set_strict_bool(strict);
+    }
+
+    pub fn define_global(&mut self, name: &str, value: LiteralValue) {
+        self.environment
+            .borrow_mut()
+            .define_top_level(name.to_string(), value);
+    }
+
+    // Runs library code written in rl against this interpreter's globals so
+    // its definitions are visible to the scripts run afterwards
+    pub fn run_prelude(&mut self, source: &str) -> Result<(), String> {
+        crate::run(self, source).map_err(|msg| format!("Prelude failed: {msg}"))
     }
 
     pub fn for_anon(parent: Rc<RefCell<Environment>>) -> Self {
-        let mut env = Environment::new();
-        env.enclosing = Some(parent);
         Self {
             specials: Rc::new(RefCell::new(Environment::new())),
-            environment: Rc::new(RefCell::new(env)),
-            locals: Rc::new(RefCell::new(HashMap::new())),
+            environment: Rc::new(RefCell::new(Environment::enclosed(parent))),
+            loop_signal: None,
+            files: vec![],
+            error_located: false,
+            print_function: false,
+        }
+    }
+
+    // Runs the statements of the file at `path`, imports in them resolve relative to it
+    pub fn interpret_file(&mut self, path: &Path, stmts: Vec<&Stmt>) -> Result<(), String> {
+        let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        if self.files.is_empty() {
+            self.error_located = false;
+        }
+        self.files.push(path);
+        let result = self.interpret(stmts);
+        self.files.pop();
+        result
+    }
+
+    fn import(&mut self, keyword: &Token, path: &str) -> Result<(), String> {
+        let base = self
+            .files
+            .last()
+            .and_then(|file| file.parent())
+            .map(Path::to_path_buf)
+            .unwrap_or_default();
+        let import_error = |err: std::io::Error| {
+            format!(
+                "Line {}: could not import '{}': {}",
+                keyword.line_number, path, err
+            )
+        };
+        let full_path = base.join(path).canonicalize().map_err(import_error)?;
+        if self.files.contains(&full_path) {
+            return Err(format!(
+                "Line {}: import cycle, '{}' is already being imported",
+                keyword.line_number, path
+            ));
+        }
+
+        let contents = std::fs::read_to_string(&full_path).map_err(import_error)?;
+        let result = crate::parse_program_for(self, &contents).and_then(|stmts| {
+            self.interpret_file(&full_path, stmts.iter().map(|b| b.as_ref()).collect())
+        });
+
+        // Only the innermost file names the error, the importers just pass it on
+        result.map_err(|err| {
+            if self.error_located {
+                err
+            } else {
+                self.error_located = true;
+                locate_error(path, &err)
+            }
+        })
+    }
+
+    fn is_returning(&self) -> bool {
+        self.specials.borrow().get("return").is_some()
+    }
+
+    // Like `interpret`, but also hands back the value of the last top-level
+    // expression statement that ran
+    pub fn interpret_last(&mut self, stmts: Vec<&Stmt>) -> Result<Option<LiteralValue>, String> {
+        let mut last = None;
+        for stmt in stmts {
+            if self.is_returning() {
+                break;
+            }
+
+            match stmt {
+                Stmt::Expression { expression } => {
+                    last = Some(expression.evaluate(self.environment.clone())?);
+                }
+                _ => self.interpret(vec![stmt])?,
+            }
         }
+
+        Ok(last)
     }
 
     pub fn interpret(&mut self, stmts: Vec<&Stmt>) -> Result<(), String> {
+        let stats = self.environment.borrow().config().stats();
         for stmt in stmts {
-            match stmt.clone() {
+            // Skip the rest of the statements once a break, continue or return fired
+            if self.loop_signal.is_some() || self.is_returning() {
+                break;
+            }
+            if let Some(stats) = &stats {
+                stats.statements.set(stats.statements.get() + 1);
+                if matches!(stmt, Stmt::Function { .. }) {
+                    stats.functions.set(stats.functions.get() + 1);
+                }
+            }
+
+            match stmt {
                 Stmt::ReturnStmt { keyword: _, value } => {
                     let eval;
                     if let Some(value) = value {
@@ -74,101 +266,277 @@ impl Interpreter {
                 }
                 Stmt::Function { name, params, body } => {
                     let arity = params.len();
+                    let min_arity = params
+                        .iter()
+                        .take_while(|(_, default)| default.is_none())
+                        .count();
 
-                    let params: Vec<Token> = params.iter().map(|t| (*t).clone()).collect();
-                    let body: Vec<Box<Stmt>> = body.iter().map(|b| (*b).clone()).collect();
+                    let params = params.clone();
+                    let body = body.clone();
+                    let param_names = params.iter().map(|(p, _)| p.lexme.clone()).collect();
 
                     let name_clone = name.lexme.clone();
                     let parent_env = self.environment.clone();
-                    let fun_impl: Rc<dyn Fn(&Vec<LiteralValue>) -> LiteralValue> =
-                        Rc::new(move |args: &Vec<LiteralValue>| {
-                            let mut clos_int = Interpreter::for_closure(parent_env.clone());
-
-                            for (i, arg) in args.iter().enumerate() {
-                                clos_int
-                                    .environment
-                                    .borrow_mut()
-                                    .define(params[i].lexme.clone(), (*arg).clone());
-                            }
+                    let fun_impl: NativeFn = Rc::new(move |args: &[LiteralValue]| {
+                        let mut clos_int = Interpreter::for_closure(parent_env.clone());
 
-                            for i in 0..(body.len()) {
-                                clos_int
-                                    .interpret(vec![body[i].as_ref()])
-                                    .expect(&format!("Evaluating failed inside {}", name_clone));
-
-                                if let Some(value) = clos_int.specials.borrow().get("return") {
-                                    return value;
+                        for (i, (param, default)) in params.iter().enumerate() {
+                            let value = match (args.get(i), default) {
+                                (Some(arg), _) => arg.clone(),
+                                (None, Some(default)) => {
+                                    default.evaluate(clos_int.environment.clone())?
                                 }
+                                (None, None) => LiteralValue::Nil,
+                            };
+                            clos_int
+                                .environment
+                                .borrow_mut()
+                                .define(param.lexme.clone(), value);
+                        }
+
+                        for stmt in body.iter() {
+                            clos_int.interpret(vec![stmt.as_ref()]).map_err(|msg| {
+                                format!("Evaluating failed inside {}: {}", name_clone, msg)
+                            })?;
 
-                                // if let Stmt::ReturnStmt {
-                                //     keyword: _,
-                                //     value: _,
-                                // } = *body[i].clone()
-                                // {
-                                //     let value = clos_int
-                                //         .environment
-                                //         .borrow()
-                                //         .get("return")
-                                //         .unwrap_or(LiteralValue::Nil);
-                                //     return value;
-                                // }
+                            if let Some(value) = clos_int.specials.borrow().get("return") {
+                                return Ok(value);
                             }
 
-                            LiteralValue::Nil
-                        });
+                            // if let Stmt::ReturnStmt {
+                            //     keyword: _,
+                            //     value: _,
+                            // } = *body[i].clone()
+                            // {
+                            //     let value = clos_int
+                            //         .environment
+                            //         .borrow()
+                            //         .get("return")
+                            //         .unwrap_or(LiteralValue::Nil);
+                            //     return value;
+                            // }
+                        }
+
+                        Ok(LiteralValue::Nil)
+                    });
 
                     let callable = LiteralValue::Callable {
-                        name: name.to_string(),
+                        name: name.lexme.clone(),
+                        min_arity,
                         arity,
+                        param_names,
                         fun: fun_impl,
+                        scoped: None,
                     };
 
-                    self.environment.borrow_mut().define(name.lexme, callable);
+                    self.environment
+                        .borrow_mut()
+                        .define(name.lexme.clone(), callable);
                 }
-                Stmt::WhileStmt { condition, body } => {
-                    let mut flag = condition.evaluate(self.environment.clone())?;
+                Stmt::WhileStmt {
+                    condition,
+                    body,
+                    increment,
+                    r#else,
+                    label,
+                } => {
+                    let label = label.as_ref().map(|label| label.lexme.clone());
+                    let mut completed = true;
+                    while condition.evaluate(self.environment.clone())?.truthiness()? {
+                        self.interpret(vec![body])?;
+
+                        match self.loop_signal.take() {
+                            Some(LoopSignal::Break(target)) => {
+                                // Breaking an outer loop stops this one on the way out
+                                if target.is_some() && target != label {
+                                    self.loop_signal = Some(LoopSignal::Break(target));
+                                }
+                                completed = false;
+                                break;
+                            }
+                            Some(LoopSignal::Continue(target))
+                                if target.is_some() && target != label =>
+                            {
+                                self.loop_signal = Some(LoopSignal::Continue(target));
+                                completed = false;
+                                break;
+                            }
+                            Some(LoopSignal::Continue(_)) | None => (),
+                        }
+                        if self.is_returning() {
+                            completed = false;
+                            break;
+                        }
+
+                        if let Some(increment) = increment {
+                            increment.evaluate(self.environment.clone())?;
+                        }
+                    }
 
-                    let body = Rc::new(RefCell::new(*body));
-                    while flag.is_truthy() == LiteralValue::True {
-                        self.interpret(vec![&body.borrow()])?;
-                        flag = condition.evaluate(self.environment.clone())?;
+                    if let (true, Some(else_stmt)) = (completed, r#else) {
+                        self.interpret(vec![else_stmt])?;
                     }
                 }
+                Stmt::BreakStmt { keyword: _, label } => {
+                    self.loop_signal = Some(LoopSignal::Break(
+                        label.as_ref().map(|label| label.lexme.clone()),
+                    ))
+                }
+                Stmt::ContinueStmt { keyword: _, label } => {
+                    self.loop_signal = Some(LoopSignal::Continue(
+                        label.as_ref().map(|label| label.lexme.clone()),
+                    ))
+                }
                 Stmt::IfStmt {
                     predicate,
                     then,
                     r#else,
                 } => {
                     let truth_val = predicate.evaluate(self.environment.clone())?;
-                    if truth_val.is_truthy() == LiteralValue::True {
-                        self.interpret(vec![&then])?;
+                    if truth_val.truthiness()? {
+                        self.interpret(vec![then])?;
                     } else if let Some(else_stmt) = r#else {
-                        self.interpret(vec![&else_stmt])?;
+                        self.interpret(vec![else_stmt])?;
                     }
                 }
                 Stmt::Expression { expression } => {
                     expression.evaluate(self.environment.clone())?;
                 }
+                Stmt::SwitchStmt { subject, cases } => {
+                    let value = subject.evaluate(self.environment.clone())?;
+                    let mut start = None;
+                    for (i, (case, _)) in cases.iter().enumerate() {
+                        if let Some(case) = case
+                            && case.evaluate(self.environment.clone())? == value
+                        {
+                            start = Some(i);
+                            break;
+                        }
+                    }
+                    let start = start.or_else(|| cases.iter().position(|(case, _)| case.is_none()));
+
+                    if let Some(start) = start {
+                        // Falls through every later case until a `break` stops it
+                        let statements: Vec<&Stmt> = cases[start..]
+                            .iter()
+                            .flat_map(|(_, stmts)| stmts.iter().map(|b| b.as_ref()))
+                            .collect();
+
+                        let switch_result = self.interpret_scoped(Environment::new(), statements);
+                        if let Some(LoopSignal::Break(None)) = self.loop_signal {
+                            self.loop_signal = None;
+                        }
+                        switch_result?
+                    }
+                }
+                Stmt::TryStmt { body, name, catch } => {
+                    let config = self.config();
+                    config.take_thrown();
+                    if let Err(msg) = self.interpret(vec![body]) {
+                        // A thrown value is caught as thrown, other errors as reported
+                        let caught = config.take_thrown().unwrap_or(msg);
+                        let mut catch_env = Environment::new();
+                        catch_env.define(name.lexme.clone(), LiteralValue::StringValue(caught));
+                        self.interpret_scoped(catch_env, vec![catch])?;
+                    }
+                }
+                Stmt::ThrowStmt { keyword: _, value } => {
+                    let value = value.evaluate(self.environment.clone())?.to_string();
+                    self.config().set_thrown(value.clone());
+                    return Err(value);
+                }
+                Stmt::Import { keyword, path } => self.import(keyword, path)?,
                 Stmt::Print { expression } => {
                     let value = expression.evaluate(self.environment.clone())?;
-                    println!("{value}");
+                    self.environment.borrow().write_line(&value)?;
+                    self.config().set_last_print(Some(value));
+                }
+                Stmt::PrintFormat {
+                    keyword,
+                    format,
+                    arguments,
+                } => {
+                    let pieces: Vec<&str> = format.split("{}").collect();
+                    if pieces.len() - 1 != arguments.len() {
+                        return Err(format!(
+                            "Line {}: print format has {} placeholders but got {} arguments",
+                            keyword.line_number,
+                            pieces.len() - 1,
+                            arguments.len()
+                        ));
+                    }
+
+                    let mut line = pieces[0].to_string();
+                    for (arg, piece) in arguments.iter().zip(&pieces[1..]) {
+                        let value = arg.evaluate(self.environment.clone())?;
+                        line.push_str(&format!("{value}{piece}"));
+                    }
+                    self.environment.borrow().write_line(&line)?;
+                    self.config()
+                        .set_last_print(Some(LiteralValue::StringValue(line)));
                 }
                 Stmt::Var { name, initializer } => {
                     let value = initializer.evaluate(self.environment.clone())?;
 
-                    self.environment.borrow_mut().define(name.lexme, value);
+                    self.environment
+                        .borrow_mut()
+                        .define(name.lexme.clone(), value);
                 }
-                Stmt::Block { statements } => {
-                    let mut new_env = Environment::new();
-                    new_env.enclosing = Some(self.environment.clone());
+                Stmt::VarArray {
+                    bracket,
+                    names,
+                    rest,
+                    initializer,
+                } => {
+                    let elements = match initializer.evaluate(self.environment.clone())? {
+                        LiteralValue::Array(elements) => elements.borrow().clone(),
+                        other => {
+                            return Err(format!(
+                                "Line {}: cannot destructure {}, expected an Array",
+                                bracket.line_number,
+                                other.as_ref()
+                            ));
+                        }
+                    };
 
-                    let old_env = self.environment.clone();
-                    self.environment = Rc::new(new_env.into());
-                    let block_result =
-                        self.interpret(statements.iter().map(|b| b.as_ref()).collect());
-                    self.environment = old_env;
+                    let fits = match rest {
+                        Some(_) => elements.len() >= names.len(),
+                        None => elements.len() == names.len(),
+                    };
+                    if !fits {
+                        let expected = match rest {
+                            Some(_) => format!("at least {}", names.len()),
+                            None => names.len().to_string(),
+                        };
+                        return Err(format!(
+                            "Line {}: expected an Array of length {} to destructure, got length {}",
+                            bracket.line_number,
+                            expected,
+                            elements.len()
+                        ));
+                    }
 
-                    block_result?
+                    let mut elements = elements.into_iter();
+                    let mut environment = self.environment.borrow_mut();
+                    for (name, value) in names.iter().zip(elements.by_ref()) {
+                        environment.define(name.lexme.clone(), value);
+                    }
+                    if let Some(rest) = rest {
+                        let rest_values = elements.collect();
+                        environment.define(
+                            rest.lexme.clone(),
+                            LiteralValue::Array(Rc::new(RefCell::new(rest_values))),
+                        );
+                    }
+                }
+                Stmt::VarGroup { declarations } => {
+                    self.interpret(declarations.iter().map(|b| b.as_ref()).collect())?;
+                }
+                Stmt::Block { statements } => {
+                    self.interpret_scoped(
+                        Environment::new(),
+                        statements.iter().map(|b| b.as_ref()).collect(),
+                    )?;
                 }
             };
         }
@@ -176,7 +544,58 @@ impl Interpreter {
         Ok(())
     }
 
-    pub fn resolve(&mut self, _expr: &Expr, _steps: usize) -> Result<(), String> {
-        todo!()
+    // Runs `stmts` with `scope` nested in the current environment. The guard
+    // puts the outer environment back however `interpret` exits, so an error
+    // or a panic inside the scope can't leave its variables visible afterwards.
+    fn interpret_scoped(
+        &mut self,
+        mut scope: Environment,
+        stmts: Vec<&Stmt>,
+    ) -> Result<(), String> {
+        scope.set_enclosing(self.environment.clone());
+        let outer = std::mem::replace(&mut self.environment, Rc::new(scope.into()));
+        let guard = ScopeGuard {
+            interpreter: self,
+            outer: Some(outer),
+        };
+        guard.interpreter.interpret(stmts)
+    }
+}
+
+struct ScopeGuard<'a> {
+    interpreter: &'a mut Interpreter,
+    outer: Option<Rc<RefCell<Environment>>>,
+}
+
+impl Drop for ScopeGuard<'_> {
+    fn drop(&mut self) {
+        if let Some(outer) = self.outer.take() {
+            self.interpreter.environment = outer;
+        }
+    }
+}
+
+// Turns "Line 4: msg" into "lib.rl:4: msg", and prefixes errors without a line
+// with just the file
+fn locate_error(file: &str, err: &str) -> String {
+    err.lines()
+        .map(|line| {
+            let numbered = line
+                .strip_prefix("Line ")
+                .and_then(|rest| rest.split_once(": "));
+            match numbered {
+                Some((number, msg)) if number.parse::<usize>().is_ok() => {
+                    format!("{file}:{number}: {msg}")
+                }
+                _ => format!("{file}: {line}"),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new()
     }
 }
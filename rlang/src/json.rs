@@ -0,0 +1,383 @@
+use crate::expr::{HashableValue, LiteralValue};
+use std::{cell::RefCell, iter::Peekable, rc::Rc, str::Chars};
+
+// Arrays can contain themselves, so encoding gives up past this depth
+const MAX_DEPTH: usize = 512;
+
+pub fn to_json(value: &LiteralValue) -> Result<String, String> {
+    let mut out = String::new();
+    write_value(value, &mut out, 0)?;
+    Ok(out)
+}
+
+pub fn from_json(text: &str) -> Result<LiteralValue, String> {
+    let mut parser = JsonParser {
+        chars: text.chars().peekable(),
+        depth: 0,
+    };
+    let value = parser.value()?;
+    parser.skip_whitespace();
+    match parser.chars.next() {
+        None => Ok(value),
+        Some(c) => Err(format!("Invalid JSON: unexpected '{c}' after the value")),
+    }
+}
+
+fn write_value(value: &LiteralValue, out: &mut String, depth: usize) -> Result<(), String> {
+    if depth > MAX_DEPTH {
+        return Err("Cannot encode JSON: value is nested too deeply".into());
+    }
+
+    match value {
+        LiteralValue::Nil => out.push_str("null"),
+        LiteralValue::True => out.push_str("true"),
+        LiteralValue::False => out.push_str("false"),
+        LiteralValue::Int(x) => out.push_str(&x.to_string()),
+        LiteralValue::Number(x) if x.is_finite() => out.push_str(&x.to_string()),
+        LiteralValue::Number(x) => return Err(format!("Cannot encode {x} as JSON")),
+        LiteralValue::StringValue(s) => write_string(s, out),
+        LiteralValue::Array(elements) => {
+            out.push('[');
+            for (i, element) in elements.borrow().iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_value(element, out, depth + 1)?;
+            }
+            out.push(']');
+        }
+        LiteralValue::Map(entries) => {
+            out.push('{');
+            for (i, (key, value)) in entries.borrow().iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                match key {
+                    HashableValue::StringValue(key) => write_string(key, out),
+                    key => return Err(format!("Cannot encode map key {key} as JSON")),
+                }
+                out.push(':');
+                write_value(value, out, depth + 1)?;
+            }
+            out.push('}');
+        }
+        LiteralValue::Callable { name, .. } => {
+            return Err(format!("Cannot encode callable {name} as JSON"));
+        }
+    }
+
+    Ok(())
+}
+
+fn write_string(s: &str, out: &mut String) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+struct JsonParser<'a> {
+    chars: Peekable<Chars<'a>>,
+    depth: usize,
+}
+
+impl JsonParser<'_> {
+    fn value(&mut self) -> Result<LiteralValue, String> {
+        self.skip_whitespace();
+        match self.chars.peek() {
+            None => Err("Invalid JSON: unexpected end of input".into()),
+            Some('n') => self.keyword("null", LiteralValue::Nil),
+            Some('t') => self.keyword("true", LiteralValue::True),
+            Some('f') => self.keyword("false", LiteralValue::False),
+            Some('"') => Ok(LiteralValue::StringValue(self.string()?)),
+            Some('[') => self.nested(Self::array),
+            Some('{') => self.nested(Self::object),
+            Some(c) if *c == '-' || c.is_ascii_digit() => self.number(),
+            Some(c) => Err(format!("Invalid JSON: unexpected '{c}'")),
+        }
+    }
+
+    fn nested(
+        &mut self,
+        parse: fn(&mut Self) -> Result<LiteralValue, String>,
+    ) -> Result<LiteralValue, String> {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            return Err("Invalid JSON: nested too deeply".into());
+        }
+        let value = parse(self);
+        self.depth -= 1;
+        value
+    }
+
+    fn keyword(&mut self, word: &str, value: LiteralValue) -> Result<LiteralValue, String> {
+        for expected in word.chars() {
+            if self.chars.next() != Some(expected) {
+                return Err(format!("Invalid JSON: expected '{word}'"));
+            }
+        }
+        Ok(value)
+    }
+
+    fn array(&mut self) -> Result<LiteralValue, String> {
+        self.chars.next();
+        let mut elements = vec![];
+
+        self.skip_whitespace();
+        if self.chars.peek() == Some(&']') {
+            self.chars.next();
+        } else {
+            loop {
+                elements.push(self.value()?);
+                self.skip_whitespace();
+                match self.chars.next() {
+                    Some(',') => continue,
+                    Some(']') => break,
+                    _ => return Err("Invalid JSON: expected ',' or ']' in array".into()),
+                }
+            }
+        }
+
+        Ok(LiteralValue::Array(Rc::new(RefCell::new(elements))))
+    }
+
+    fn object(&mut self) -> Result<LiteralValue, String> {
+        self.chars.next();
+        let mut entries: Vec<(HashableValue, LiteralValue)> = vec![];
+
+        self.skip_whitespace();
+        if self.chars.peek() == Some(&'}') {
+            self.chars.next();
+        } else {
+            loop {
+                self.skip_whitespace();
+                if self.chars.peek() != Some(&'"') {
+                    return Err("Invalid JSON: object keys must be strings".into());
+                }
+                let key = HashableValue::StringValue(self.string()?);
+
+                self.skip_whitespace();
+                if self.chars.next() != Some(':') {
+                    return Err("Invalid JSON: expected ':' after object key".into());
+                }
+                let value = self.value()?;
+
+                // Later duplicates win, like most JSON decoders
+                match entries.iter_mut().find(|(k, _)| *k == key) {
+                    Some(entry) => entry.1 = value,
+                    None => entries.push((key, value)),
+                }
+
+                self.skip_whitespace();
+                match self.chars.next() {
+                    Some(',') => continue,
+                    Some('}') => break,
+                    _ => return Err("Invalid JSON: expected ',' or '}' in object".into()),
+                }
+            }
+        }
+
+        Ok(LiteralValue::Map(Rc::new(RefCell::new(entries))))
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.chars.next();
+        let mut s = String::new();
+
+        loop {
+            match self.chars.next() {
+                None => return Err("Invalid JSON: unterminated string".into()),
+                Some('"') => return Ok(s),
+                Some('\\') => match self.chars.next() {
+                    Some('"') => s.push('"'),
+                    Some('\\') => s.push('\\'),
+                    Some('/') => s.push('/'),
+                    Some('b') => s.push('\u{8}'),
+                    Some('f') => s.push('\u{c}'),
+                    Some('n') => s.push('\n'),
+                    Some('r') => s.push('\r'),
+                    Some('t') => s.push('\t'),
+                    Some('u') => s.push(self.unicode_escape()?),
+                    _ => return Err("Invalid JSON: unknown escape in string".into()),
+                },
+                Some(c) if (c as u32) < 0x20 => {
+                    return Err("Invalid JSON: control character in string".into());
+                }
+                Some(c) => s.push(c),
+            }
+        }
+    }
+
+    fn unicode_escape(&mut self) -> Result<char, String> {
+        let high = self.hex4()?;
+        if !(0xD800..0xDC00).contains(&high) {
+            return char::from_u32(high).ok_or_else(|| "Invalid JSON: bad \\u escape".into());
+        }
+
+        // Characters outside the BMP come as a surrogate pair
+        if self.chars.next() != Some('\\') || self.chars.next() != Some('u') {
+            return Err("Invalid JSON: unpaired surrogate in \\u escape".into());
+        }
+        let low = self.hex4()?;
+        if !(0xDC00..0xE000).contains(&low) {
+            return Err("Invalid JSON: unpaired surrogate in \\u escape".into());
+        }
+        let code = 0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00);
+        char::from_u32(code).ok_or_else(|| "Invalid JSON: bad \\u escape".into())
+    }
+
+    fn hex4(&mut self) -> Result<u32, String> {
+        let mut code = 0;
+        for _ in 0..4 {
+            let digit = self
+                .chars
+                .next()
+                .and_then(|c| c.to_digit(16))
+                .ok_or("Invalid JSON: expected 4 hex digits in \\u escape")?;
+            code = code * 16 + digit;
+        }
+        Ok(code)
+    }
+
+    fn number(&mut self) -> Result<LiteralValue, String> {
+        let mut text = String::new();
+        while let Some(&c) = self.chars.peek() {
+            if c.is_ascii_digit() || matches!(c, '-' | '+' | '.' | 'e' | 'E') {
+                text.push(c);
+                self.chars.next();
+            } else {
+                break;
+            }
+        }
+
+        if !is_json_number(&text) {
+            return Err(format!("Invalid JSON: bad number '{text}'"));
+        }
+        if let Ok(x) = text.parse::<i64>() {
+            return Ok(LiteralValue::Int(x));
+        }
+        text.parse::<f64>()
+            .map(LiteralValue::Number)
+            .map_err(|_| format!("Invalid JSON: bad number '{text}'"))
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self
+            .chars
+            .peek()
+            .is_some_and(|c| matches!(c, ' ' | '\t' | '\n' | '\r'))
+        {
+            self.chars.next();
+        }
+    }
+}
+
+// Rust's number parsing is looser than JSON, which has no leading zeros or
+// '+' signs and needs digits after '.' and after the exponent marker
+fn is_json_number(text: &str) -> bool {
+    fn digits(bytes: &[u8]) -> usize {
+        bytes.iter().take_while(|b| b.is_ascii_digit()).count()
+    }
+
+    let bytes = text.as_bytes();
+    let mut i = usize::from(bytes.first() == Some(&b'-'));
+    match digits(&bytes[i..]) {
+        0 => return false,
+        n if n > 1 && bytes[i] == b'0' => return false,
+        n => i += n,
+    }
+    if bytes.get(i) == Some(&b'.') {
+        match digits(&bytes[i + 1..]) {
+            0 => return false,
+            n => i += 1 + n,
+        }
+    }
+    if matches!(bytes.get(i), Some(b'e' | b'E')) {
+        i += 1;
+        if matches!(bytes.get(i), Some(b'+' | b'-')) {
+            i += 1;
+        }
+        match digits(&bytes[i..]) {
+            0 => return false,
+            n => i += n,
+        }
+    }
+    i == bytes.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let sources = [
+            "null",
+            "true",
+            "-12",
+            "0",
+            "-0.25",
+            "1.5",
+            "\"a \\\"quoted\\\" line\\n\"",
+            "[1,[2,3],[]]",
+            "{\"name\":\"rl\",\"tags\":[\"a\",\"b\"],\"nested\":{\"ok\":false},\"empty\":{}}",
+        ];
+        for source in sources {
+            let value = from_json(source).unwrap();
+            assert_eq!(to_json(&value).unwrap(), source);
+        }
+    }
+
+    #[test]
+    fn parses_whitespace_and_escapes() {
+        let value =
+            from_json(" { \"a\" : [ 1 , 2e2 ] , \"b\" : \"\\u00e9\\ud83d\\ude00\" } ").unwrap();
+        assert_eq!(to_json(&value).unwrap(), "{\"a\":[1,200],\"b\":\"é😀\"}");
+    }
+
+    #[test]
+    fn rejects_invalid_json() {
+        for source in [
+            "",
+            "[1,",
+            "{\"a\" 1}",
+            "{1: 2}",
+            "tru",
+            "1 2",
+            "\"open",
+            "01.",
+            "01",
+            "-01",
+            "1.e5",
+            "1e",
+            "1e+",
+            "+1",
+            ".5",
+            "-",
+            "[1,]",
+        ] {
+            assert!(from_json(source).is_err(), "Source: {:?}", source);
+        }
+    }
+
+    #[test]
+    fn rejects_unencodable_values() {
+        assert!(to_json(&LiteralValue::Number(f64::NAN)).is_err());
+
+        let array = Rc::new(RefCell::new(vec![]));
+        array.borrow_mut().push(LiteralValue::Array(array.clone()));
+        let err = to_json(&LiteralValue::Array(array.clone())).unwrap_err();
+        assert!(err.contains("nested too deeply"), "Error: '{}'", err);
+        // Break the cycle so the test doesn't leak it
+        array.borrow_mut().clear();
+    }
+}
@@ -1,15 +1,15 @@
 //#![allow(dead_code)]
 
-use std::{cell::LazyCell, collections::HashMap, rc::Rc};
+use std::{collections::HashMap, rc::Rc, sync::LazyLock};
 
+// Digits stay ASCII, so only `0`-`9` start a number or continue an identifier
 fn is_digit(ch: char) -> bool {
-    ch as u8 >= b'0' && ch as u8 <= b'9'
-    //ch.is_ascii_digit()
+    ch.is_ascii_digit()
 }
 
+// Any Unicode letter may appear in an identifier, like `café`
 fn is_alpha(ch: char) -> bool {
-    let uch = ch as u8;
-    (uch >= b'a' && uch <= b'z') || (uch >= b'A' && uch <= b'Z') || (ch == '_')
+    ch.is_alphabetic() || ch == '_'
 }
 
 fn is_alphanum(ch: char) -> bool {
@@ -18,23 +18,33 @@ fn is_alphanum(ch: char) -> bool {
 
 // TODO: Improve this to make runtime faster. HashMap has runtime overhead
 //       Maybe using BTreeMap
-pub const KEYOWRDS: LazyCell<HashMap<&str, TokenType>> = LazyCell::new(|| {
+pub static KEYOWRDS: LazyLock<HashMap<&str, TokenType>> = LazyLock::new(|| {
     HashMap::from([
         ("and", TokenType::And),
+        ("break", TokenType::Break),
+        ("case", TokenType::Case),
+        ("catch", TokenType::Catch),
+        ("continue", TokenType::Continue),
         ("class", TokenType::Class),
+        ("default", TokenType::Default),
         ("while", TokenType::While),
         ("else", TokenType::Else),
         ("false", TokenType::False),
         ("for", TokenType::For),
         ("fn", TokenType::Fn),
         ("if", TokenType::If),
+        ("import", TokenType::Import),
         ("nil", TokenType::Nil),
         ("or", TokenType::Or),
         ("print", TokenType::Print),
         ("return", TokenType::Return),
         ("super", TokenType::Super),
+        ("switch", TokenType::Switch),
         ("this", TokenType::This),
+        ("throw", TokenType::Throw),
         ("true", TokenType::True),
+        ("try", TokenType::Try),
+        ("typeof", TokenType::Typeof),
         ("var", TokenType::Var),
     ])
 });
@@ -42,10 +52,23 @@ pub const KEYOWRDS: LazyCell<HashMap<&str, TokenType>> = LazyCell::new(|| {
 #[derive(Debug, Clone)]
 pub struct Lexer<'a> {
     source: &'a str,
-    tokens: Rc<Vec<Token>>,
+    tokens: Vec<Token>,
     start: usize,
     current: usize,
     line: usize,
+    limits: LexerLimits,
+    // Off, `print` lexes as an identifier so it can name a function
+    print_keyword: bool,
+    unterminated: bool,
+}
+
+// Upper bounds in bytes for single tokens, so hosts running untrusted input
+// can refuse gigantic literals. `None` means unlimited, which is the default.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct LexerLimits {
+    pub max_identifier_len: Option<usize>,
+    pub max_string_len: Option<usize>,
+    pub max_number_len: Option<usize>,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -55,6 +78,9 @@ pub enum TokenType {
     RightParen,
     LeftBrace,
     RightBrace,
+    LeftBracket,
+    RightBracket,
+    Colon,
     Comma,
     Dot,
     Minus,
@@ -72,6 +98,12 @@ pub enum TokenType {
     GreaterEqual,
     Less,
     LessEqual,
+    MinusEqual,
+    PlusEqual,
+    SlashEqual,
+    StarEqual,
+    StarStar,
+    DotDotDot,
 
     // Literals
     Identifier,
@@ -80,25 +112,107 @@ pub enum TokenType {
 
     // Keywords
     And,
+    Break,
+    Case,
+    Catch,
     Class,
+    Continue,
+    Default,
     Else,
     False,
     True,
     Fn,
     For,
     If,
+    Import,
     Nil,
     Or,
     Print,
     Return,
     Super,
+    Switch,
     This,
+    Throw,
+    Try,
+    Typeof,
     Var,
     While,
 
     Eof,
 }
 
+// Categories for tools such as highlighters. They overlap: `true` is a
+// keyword and a literal, `and` a keyword and an operator.
+impl TokenType {
+    // Tokens that are a value by themselves
+    pub fn is_literal(self) -> bool {
+        matches!(
+            self,
+            Self::String | Self::Number | Self::True | Self::False | Self::Nil
+        )
+    }
+
+    // Unary, binary and assignment operators
+    pub fn is_operator(self) -> bool {
+        matches!(
+            self,
+            Self::Minus
+                | Self::Plus
+                | Self::Slash
+                | Self::Star
+                | Self::StarStar
+                | Self::Bang
+                | Self::BangEqual
+                | Self::Equal
+                | Self::EqualEqual
+                | Self::Greater
+                | Self::GreaterEqual
+                | Self::Less
+                | Self::LessEqual
+                | Self::MinusEqual
+                | Self::PlusEqual
+                | Self::SlashEqual
+                | Self::StarEqual
+                | Self::And
+                | Self::Or
+                | Self::Typeof
+        )
+    }
+
+    // Everything in `KEYOWRDS`
+    pub fn is_keyword(self) -> bool {
+        matches!(
+            self,
+            Self::And
+                | Self::Break
+                | Self::Case
+                | Self::Catch
+                | Self::Class
+                | Self::Continue
+                | Self::Default
+                | Self::Else
+                | Self::False
+                | Self::True
+                | Self::Fn
+                | Self::For
+                | Self::If
+                | Self::Import
+                | Self::Nil
+                | Self::Or
+                | Self::Print
+                | Self::Return
+                | Self::Super
+                | Self::Switch
+                | Self::This
+                | Self::Throw
+                | Self::Try
+                | Self::Typeof
+                | Self::Var
+                | Self::While
+        )
+    }
+}
+
 impl std::fmt::Display for TokenType {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{:?}", self)
@@ -113,12 +227,20 @@ pub enum LiteralValue {
     IdentifierValue(String),
 }
 
+// Byte offsets of a token in the source, `end` is exclusive
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct Token {
     pub token_t: TokenType,
     pub lexme: String,
     pub literal: Option<LiteralValue>,
     pub line_number: usize,
+    pub span: Span,
 }
 
 impl Token {
@@ -127,12 +249,27 @@ impl Token {
         lexme: String,
         literal: Option<LiteralValue>,
         line_number: usize,
+        span: Span,
     ) -> Self {
         Self {
             token_t: token_type,
             lexme,
             line_number,
             literal,
+            span,
+        }
+    }
+
+    // A readable one-liner such as `Number '5' @line 1`, unlike `Display`
+    // which includes the Debug form of the literal
+    pub fn describe(&self) -> String {
+        if self.lexme.is_empty() {
+            format!("{} @line {}", self.token_t, self.line_number)
+        } else {
+            format!(
+                "{} '{}' @line {}",
+                self.token_t, self.lexme, self.line_number
+            )
         }
     }
 }
@@ -147,15 +284,49 @@ impl<'a> Lexer<'a> {
     pub fn new(source: &'a str) -> Self {
         Self {
             source,
-            tokens: Rc::new(vec![]),
+            tokens: vec![],
             start: 0,
             current: 0,
             line: 1,
+            limits: LexerLimits::default(),
+            print_keyword: true,
+            unterminated: false,
+        }
+    }
+
+    pub fn with_limits(source: &'a str, limits: LexerLimits) -> Self {
+        Self {
+            limits,
+            ..Self::new(source)
+        }
+    }
+
+    pub fn set_print_keyword(&mut self, enabled: bool) {
+        self.print_keyword = enabled;
+    }
+
+    // Whether the source ended inside a string, so reading more of it could
+    // still make it valid
+    pub fn unterminated(&self) -> bool {
+        self.unterminated
+    }
+
+    fn check_limit(&self, kind: &str, len: usize, limit: Option<usize>) -> Result<(), String> {
+        match limit {
+            Some(limit) if len > limit => Err(format!(
+                "{kind} at line {} is {len} bytes long, the limit is {limit}",
+                self.line
+            )),
+            _ => Ok(()),
         }
     }
 
     pub fn scan_tokens(&mut self) -> Result<Rc<Vec<Token>>, String> {
         let mut errors: Vec<String> = vec![];
+        // A `#!` line only counts as a shebang at the very start of the source
+        if self.current == 0 && self.source.starts_with("#!") {
+            self.current = self.source.find('\n').unwrap_or(self.source.len());
+        }
         while !self.is_end() {
             self.start = self.current;
             match self.scan_token() {
@@ -164,14 +335,16 @@ impl<'a> Lexer<'a> {
             }
         }
 
-        Rc::get_mut(&mut self.tokens)
-            .expect("Failed to get mutable")
-            .push(Token {
-                token_t: TokenType::Eof,
-                lexme: String::new(),
-                literal: None,
-                line_number: self.line,
-            });
+        self.tokens.push(Token {
+            token_t: TokenType::Eof,
+            lexme: String::new(),
+            literal: None,
+            line_number: self.line,
+            span: Span {
+                start: self.current,
+                end: self.current,
+            },
+        });
 
         if !errors.is_empty() {
             let mut joined: String = String::new();
@@ -182,7 +355,7 @@ impl<'a> Lexer<'a> {
             return Err(joined);
         }
 
-        Ok(self.tokens.clone())
+        Ok(Rc::new(std::mem::take(&mut self.tokens)))
     }
 
     fn scan_token(&mut self) -> Result<(), String> {
@@ -193,11 +366,46 @@ impl<'a> Lexer<'a> {
             ')' => self.add_token(TokenType::RightParen),
             '{' => self.add_token(TokenType::LeftBrace),
             '}' => self.add_token(TokenType::RightBrace),
+            '[' => self.add_token(TokenType::LeftBracket),
+            ']' => self.add_token(TokenType::RightBracket),
+            ':' => self.add_token(TokenType::Colon),
             ',' => self.add_token(TokenType::Comma),
-            '.' => self.add_token(TokenType::Dot),
-            '-' => self.add_token(TokenType::Minus),
-            '+' => self.add_token(TokenType::Plus),
-            '*' => self.add_token(TokenType::Star),
+            '.' => {
+                let token = if self.peek() == '.' && self.peek_next() == '.' {
+                    self.advance();
+                    self.advance();
+                    TokenType::DotDotDot
+                } else {
+                    TokenType::Dot
+                };
+                self.add_token(token);
+            }
+            '-' => {
+                let token = if self.char_match('=') {
+                    TokenType::MinusEqual
+                } else {
+                    TokenType::Minus
+                };
+                self.add_token(token);
+            }
+            '+' => {
+                let token = if self.char_match('=') {
+                    TokenType::PlusEqual
+                } else {
+                    TokenType::Plus
+                };
+                self.add_token(token);
+            }
+            '*' => {
+                let token = if self.char_match('=') {
+                    TokenType::StarEqual
+                } else if self.char_match('*') {
+                    TokenType::StarStar
+                } else {
+                    TokenType::Star
+                };
+                self.add_token(token);
+            }
             ';' => self.add_token(TokenType::Semicolon),
             '/' => {
                 if self.char_match('/') {
@@ -207,6 +415,8 @@ impl<'a> Lexer<'a> {
                         }
                         self.advance();
                     }
+                } else if self.char_match('=') {
+                    self.add_token(TokenType::SlashEqual)
                 } else {
                     self.add_token(TokenType::Slash)
                 }
@@ -250,7 +460,7 @@ impl<'a> Lexer<'a> {
                 if is_digit(c) {
                     self.number()?;
                 } else if is_alpha(c) {
-                    self.identifier();
+                    self.identifier()?;
                 } else {
                     return Err(format!("Unrecognized char at line {}: '{}'", self.line, c));
                 }
@@ -260,28 +470,37 @@ impl<'a> Lexer<'a> {
         Ok(())
     }
 
-    fn identifier(&mut self) {
+    fn identifier(&mut self) -> Result<(), String> {
         while is_alphanum(self.peek()) {
             self.advance();
         }
+        self.check_limit(
+            "Identifier",
+            self.current - self.start,
+            self.limits.max_identifier_len,
+        )?;
 
         let keyword = &self.source[self.start..self.current];
-        if let Some(ty) = HashMap::get(&KEYOWRDS, keyword) {
+        if let Some(ty) = HashMap::get(&KEYOWRDS, keyword)
+            .filter(|ty| self.print_keyword || **ty != TokenType::Print)
+        {
             self.add_token(*ty);
         } else {
             self.add_token(TokenType::Identifier);
         }
+        Ok(())
     }
 
     fn is_end(&self) -> bool {
         self.current >= self.source.len()
     }
 
+    // `current` is a byte offset, stepping over whole characters keeps it on a
+    // character boundary
     fn advance(&mut self) -> char {
-        let c = self.source.as_bytes()[self.current];
-        self.current += 1;
-
-        c as char
+        let c = self.peek();
+        self.current += c.len_utf8();
+        c
     }
 
     fn add_token(&mut self, token_t: TokenType) {
@@ -294,14 +513,16 @@ impl<'a> Lexer<'a> {
             // String::from_utf8(self.source.as_bytes()[self.start..self.current].into()).unwrap();
             self.source[self.start..self.current].to_string();
 
-        Rc::get_mut(&mut self.tokens)
-            .expect("Failed to get Rc mutable for push_tokens")
-            .push(Token {
-                token_t,
-                lexme: text,
-                literal,
-                line_number: self.line,
-            });
+        self.tokens.push(Token {
+            token_t,
+            lexme: text,
+            literal,
+            line_number: self.line,
+            span: Span {
+                start: self.start,
+                end: self.current,
+            },
+        });
     }
 
     fn char_match(&mut self, ch: char) -> bool {
@@ -309,20 +530,16 @@ impl<'a> Lexer<'a> {
             return false;
         }
 
-        if self.source.as_bytes()[self.current] as char != ch {
+        if self.peek() != ch {
             false
         } else {
-            self.current += 1;
+            self.current += ch.len_utf8();
             true
         }
     }
 
     fn peek(&self) -> char {
-        if self.is_end() {
-            return '\0';
-        }
-
-        self.source.as_bytes()[self.current] as char
+        self.source[self.current..].chars().next().unwrap_or('\0')
     }
 
     fn number(&mut self) -> Result<(), String> {
@@ -336,7 +553,18 @@ impl<'a> Lexer<'a> {
             }
         }
 
+        self.check_limit(
+            "Number",
+            self.current - self.start,
+            self.limits.max_number_len,
+        )?;
+
+        // Integers stay exact as long as they fit in an i64
         let substring = &self.source[self.start..self.current];
+        if let Ok(value) = substring.parse::<i64>() {
+            self.push_token(TokenType::Number, Some(LiteralValue::IntValue(value)));
+            return Ok(());
+        }
         match substring.parse::<f64>() {
             Ok(value) => self.push_token(TokenType::Number, Some(LiteralValue::FloatValue(value))),
             Err(_) => return Err(format!("Could not parse integer: {}", substring)),
@@ -345,11 +573,7 @@ impl<'a> Lexer<'a> {
     }
 
     fn peek_next(&self) -> char {
-        if self.current + 1 >= self.source.len() {
-            return '\0';
-        }
-
-        self.source.as_bytes()[self.current + 1] as char
+        self.source[self.current..].chars().nth(1).unwrap_or('\0')
     }
 
     fn string(&mut self) -> Result<(), String> {
@@ -361,11 +585,13 @@ impl<'a> Lexer<'a> {
         }
 
         if self.is_end() {
+            self.unterminated = true;
             return Err("Unterminated string".into());
         }
         self.advance();
 
         let value = &self.source.as_bytes()[self.start + 1..self.current - 1];
+        self.check_limit("String", value.len(), self.limits.max_string_len)?;
         self.push_token(
             TokenType::String,
             Some(LiteralValue::StringValue(
@@ -384,47 +610,57 @@ mod tests {
     pub fn handle_one_char_tokens() {
         let source = "(( )) }{";
         let mut lexer = Lexer::new(source);
-        lexer.scan_tokens().unwrap();
-        //dbg!(&lexer.tokens);
-        assert_eq!(lexer.tokens.len(), 6 + 1); // Plus one because of eof
-        assert_eq!(lexer.tokens[0].token_t, TokenType::LeftParen);
-        assert_eq!(lexer.tokens[1].token_t, TokenType::LeftParen);
+        let tokens = lexer.scan_tokens().unwrap();
+        //dbg!(&tokens);
+        assert_eq!(tokens.len(), 6 + 1); // Plus one because of eof
+        assert_eq!(tokens[0].token_t, TokenType::LeftParen);
+        assert_eq!(tokens[1].token_t, TokenType::LeftParen);
 
-        assert_eq!(lexer.tokens[2].token_t, TokenType::RightParen);
-        assert_eq!(lexer.tokens[3].token_t, TokenType::RightParen);
+        assert_eq!(tokens[2].token_t, TokenType::RightParen);
+        assert_eq!(tokens[3].token_t, TokenType::RightParen);
 
-        assert_eq!(lexer.tokens[4].token_t, TokenType::RightBrace);
-        assert_eq!(lexer.tokens[5].token_t, TokenType::LeftBrace);
+        assert_eq!(tokens[4].token_t, TokenType::RightBrace);
+        assert_eq!(tokens[5].token_t, TokenType::LeftBrace);
 
-        assert_eq!(lexer.tokens[6].token_t, TokenType::Eof);
+        assert_eq!(tokens[6].token_t, TokenType::Eof);
     }
 
     #[test]
     pub fn handle_two_char_tokens() {
         let source = "! != == >=";
         let mut lexer = Lexer::new(source);
-        lexer.scan_tokens().unwrap();
-        //dbg!(&lexer.tokens);
-        assert_eq!(lexer.tokens.len(), 4 + 1); // Plus one because of eof
-        assert_eq!(lexer.tokens[0].token_t, TokenType::Bang);
-        assert_eq!(lexer.tokens[1].token_t, TokenType::BangEqual);
+        let tokens = lexer.scan_tokens().unwrap();
+        //dbg!(&tokens);
+        assert_eq!(tokens.len(), 4 + 1); // Plus one because of eof
+        assert_eq!(tokens[0].token_t, TokenType::Bang);
+        assert_eq!(tokens[1].token_t, TokenType::BangEqual);
 
-        assert_eq!(lexer.tokens[2].token_t, TokenType::EqualEqual);
-        assert_eq!(lexer.tokens[3].token_t, TokenType::GreaterEqual);
+        assert_eq!(tokens[2].token_t, TokenType::EqualEqual);
+        assert_eq!(tokens[3].token_t, TokenType::GreaterEqual);
 
-        assert_eq!(lexer.tokens[4].token_t, TokenType::Eof);
+        assert_eq!(tokens[4].token_t, TokenType::Eof);
+    }
+
+    #[test]
+    fn handle_blank_source() {
+        for source in ["", "  \n\t\r\n", "// hi", "// hi\n  // there\n"] {
+            let mut lexer = Lexer::new(source);
+            let tokens = lexer.scan_tokens().unwrap();
+            assert_eq!(tokens.len(), 1, "Source: {:?}", source); // Only eof
+            assert_eq!(tokens[0].token_t, TokenType::Eof);
+        }
     }
 
     #[test]
     fn handle_string_lit() {
         let source = "\"ABC\"";
         let mut lexer = Lexer::new(source);
-        lexer.scan_tokens().unwrap();
-        //dbg!(&lexer.tokens);
-        assert_eq!(lexer.tokens.len(), 1 + 1); // Plus one because of eof
-        assert_eq!(lexer.tokens[0].token_t, TokenType::String);
+        let tokens = lexer.scan_tokens().unwrap();
+        //dbg!(&tokens);
+        assert_eq!(tokens.len(), 1 + 1); // Plus one because of eof
+        assert_eq!(tokens[0].token_t, TokenType::String);
         assert_eq!(
-            lexer.tokens[0].literal.as_ref().unwrap(),
+            tokens[0].literal.as_ref().unwrap(),
             &LiteralValue::StringValue("ABC".into())
         );
     }
@@ -444,38 +680,47 @@ mod tests {
         let mut lexer = Lexer::new(source);
         let res = lexer.scan_tokens();
         // dbg!(&res);
-        assert!(!res.is_err()); // Wont fail because it supports multiple lines !
-        assert_eq!(lexer.tokens.len(), 1 + 1); // Plus one because of eof
-        assert_eq!(lexer.tokens[0].token_t, TokenType::String);
+        assert!(res.is_ok()); // Wont fail because it supports multiple lines !
+        let tokens = res.unwrap();
+        assert_eq!(tokens.len(), 1 + 1); // Plus one because of eof
+        assert_eq!(tokens[0].token_t, TokenType::String);
         assert_eq!(
-            lexer.tokens[0].literal.as_ref().unwrap(),
+            tokens[0].literal.as_ref().unwrap(),
             &LiteralValue::StringValue("ABC\nhi".into())
         );
     }
 
     #[test]
     fn num_literals() {
-        let source = "123.123\n321.0\n5";
+        let source = "123.123\n321.0\n5\n9007199254740993\n99999999999999999999";
         let mut lexer = Lexer::new(source);
-        lexer.scan_tokens().unwrap();
+        let tokens = lexer.scan_tokens().unwrap();
         // dbg!(&lexer);
-        assert_eq!(lexer.tokens.len(), 4);
+        assert_eq!(tokens.len(), 6);
 
-        for i in 0..3 {
-            assert_eq!(lexer.tokens[i].token_t, TokenType::Number);
+        for i in 0..5 {
+            assert_eq!(tokens[i].token_t, TokenType::Number);
         }
 
         assert_eq!(
-            lexer.tokens[0].literal.as_ref().unwrap(),
+            tokens[0].literal.as_ref().unwrap(),
             &LiteralValue::FloatValue(123.123)
         );
         assert_eq!(
-            lexer.tokens[1].literal.as_ref().unwrap(),
+            tokens[1].literal.as_ref().unwrap(),
             &LiteralValue::FloatValue(321.0)
         );
         assert_eq!(
-            lexer.tokens[2].literal.as_ref().unwrap(),
-            &LiteralValue::FloatValue(5.0)
+            tokens[2].literal.as_ref().unwrap(),
+            &LiteralValue::IntValue(5)
+        );
+        assert_eq!(
+            tokens[3].literal.as_ref().unwrap(),
+            &LiteralValue::IntValue(9007199254740993)
+        );
+        assert_eq!(
+            tokens[4].literal.as_ref().unwrap(),
+            &LiteralValue::FloatValue(1e20)
         );
     }
 
@@ -483,39 +728,195 @@ mod tests {
     fn get_ident() {
         let source = "this_is_var = 12;";
         let mut lexer = Lexer::new(source);
-        lexer.scan_tokens().unwrap();
+        let tokens = lexer.scan_tokens().unwrap();
         // dbg!(&lexer);
 
-        assert_eq!(lexer.tokens.len(), 5);
+        assert_eq!(tokens.len(), 5);
 
-        assert_eq!(lexer.tokens[0].token_t, TokenType::Identifier);
-        assert_eq!(lexer.tokens[1].token_t, TokenType::Equal);
-        assert_eq!(lexer.tokens[2].token_t, TokenType::Number);
-        assert_eq!(lexer.tokens[3].token_t, TokenType::Semicolon);
-        assert_eq!(lexer.tokens[4].token_t, TokenType::Eof);
+        assert_eq!(tokens[0].token_t, TokenType::Identifier);
+        assert_eq!(tokens[1].token_t, TokenType::Equal);
+        assert_eq!(tokens[2].token_t, TokenType::Number);
+        assert_eq!(tokens[3].token_t, TokenType::Semicolon);
+        assert_eq!(tokens[4].token_t, TokenType::Eof);
+    }
+
+    #[test]
+    fn get_unicode_ident() {
+        let source = "var café = 1; print café + ñandú_2;";
+        let mut lexer = Lexer::new(source);
+        let tokens = lexer.scan_tokens().unwrap();
+
+        assert_eq!(tokens.len(), 11);
+        assert_eq!(tokens[1].token_t, TokenType::Identifier);
+        assert_eq!(tokens[1].lexme, "café");
+        assert_eq!(&source[tokens[1].span.start..tokens[1].span.end], "café");
+        assert_eq!(tokens[6].lexme, "café");
+        assert_eq!(tokens[8].token_t, TokenType::Identifier);
+        assert_eq!(tokens[8].lexme, "ñandú_2");
+
+        let err = Lexer::new("var a = 1 ¤ 2;").scan_tokens().unwrap_err();
+        assert!(
+            err.contains("Unrecognized char at line 1: '¤'"),
+            "Error: '{}'",
+            err
+        );
+    }
+
+    #[test]
+    fn describe_tokens() {
+        let tokens = Lexer::new("var x = 5;\nprint \"hi\";")
+            .scan_tokens()
+            .unwrap();
+        let described: Vec<String> = tokens.iter().map(Token::describe).collect();
+        assert_eq!(
+            described,
+            [
+                "Var 'var' @line 1",
+                "Identifier 'x' @line 1",
+                "Equal '=' @line 1",
+                "Number '5' @line 1",
+                "Semicolon ';' @line 1",
+                "Print 'print' @line 2",
+                "String '\"hi\"' @line 2",
+                "Semicolon ';' @line 2",
+                "Eof @line 2",
+            ]
+        );
     }
 
     #[test]
     fn get_keywords() {
         let source = "var this_a_var = 12;\nwhile true { print 3 };";
-        let mut lexer = Lexer::new(&source);
-        lexer.scan_tokens().unwrap();
+        let mut lexer = Lexer::new(source);
+        let tokens = lexer.scan_tokens().unwrap();
 
         // dbg!(&lexer);
-        assert_eq!(lexer.tokens.len(), 13);
-
-        assert_eq!(lexer.tokens[0].token_t, TokenType::Var);
-        assert_eq!(lexer.tokens[1].token_t, TokenType::Identifier);
-        assert_eq!(lexer.tokens[2].token_t, TokenType::Equal);
-        assert_eq!(lexer.tokens[3].token_t, TokenType::Number);
-        assert_eq!(lexer.tokens[4].token_t, TokenType::Semicolon);
-        assert_eq!(lexer.tokens[5].token_t, TokenType::While);
-        assert_eq!(lexer.tokens[6].token_t, TokenType::True);
-        assert_eq!(lexer.tokens[7].token_t, TokenType::LeftBrace);
-        assert_eq!(lexer.tokens[8].token_t, TokenType::Print);
-        assert_eq!(lexer.tokens[9].token_t, TokenType::Number);
-        assert_eq!(lexer.tokens[10].token_t, TokenType::RightBrace);
-        assert_eq!(lexer.tokens[11].token_t, TokenType::Semicolon);
-        assert_eq!(lexer.tokens[12].token_t, TokenType::Eof);
+        assert_eq!(tokens.len(), 13);
+
+        assert_eq!(tokens[0].token_t, TokenType::Var);
+        assert_eq!(tokens[1].token_t, TokenType::Identifier);
+        assert_eq!(tokens[2].token_t, TokenType::Equal);
+        assert_eq!(tokens[3].token_t, TokenType::Number);
+        assert_eq!(tokens[4].token_t, TokenType::Semicolon);
+        assert_eq!(tokens[5].token_t, TokenType::While);
+        assert_eq!(tokens[6].token_t, TokenType::True);
+        assert_eq!(tokens[7].token_t, TokenType::LeftBrace);
+        assert_eq!(tokens[8].token_t, TokenType::Print);
+        assert_eq!(tokens[9].token_t, TokenType::Number);
+        assert_eq!(tokens[10].token_t, TokenType::RightBrace);
+        assert_eq!(tokens[11].token_t, TokenType::Semicolon);
+        assert_eq!(tokens[12].token_t, TokenType::Eof);
+    }
+
+    #[test]
+    fn token_spans() {
+        let source = "var name = \"abc\";";
+        let mut lexer = Lexer::new(source);
+        let tokens = lexer.scan_tokens().unwrap();
+
+        assert_eq!(tokens[1].span, Span { start: 4, end: 8 });
+        assert_eq!(&source[tokens[1].span.start..tokens[1].span.end], "name");
+        assert_eq!(tokens[3].span, Span { start: 11, end: 16 });
+        assert_eq!(tokens[5].span, Span { start: 17, end: 17 });
+    }
+
+    #[test]
+    fn flags_unterminated_string() {
+        let mut lexer = Lexer::new("print \"open\n{");
+        assert!(lexer.scan_tokens().is_err());
+        assert!(lexer.unterminated());
+
+        for source in ["print \"closed\";", "print @;", "print \"a\" @;"] {
+            let mut lexer = Lexer::new(source);
+            let _ = lexer.scan_tokens();
+            assert!(!lexer.unterminated(), "Source: {:?}", source);
+        }
+    }
+
+    #[test]
+    fn classifies_token_types() {
+        let tokens = Lexer::new("var x = \"s\" + 1 and true; (x);")
+            .scan_tokens()
+            .unwrap();
+        let kinds: Vec<(bool, bool, bool)> = tokens
+            .iter()
+            .map(|token| {
+                let t = token.token_t;
+                (t.is_literal(), t.is_operator(), t.is_keyword())
+            })
+            .collect();
+        assert_eq!(
+            kinds,
+            [
+                (false, false, true),  // var
+                (false, false, false), // x
+                (false, true, false),  // =
+                (true, false, false),  // "s"
+                (false, true, false),  // +
+                (true, false, false),  // 1
+                (false, true, true),   // and
+                (true, false, true),   // true
+                (false, false, false), // ;
+                (false, false, false), // (
+                (false, false, false), // x
+                (false, false, false), // )
+                (false, false, false), // ;
+                (false, false, false), // Eof
+            ]
+        );
+
+        for ty in KEYOWRDS.values() {
+            assert!(ty.is_keyword(), "{ty}");
+        }
+        assert!(TokenType::StarStar.is_operator() && TokenType::Typeof.is_operator());
+        assert!(!TokenType::Identifier.is_literal() && !TokenType::DotDotDot.is_operator());
+    }
+
+    #[test]
+    fn skips_shebang() {
+        let source = "#!/usr/bin/env rl\nprint 1;";
+        let tokens = Lexer::new(source).scan_tokens().unwrap();
+        assert_eq!(tokens.len(), 4);
+        assert_eq!(tokens[0].token_t, TokenType::Print);
+        assert_eq!(tokens[0].line_number, 2);
+        assert_eq!(&source[tokens[1].span.start..tokens[1].span.end], "1");
+
+        let tokens = Lexer::new("#!rl").scan_tokens().unwrap();
+        assert_eq!(tokens[0].token_t, TokenType::Eof);
+
+        for source in [
+            " #!/usr/bin/env rl\nprint 1;",
+            "print 1;\n#!rl",
+            "print #1;",
+        ] {
+            let err = Lexer::new(source).scan_tokens().unwrap_err();
+            assert!(err.contains("Unrecognized char"), "Error: '{}'", err);
+        }
+    }
+
+    #[test]
+    fn handle_limits() {
+        let limits = LexerLimits {
+            max_identifier_len: Some(4),
+            max_string_len: Some(3),
+            max_number_len: Some(3),
+        };
+
+        let mut lexer = Lexer::with_limits("var abcd = \"abc\" + 123;", limits);
+        assert!(lexer.scan_tokens().is_ok());
+
+        for (source, kind) in [
+            ("abcde", "Identifier"),
+            ("\"abcd\"", "String"),
+            ("1234", "Number"),
+            ("12.5", "Number"),
+        ] {
+            let mut lexer = Lexer::with_limits(source, limits);
+            let err = lexer.scan_tokens().unwrap_err();
+            assert!(err.starts_with(kind), "Error: '{}'", err);
+        }
+
+        let mut lexer = Lexer::new("var abcdefgh = \"abcdefgh\";");
+        assert!(lexer.scan_tokens().is_ok());
     }
 }
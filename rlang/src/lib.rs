@@ -1,18 +1,31 @@
 pub mod environment;
 pub mod expr;
+pub mod fold;
 pub mod interpreter;
+pub mod json;
 pub mod lexer;
+pub mod natives;
 pub mod parser;
 pub mod resolver;
 pub mod stmt;
 
 pub fn run_file(path: &str) -> Result<(), Box<dyn std::error::Error>> {
     let mut interpreter = interpreter::Interpreter::new();
-    let contents = std::fs::read_to_string(path)?;
-    match run(&mut interpreter, &contents) {
-        Err(msg) => Err(msg.into()),
-        Ok(()) => Ok(()),
-    }
+    run_file_with(&mut interpreter, path)
+}
+
+pub fn run_file_with(
+    interpreter: &mut interpreter::Interpreter,
+    path: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let contents = std::fs::read_to_string(path)
+        .map_err(|err| format!("Could not read '{}': {}", path, err))?;
+    let stmts = parse_program_or_expr_for(interpreter, &contents)?;
+    run_resolved(interpreter, &stmts, |interpreter, stmts| {
+        interpreter.interpret_file(std::path::Path::new(path), stmts)
+    })?;
+
+    Ok(())
 }
 
 pub fn run_string(contents: &str) -> Result<(), String> {
@@ -20,13 +33,218 @@ pub fn run_string(contents: &str) -> Result<(), String> {
     run(&mut interpreter, contents)
 }
 
+pub fn lex(contents: &str) -> Result<std::rc::Rc<Vec<lexer::Token>>, String> {
+    lexer::Lexer::new(contents).scan_tokens()
+}
+
+// Whether `contents` could be run as is, or is still missing a closing
+// delimiter or quote and more lines should be read first. Input the lexer
+// rejects for any other reason, or with a stray closing delimiter, counts as
+// complete since more lines can't fix it.
+pub fn is_complete(contents: &str) -> bool {
+    let mut lexer = lexer::Lexer::new(contents);
+    let tokens = match lexer.scan_tokens() {
+        Ok(tokens) => tokens,
+        Err(_) => return !lexer.unterminated(),
+    };
+
+    let mut depth: isize = 0;
+    for token in tokens.iter() {
+        match token.token_t {
+            lexer::TokenType::LeftParen
+            | lexer::TokenType::LeftBrace
+            | lexer::TokenType::LeftBracket => depth += 1,
+            lexer::TokenType::RightParen
+            | lexer::TokenType::RightBrace
+            | lexer::TokenType::RightBracket => depth -= 1,
+            _ => (),
+        }
+        if depth < 0 {
+            return true;
+        }
+    }
+    depth == 0
+}
+
+// Parses like `run_file`, so it accepts the same bare-expression files
+pub fn check(contents: &str) -> Result<(), String> {
+    let tokens = lex(contents)?;
+
+    let mut parser = parser::Parser::new(tokens.to_vec());
+    let stmts = parser.parse_program_or_expr()?;
+    // A fresh resolver after each error, so one bad statement doesn't leave
+    // its scopes around for the rest
+    let mut resolver = resolver::Resolver::new();
+    let mut errs = vec![];
+    for stmt in stmts.iter() {
+        if let Err(msg) = resolver.resolve(stmt) {
+            errs.push(msg);
+            resolver = resolver::Resolver::new();
+        }
+    }
+
+    if errs.is_empty() {
+        Ok(())
+    } else {
+        Err(errs.join("\n"))
+    }
+}
+
+pub fn fold_constants(stmts: &mut [Box<stmt::Stmt>]) {
+    fold::fold_stmts(stmts);
+}
+
 pub fn run(interpreter: &mut interpreter::Interpreter, contents: &str) -> Result<(), String> {
+    let stmts = parse_program_for(interpreter, contents)?;
+    run_resolved(interpreter, &stmts, interpreter::Interpreter::interpret)?;
+
+    Ok(())
+}
+
+// Resolves `stmts` before handing them to `run`, so the variables they read
+// from the globals skip the scopes in between. Programs fed into a scope below
+// the globals are run unresolved. A statement the resolver rejects still runs,
+// `check` is what reports those.
+fn run_resolved<T>(
+    interpreter: &mut interpreter::Interpreter,
+    stmts: &[Box<stmt::Stmt>],
+    run: impl FnOnce(&mut interpreter::Interpreter, Vec<&stmt::Stmt>) -> Result<T, String>,
+) -> Result<T, String> {
+    let config = interpreter.config();
+    if interpreter.environment.borrow().enclosing.is_none() {
+        let mut resolver = resolver::Resolver::for_interpreter(interpreter);
+        for stmt in stmts {
+            if resolver.resolve(stmt).is_err() {
+                resolver = resolver::Resolver::for_interpreter(interpreter);
+            }
+        }
+    }
+
+    let result = run(interpreter, stmts.iter().map(|b| b.as_ref()).collect());
+    config.clear_globals();
+    result
+}
+
+// What `run_collect` saw while running a program
+#[derive(Debug, Default, PartialEq)]
+pub struct RunReport {
+    // Every statement that ran, counting each pass through a loop body and
+    // the statements inside blocks and function calls
+    pub statements: usize,
+    // `fn` declarations that ran
+    pub functions: usize,
+    // Everything printed, which doesn't reach the interpreter's own output
+    pub output: String,
+}
+
+// Runs like `run`, capturing the output and counting what ran
+pub fn run_collect(
+    interpreter: &mut interpreter::Interpreter,
+    contents: &str,
+) -> Result<RunReport, String> {
+    use std::{cell::RefCell, rc::Rc};
+
+    let stats = Rc::new(environment::RunStats::default());
+    let buffer = Rc::new(RefCell::new(Vec::<u8>::new()));
+    let config = interpreter.config();
+    let previous = config.replace_output(Some(buffer.clone()));
+    config.set_stats(Some(stats.clone()));
+
+    let result = run(interpreter, contents);
+    config.replace_output(previous);
+    config.set_stats(None);
+    result?;
+
+    let output = String::from_utf8_lossy(&buffer.borrow()).into_owned();
+    Ok(RunReport {
+        statements: stats.statements.get(),
+        functions: stats.functions.get(),
+        output,
+    })
+}
+
+// Runs like `run` and returns the value of the last top-level expression statement
+pub fn run_last(
+    interpreter: &mut interpreter::Interpreter,
+    contents: &str,
+) -> Result<Option<expr::LiteralValue>, String> {
+    let stmts = parse_program_for(interpreter, contents)?;
+    run_resolved(
+        interpreter,
+        &stmts,
+        interpreter::Interpreter::interpret_last,
+    )
+}
+
+// Runs one line of REPL input. A line missing its final `;` is retried with
+// one appended, and the value of a trailing expression statement is returned.
+pub fn feed(
+    interpreter: &mut interpreter::Interpreter,
+    line: &str,
+) -> Result<Option<expr::LiteralValue>, String> {
+    let stmts = match parse_program_for(interpreter, line) {
+        Ok(stmts) => stmts,
+        Err(err) => {
+            let line = line.trim_end();
+            if line.is_empty() || line.ends_with(';') || line.ends_with('}') {
+                return Err(err);
+            }
+            parse_program_for(interpreter, &format!("{line};")).map_err(|_| err)?
+        }
+    };
+    run_resolved(
+        interpreter,
+        &stmts,
+        interpreter::Interpreter::interpret_last,
+    )
+}
+
+// Lexes and parses a whole program, printing parser warnings to stderr
+pub fn parse_program(contents: &str) -> Result<Vec<Box<stmt::Stmt>>, String> {
+    parse_tokens(lex(contents)?)
+}
+
+// `parse_program` for code `interpreter` will run, which decides whether
+// `print` is a statement or a function
+pub fn parse_program_for(
+    interpreter: &interpreter::Interpreter,
+    contents: &str,
+) -> Result<Vec<Box<stmt::Stmt>>, String> {
+    parse_tokens(lexer_for(interpreter, contents).scan_tokens()?)
+}
+
+// `parse_program_for` through `Parser::parse_program_or_expr`
+pub fn parse_program_or_expr_for(
+    interpreter: &interpreter::Interpreter,
+    contents: &str,
+) -> Result<Vec<Box<stmt::Stmt>>, String> {
+    let tokens = lexer_for(interpreter, contents).scan_tokens()?;
+    let mut parser = parser::Parser::new(tokens.to_vec());
+    let stmts = parser.parse_program_or_expr()?;
+    print_warnings(&parser);
+
+    Ok(stmts)
+}
+
+fn lexer_for<'a>(interpreter: &interpreter::Interpreter, contents: &'a str) -> lexer::Lexer<'a> {
     let mut lexer = lexer::Lexer::new(contents);
-    let tokens = lexer.scan_tokens()?;
+    lexer.set_print_keyword(!interpreter.print_function());
+    lexer
+}
 
+// The parsing half of `parse_program`, for tokens from a configured `Lexer`
+pub fn parse_tokens(
+    tokens: std::rc::Rc<Vec<lexer::Token>>,
+) -> Result<Vec<Box<stmt::Stmt>>, String> {
     let mut parser = parser::Parser::new(tokens.to_vec());
     let stmts = parser.parse()?;
-    interpreter.interpret(stmts.iter().map(|b| b.as_ref()).collect())?;
+    print_warnings(&parser);
 
-    Ok(())
+    Ok(stmts)
+}
+
+fn print_warnings(parser: &parser::Parser) {
+    for warning in parser.warnings() {
+        eprintln!("Warning: {warning}");
+    }
 }
@@ -0,0 +1,777 @@
+use crate::{
+    environment::{Environment, LogLevel},
+    expr::{HashableValue, LiteralValue, NativeFn, ScopedFn},
+    json,
+};
+use std::{
+    cell::RefCell, cmp::Ordering, collections::HashMap, fmt::Write, rc::Rc, time::SystemTime,
+};
+
+fn define_native(
+    env: &mut Environment,
+    name: &str,
+    arity: usize,
+    fun: fn(&[LiteralValue]) -> Result<LiteralValue, String>,
+) {
+    define_native_range(env, name, arity, arity, fun);
+}
+
+// For natives whose trailing arguments are optional, `fun` gets only the ones passed
+fn define_native_range(
+    env: &mut Environment,
+    name: &str,
+    min_arity: usize,
+    arity: usize,
+    fun: fn(&[LiteralValue]) -> Result<LiteralValue, String>,
+) {
+    env.define(
+        name.into(),
+        LiteralValue::Callable {
+            name: name.into(),
+            min_arity,
+            arity,
+            param_names: vec![],
+            fun: Rc::new(fun),
+            scoped: None,
+        },
+    );
+}
+
+fn define_scoped_native(env: &mut Environment, name: &str, arity: usize, scoped: ScopedFn) {
+    define_scoped_native_range(env, name, arity, arity, scoped);
+}
+
+// Only direct calls get the environment, anything calling `fun` itself gets an error
+fn define_scoped_native_range(
+    env: &mut Environment,
+    name: &str,
+    min_arity: usize,
+    arity: usize,
+    scoped: ScopedFn,
+) {
+    let native = name.to_string();
+    env.define(
+        name.into(),
+        LiteralValue::Callable {
+            name: name.into(),
+            min_arity,
+            arity,
+            param_names: vec![],
+            fun: Rc::new(move |_| Err(format!("{native} can only be called directly"))),
+            scoped: Some(scoped),
+        },
+    );
+}
+
+// For interpreters where `print` is a function rather than a statement
+pub fn define_print(env: &mut Environment) {
+    define_scoped_native_range(env, "print", 0, 255, print_impl);
+}
+
+// Prints its arguments on one line, separated by spaces
+fn print_impl(env: &Environment, args: &[LiteralValue]) -> Result<LiteralValue, String> {
+    let line = args
+        .iter()
+        .map(|arg| arg.to_string())
+        .collect::<Vec<String>>()
+        .join(" ");
+    env.write_line(&line)?;
+
+    // One argument is recorded as itself, like the statement would
+    let printed = match args {
+        [arg] => arg.clone(),
+        _ => LiteralValue::StringValue(line),
+    };
+    env.config().set_last_print(Some(printed));
+    Ok(LiteralValue::Nil)
+}
+
+pub fn define_natives(env: &mut Environment) {
+    define_native(env, "clock", 0, clock_impl);
+    define_native(env, "assert", 1, assert_impl);
+    define_native(env, "assert_eq", 2, assert_eq_impl);
+    define_native(env, "keys", 1, keys_impl);
+    define_native(env, "values", 1, values_impl);
+    define_native(env, "reduce", 3, reduce_impl);
+    define_native(env, "to_json", 1, to_json_impl);
+    define_native(env, "from_json", 1, from_json_impl);
+    define_native(env, "pretty", 1, pretty_impl);
+    define_native(env, "copy", 1, copy_impl);
+    define_native(env, "num", 1, num_impl);
+    define_scoped_native(env, "dump_env", 0, dump_env_impl);
+    define_scoped_native(env, "log", 2, log_impl);
+    define_math_natives(env);
+    define_string_natives(env);
+    define_array_natives(env);
+}
+
+fn to_json_impl(args: &[LiteralValue]) -> Result<LiteralValue, String> {
+    Ok(LiteralValue::StringValue(json::to_json(&args[0])?))
+}
+
+fn from_json_impl(args: &[LiteralValue]) -> Result<LiteralValue, String> {
+    match &args[0] {
+        LiteralValue::StringValue(text) => json::from_json(text),
+        other => Err(format!(
+            "from_json expected a String got {}",
+            other.as_ref()
+        )),
+    }
+}
+
+// "42" gives an Int and "42.0" a Number, like the same text written as a
+// literal. Exponents such as "4e1" are accepted too and give a Number, though
+// literals can't be written that way. Integers too big for an Int become a
+// Number.
+fn num_impl(args: &[LiteralValue]) -> Result<LiteralValue, String> {
+    let text = match &args[0] {
+        LiteralValue::StringValue(s) => s.trim(),
+        other => return Err(format!("num expected a String got {}", other.as_ref())),
+    };
+
+    // Rust would also read words like `inf` and `nan`, rl literals can't be those
+    let numeric = text.chars().any(|c| c.is_ascii_digit())
+        && text
+            .chars()
+            .all(|c| c.is_ascii_digit() || matches!(c, '+' | '-' | '.' | 'e' | 'E'));
+    let is_float = text.contains(['.', 'e', 'E']);
+
+    let int = text.parse::<i64>().ok().filter(|_| numeric && !is_float);
+    match (int, text.parse::<f64>()) {
+        (Some(x), _) => Ok(LiteralValue::Int(x)),
+        (None, Ok(x)) if numeric => Ok(LiteralValue::Number(x)),
+        _ => Err(format!("num could not parse '{text}' as a Number")),
+    }
+}
+
+fn copy_impl(args: &[LiteralValue]) -> Result<LiteralValue, String> {
+    Ok(deep_copy(&args[0], &mut HashMap::new()))
+}
+
+// Arrays and maps get fresh storage all the way down. `copies` maps each
+// collection already copied to its copy, so one reached twice, or one that
+// contains itself, is copied once and the copy keeps the same shape.
+fn deep_copy(value: &LiteralValue, copies: &mut HashMap<*const (), LiteralValue>) -> LiteralValue {
+    match value {
+        LiteralValue::Array(elements) => {
+            let ptr = Rc::as_ptr(elements) as *const ();
+            if let Some(copy) = copies.get(&ptr) {
+                return copy.clone();
+            }
+            let copy = Rc::new(RefCell::new(vec![]));
+            copies.insert(ptr, LiteralValue::Array(copy.clone()));
+            let items: Vec<LiteralValue> = elements.borrow().clone();
+            *copy.borrow_mut() = items.iter().map(|item| deep_copy(item, copies)).collect();
+            LiteralValue::Array(copy)
+        }
+        LiteralValue::Map(entries) => {
+            let ptr = Rc::as_ptr(entries) as *const ();
+            if let Some(copy) = copies.get(&ptr) {
+                return copy.clone();
+            }
+            let copy = Rc::new(RefCell::new(vec![]));
+            copies.insert(ptr, LiteralValue::Map(copy.clone()));
+            let items: Vec<(HashableValue, LiteralValue)> = entries.borrow().clone();
+            *copy.borrow_mut() = items
+                .iter()
+                .map(|(key, item)| (key.clone(), deep_copy(item, copies)))
+                .collect();
+            LiteralValue::Map(copy)
+        }
+        other => other.clone(),
+    }
+}
+
+fn pretty_impl(args: &[LiteralValue]) -> Result<LiteralValue, String> {
+    let mut out = String::new();
+    write_pretty(&args[0], 0, &mut vec![], &mut out);
+    Ok(LiteralValue::StringValue(out))
+}
+
+// Puts every element and entry of a nested collection on its own line. A
+// collection that contains itself shows up as `[...]` or `{...}` the second time.
+fn write_pretty(value: &LiteralValue, depth: usize, open: &mut Vec<*const ()>, out: &mut String) {
+    let (ptr, items, brackets) = match value {
+        LiteralValue::Array(elements) if !elements.borrow().is_empty() => {
+            let items: Vec<(Option<String>, LiteralValue)> = elements
+                .borrow()
+                .iter()
+                .map(|element| (None, element.clone()))
+                .collect();
+            (Rc::as_ptr(elements) as *const (), items, ('[', ']'))
+        }
+        LiteralValue::Map(entries) if !entries.borrow().is_empty() => {
+            let items = entries
+                .borrow()
+                .iter()
+                .map(|(key, value)| (Some(key.to_string()), value.clone()))
+                .collect();
+            (Rc::as_ptr(entries) as *const (), items, ('{', '}'))
+        }
+        other => {
+            let _ = write!(out, "{other}");
+            return;
+        }
+    };
+
+    if open.contains(&ptr) {
+        out.push(brackets.0);
+        out.push_str("...");
+        out.push(brackets.1);
+        return;
+    }
+
+    open.push(ptr);
+    out.push(brackets.0);
+    for (i, (key, value)) in items.iter().enumerate() {
+        out.push_str(if i == 0 { "\n" } else { ",\n" });
+        out.push_str(&"  ".repeat(depth + 1));
+        if let Some(key) = key {
+            out.push_str(key);
+            out.push_str(": ");
+        }
+        write_pretty(value, depth + 1, open, out);
+    }
+    out.push('\n');
+    out.push_str(&"  ".repeat(depth));
+    out.push(brackets.1);
+    open.pop();
+}
+
+fn define_math_natives(env: &mut Environment) {
+    define_native(env, "nan", 0, nan_impl);
+    define_native(env, "inf", 0, inf_impl);
+    define_native(env, "is_nan", 1, is_nan_impl);
+    define_native(env, "is_finite", 1, is_finite_impl);
+    define_native(env, "clamp", 3, clamp_impl);
+    define_native(env, "sign", 1, sign_impl);
+    define_native(env, "pi", 0, pi_impl);
+    define_native(env, "sin", 1, sin_impl);
+    define_native(env, "cos", 1, cos_impl);
+    define_native(env, "tan", 1, tan_impl);
+    define_native(env, "atan2", 2, atan2_impl);
+    define_native(env, "to_radix", 2, to_radix_impl);
+    define_native(env, "print_fixed", 2, print_fixed_impl);
+}
+
+fn define_string_natives(env: &mut Environment) {
+    define_native(env, "substr", 3, substr_impl);
+    define_native(env, "lines", 1, lines_impl);
+    define_native(env, "trim", 1, trim_impl);
+    define_native(env, "starts_with", 2, starts_with_impl);
+    define_native(env, "ends_with", 2, ends_with_impl);
+    define_native(env, "replace", 3, replace_impl);
+}
+
+fn define_array_natives(env: &mut Environment) {
+    define_native(env, "zip", 2, zip_impl);
+    define_native_range(env, "sort", 1, 2, sort_impl);
+    define_native(env, "reverse", 1, reverse_impl);
+    define_native(env, "join", 2, join_impl);
+    define_native(env, "min", 1, min_impl);
+    define_native(env, "max", 1, max_impl);
+}
+
+fn array_arg(native: &str, value: &LiteralValue) -> Result<Vec<LiteralValue>, String> {
+    match value {
+        LiteralValue::Array(elements) => Ok(elements.borrow().clone()),
+        other => Err(format!("{native} expected an Array got {}", other.as_ref())),
+    }
+}
+
+fn string_arg<'a>(native: &str, value: &'a LiteralValue) -> Result<&'a str, String> {
+    match value {
+        LiteralValue::StringValue(s) => Ok(s),
+        other => Err(format!("{native} expected a String got {}", other.as_ref())),
+    }
+}
+
+fn array(elements: Vec<LiteralValue>) -> LiteralValue {
+    LiteralValue::Array(Rc::new(RefCell::new(elements)))
+}
+
+fn number_arg(native: &str, value: &LiteralValue) -> Result<f64, String> {
+    match value {
+        LiteralValue::Number(_) | LiteralValue::Int(_) => Ok(value.as_f64().unwrap_or_default()),
+        other => Err(format!("{native} expected a Number got {}", other.as_ref())),
+    }
+}
+
+fn count_arg(native: &str, value: &LiteralValue) -> Result<usize, String> {
+    let x = number_arg(native, value)?;
+    if x < 0.0 || x.fract() != 0.0 {
+        return Err(format!(
+            "{native} expected a whole non-negative Number got {x}"
+        ));
+    }
+    Ok(x as usize)
+}
+
+// Positions count characters rather than bytes, so the result never ends
+// in the middle of one
+fn substr_impl(args: &[LiteralValue]) -> Result<LiteralValue, String> {
+    let s = string_arg("substr", &args[0])?;
+    let start = count_arg("substr", &args[1])?;
+    let count = count_arg("substr", &args[2])?;
+
+    let len = s.chars().count();
+    if start > len || count > len - start {
+        return Err(format!(
+            "substr index out of range, {start} + {count} is past the end of a string of length {len}"
+        ));
+    }
+    Ok(LiteralValue::StringValue(
+        s.chars().skip(start).take(count).collect(),
+    ))
+}
+
+// Splits on "\n" and "\r\n", a final newline doesn't add an empty line
+fn lines_impl(args: &[LiteralValue]) -> Result<LiteralValue, String> {
+    let s = string_arg("lines", &args[0])?;
+    let lines = s
+        .lines()
+        .map(|line| LiteralValue::StringValue(line.to_string()))
+        .collect();
+    Ok(array(lines))
+}
+
+fn trim_impl(args: &[LiteralValue]) -> Result<LiteralValue, String> {
+    let s = string_arg("trim", &args[0])?;
+    Ok(LiteralValue::StringValue(s.trim().to_string()))
+}
+
+fn starts_with_impl(args: &[LiteralValue]) -> Result<LiteralValue, String> {
+    let s = string_arg("starts_with", &args[0])?;
+    let prefix = string_arg("starts_with", &args[1])?;
+    Ok(LiteralValue::from(s.starts_with(prefix)))
+}
+
+fn ends_with_impl(args: &[LiteralValue]) -> Result<LiteralValue, String> {
+    let s = string_arg("ends_with", &args[0])?;
+    let suffix = string_arg("ends_with", &args[1])?;
+    Ok(LiteralValue::from(s.ends_with(suffix)))
+}
+
+// Replaces every occurrence, left to right without overlaps
+fn replace_impl(args: &[LiteralValue]) -> Result<LiteralValue, String> {
+    let s = string_arg("replace", &args[0])?;
+    let from = string_arg("replace", &args[1])?;
+    let to = string_arg("replace", &args[2])?;
+    if from.is_empty() {
+        return Err("replace expected a non-empty String to replace".into());
+    }
+    Ok(LiteralValue::StringValue(s.replace(from, to)))
+}
+
+// Pairs stop at the end of the shorter array
+fn zip_impl(args: &[LiteralValue]) -> Result<LiteralValue, String> {
+    let left = array_arg("zip", &args[0])?;
+    let right = array_arg("zip", &args[1])?;
+    let pairs = left
+        .into_iter()
+        .zip(right)
+        .map(|(a, b)| array(vec![a, b]))
+        .collect();
+    Ok(array(pairs))
+}
+
+// Returns a sorted copy. Without a comparator numbers sort numerically and
+// strings lexically, with one `cmp(a, b)` returns a Number below 0 when `a`
+// goes first, 0 when they tie and above 0 when `b` goes first.
+fn sort_impl(args: &[LiteralValue]) -> Result<LiteralValue, String> {
+    let elements = array_arg("sort", &args[0])?;
+    let sorted = match args.get(1) {
+        None => merge_sort(elements, &mut |a, b| natural_order("sort", a, b))?,
+        Some(LiteralValue::Callable {
+            name,
+            min_arity,
+            arity,
+            fun,
+            ..
+        }) => {
+            if !(*min_arity..=*arity).contains(&2) {
+                return Err(format!(
+                    "sort expected a callable with 2 arguments, {name} takes {arity}"
+                ));
+            }
+            merge_sort(elements, &mut |a, b| comparator_order(fun, a, b))?
+        }
+        Some(other) => return Err(format!("sort expected a Callable got {}", other.as_ref())),
+    };
+    Ok(array(sorted))
+}
+
+// NaN sorts after every other number and ties with itself, so `sort` puts
+// it last, `max` returns it whenever there is one and `min` only when
+// everything is NaN
+fn natural_order(native: &str, a: &LiteralValue, b: &LiteralValue) -> Result<Ordering, String> {
+    let order = match (a, b) {
+        (LiteralValue::Int(x), LiteralValue::Int(y)) => Some(x.cmp(y)),
+        (
+            LiteralValue::Int(_) | LiteralValue::Number(_),
+            LiteralValue::Int(_) | LiteralValue::Number(_),
+        ) => {
+            let (x, y) = (
+                a.as_f64().unwrap_or_default(),
+                b.as_f64().unwrap_or_default(),
+            );
+            Some(match (x.is_nan(), y.is_nan()) {
+                (true, true) => Ordering::Equal,
+                (true, false) => Ordering::Greater,
+                (false, true) => Ordering::Less,
+                (false, false) => x.partial_cmp(&y).unwrap_or(Ordering::Equal),
+            })
+        }
+        (LiteralValue::StringValue(x), LiteralValue::StringValue(y)) => Some(x.cmp(y)),
+        _ if a.as_ref() != b.as_ref() => {
+            return Err(format!(
+                "{native} cannot compare {} with {}",
+                a.as_ref(),
+                b.as_ref()
+            ));
+        }
+        _ => None,
+    };
+    order.ok_or_else(|| format!("{native} cannot order {a} and {b}"))
+}
+
+fn min_impl(args: &[LiteralValue]) -> Result<LiteralValue, String> {
+    extreme("min", &args[0], Ordering::Less)
+}
+
+fn max_impl(args: &[LiteralValue]) -> Result<LiteralValue, String> {
+    extreme("max", &args[0], Ordering::Greater)
+}
+
+// The first element that orders `wanted` against all the others
+fn extreme(native: &str, value: &LiteralValue, wanted: Ordering) -> Result<LiteralValue, String> {
+    let mut elements = array_arg(native, value)?.into_iter();
+    let mut best = elements
+        .next()
+        .ok_or_else(|| format!("{native} expected a non-empty Array"))?;
+    for element in elements {
+        if natural_order(native, &element, &best)? == wanted {
+            best = element;
+        }
+    }
+    Ok(best)
+}
+
+fn comparator_order(
+    fun: &NativeFn,
+    a: &LiteralValue,
+    b: &LiteralValue,
+) -> Result<Ordering, String> {
+    let result = fun(&[a.clone(), b.clone()])?;
+    // NaN has no order either way, so it is rejected like a non-Number
+    match result.as_f64().and_then(|x| x.partial_cmp(&0.0)) {
+        Some(order) => Ok(order),
+        None => Err(format!(
+            "sort expected the comparator to return a Number got {result}"
+        )),
+    }
+}
+
+// A stable merge sort that stops at the first failed comparison. `slice::sort_by`
+// can't report errors and may panic when a comparator is inconsistent.
+fn merge_sort(
+    mut items: Vec<LiteralValue>,
+    cmp: &mut dyn FnMut(&LiteralValue, &LiteralValue) -> Result<Ordering, String>,
+) -> Result<Vec<LiteralValue>, String> {
+    if items.len() <= 1 {
+        return Ok(items);
+    }
+
+    let right = items.split_off(items.len() / 2);
+    let left = merge_sort(items, cmp)?;
+    let right = merge_sort(right, cmp)?;
+
+    let mut merged = Vec::with_capacity(left.len() + right.len());
+    let mut left = left.into_iter().peekable();
+    let mut right = right.into_iter().peekable();
+    while let (Some(a), Some(b)) = (left.peek(), right.peek()) {
+        if cmp(a, b)? == Ordering::Greater {
+            merged.extend(right.next());
+        } else {
+            merged.extend(left.next());
+        }
+    }
+    merged.extend(left);
+    merged.extend(right);
+    Ok(merged)
+}
+
+// Strings reverse by character, so multi-byte ones stay intact
+fn reverse_impl(args: &[LiteralValue]) -> Result<LiteralValue, String> {
+    match &args[0] {
+        LiteralValue::StringValue(s) => Ok(LiteralValue::StringValue(s.chars().rev().collect())),
+        LiteralValue::Array(elements) => {
+            Ok(array(elements.borrow().iter().rev().cloned().collect()))
+        }
+        other => Err(format!(
+            "reverse expected a String or Array got {}",
+            other.as_ref()
+        )),
+    }
+}
+
+// Elements render like `print` shows them
+fn join_impl(args: &[LiteralValue]) -> Result<LiteralValue, String> {
+    let elements = array_arg("join", &args[0])?;
+    let separator = match &args[1] {
+        LiteralValue::StringValue(s) => s,
+        other => {
+            return Err(format!(
+                "join expected a String separator got {}",
+                other.as_ref()
+            ));
+        }
+    };
+    let parts: Vec<String> = elements.iter().map(|element| element.to_string()).collect();
+    Ok(LiteralValue::StringValue(parts.join(separator)))
+}
+
+fn clock_impl(_args: &[LiteralValue]) -> Result<LiteralValue, String> {
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_err(|_| "Could not get system time".to_string())?
+        .as_secs_f64();
+    Ok(LiteralValue::Number(now))
+}
+
+fn assert_impl(args: &[LiteralValue]) -> Result<LiteralValue, String> {
+    if args[0].truthiness()? {
+        Ok(LiteralValue::Nil)
+    } else {
+        Err(format!("assert failed: {} is not truthy", args[0]))
+    }
+}
+
+fn assert_eq_impl(args: &[LiteralValue]) -> Result<LiteralValue, String> {
+    let (got, expected) = (&args[0], &args[1]);
+    if got == expected {
+        Ok(LiteralValue::Nil)
+    } else {
+        Err(format!(
+            "assert_eq failed: expected {} got {}",
+            expected, got
+        ))
+    }
+}
+
+fn keys_impl(args: &[LiteralValue]) -> Result<LiteralValue, String> {
+    match &args[0] {
+        LiteralValue::Map(entries) => {
+            let keys = entries
+                .borrow()
+                .iter()
+                .map(|(key, _)| key.to_value())
+                .collect();
+            Ok(LiteralValue::Array(Rc::new(RefCell::new(keys))))
+        }
+        other => Err(format!("keys expected a Map got {}", other.as_ref())),
+    }
+}
+
+fn values_impl(args: &[LiteralValue]) -> Result<LiteralValue, String> {
+    match &args[0] {
+        LiteralValue::Map(entries) => {
+            let values = entries
+                .borrow()
+                .iter()
+                .map(|(_, value)| value.clone())
+                .collect();
+            Ok(LiteralValue::Array(Rc::new(RefCell::new(values))))
+        }
+        other => Err(format!("values expected a Map got {}", other.as_ref())),
+    }
+}
+
+// Prints every variable visible from the calling scope, innermost scope first.
+// The natives are left out of the globals to keep the dump readable.
+fn dump_env_impl(env: &Environment, _args: &[LiteralValue]) -> Result<LiteralValue, String> {
+    let mut natives = Environment::new();
+    define_natives(&mut natives);
+
+    let mut lines = vec![];
+    dump_scope(env, &natives, &mut lines);
+    for line in lines {
+        env.write_line(line)?;
+    }
+    Ok(LiteralValue::Nil)
+}
+
+// `log(level, msg)` hands `msg` to the interpreter's log sink
+fn log_impl(env: &Environment, args: &[LiteralValue]) -> Result<LiteralValue, String> {
+    let level = match string_arg("log", &args[0])? {
+        "info" => LogLevel::Info,
+        "error" => LogLevel::Error,
+        "message" => LogLevel::Message,
+        other => {
+            return Err(format!(
+                "log expected a level of \"info\", \"error\" or \"message\" got '{other}'"
+            ));
+        }
+    };
+    env.log(level, &format!("{}", args[1]))?;
+    Ok(LiteralValue::Nil)
+}
+
+fn dump_scope(env: &Environment, natives: &Environment, lines: &mut Vec<String>) {
+    lines.push(format!("-- scope {}", env.depth()));
+    for (name, value) in env.local_values() {
+        let is_native = natives.is_defined_local(&name)
+            && matches!(&value, LiteralValue::Callable { param_names, .. } if param_names.is_empty());
+        if !is_native {
+            lines.push(format!("{name} = {value}"));
+        }
+    }
+
+    if let Some(enclosing) = &env.enclosing {
+        dump_scope(&enclosing.borrow(), natives, lines);
+    }
+}
+
+fn reduce_impl(args: &[LiteralValue]) -> Result<LiteralValue, String> {
+    let elements = match &args[0] {
+        LiteralValue::Array(elements) => elements.borrow().clone(),
+        other => return Err(format!("reduce expected an Array got {}", other.as_ref())),
+    };
+
+    match &args[2] {
+        LiteralValue::Callable {
+            name,
+            min_arity,
+            arity,
+            fun,
+            ..
+        } => {
+            if !(*min_arity..=*arity).contains(&2) {
+                return Err(format!(
+                    "reduce expected a callable with 2 arguments, {name} takes {arity}"
+                ));
+            }
+
+            let mut acc = args[1].clone();
+            for element in elements {
+                acc = fun(&[acc, element])?;
+            }
+            Ok(acc)
+        }
+        other => Err(format!("reduce expected a Callable got {}", other.as_ref())),
+    }
+}
+
+fn nan_impl(_args: &[LiteralValue]) -> Result<LiteralValue, String> {
+    Ok(LiteralValue::Number(f64::NAN))
+}
+
+fn inf_impl(_args: &[LiteralValue]) -> Result<LiteralValue, String> {
+    Ok(LiteralValue::Number(f64::INFINITY))
+}
+
+fn is_nan_impl(args: &[LiteralValue]) -> Result<LiteralValue, String> {
+    Ok(number_arg("is_nan", &args[0])?.is_nan().into())
+}
+
+fn is_finite_impl(args: &[LiteralValue]) -> Result<LiteralValue, String> {
+    Ok(number_arg("is_finite", &args[0])?.is_finite().into())
+}
+
+fn clamp_impl(args: &[LiteralValue]) -> Result<LiteralValue, String> {
+    let x = number_arg("clamp", &args[0])?;
+    let lo = number_arg("clamp", &args[1])?;
+    let hi = number_arg("clamp", &args[2])?;
+    if lo > hi || lo.is_nan() || hi.is_nan() {
+        return Err(format!("clamp expected lo <= hi got {lo} and {hi}"));
+    }
+
+    Ok(LiteralValue::Number(x.max(lo).min(hi)))
+}
+
+fn sign_impl(args: &[LiteralValue]) -> Result<LiteralValue, String> {
+    let x = number_arg("sign", &args[0])?;
+    let sign = if x > 0.0 {
+        1.0
+    } else if x < 0.0 {
+        -1.0
+    } else {
+        // Zero keeps 0 and NaN stays NaN
+        x * 0.0
+    };
+    Ok(LiteralValue::Number(sign))
+}
+
+fn pi_impl(_args: &[LiteralValue]) -> Result<LiteralValue, String> {
+    Ok(LiteralValue::Number(std::f64::consts::PI))
+}
+
+// Angles are in radians
+fn sin_impl(args: &[LiteralValue]) -> Result<LiteralValue, String> {
+    Ok(LiteralValue::Number(number_arg("sin", &args[0])?.sin()))
+}
+
+fn cos_impl(args: &[LiteralValue]) -> Result<LiteralValue, String> {
+    Ok(LiteralValue::Number(number_arg("cos", &args[0])?.cos()))
+}
+
+fn tan_impl(args: &[LiteralValue]) -> Result<LiteralValue, String> {
+    Ok(LiteralValue::Number(number_arg("tan", &args[0])?.tan()))
+}
+
+fn atan2_impl(args: &[LiteralValue]) -> Result<LiteralValue, String> {
+    let y = number_arg("atan2", &args[0])?;
+    let x = number_arg("atan2", &args[1])?;
+    Ok(LiteralValue::Number(y.atan2(x)))
+}
+
+// Lowercase digits, with a leading '-' for negative values
+fn to_radix_impl(args: &[LiteralValue]) -> Result<LiteralValue, String> {
+    let value = match &args[0] {
+        LiteralValue::Int(x) => *x,
+        LiteralValue::Number(x) if x.fract() == 0.0 && x.abs() < i64::MAX as f64 => *x as i64,
+        other => {
+            return Err(format!("to_radix expected a whole Number got {other}"));
+        }
+    };
+    let radix = count_arg("to_radix", &args[1])?;
+    if !(2..=36).contains(&radix) {
+        return Err(format!(
+            "to_radix expected a radix between 2 and 36 got {radix}"
+        ));
+    }
+
+    let mut magnitude = value.unsigned_abs();
+    let mut digits = vec![];
+    loop {
+        let digit = (magnitude % radix as u64) as u32;
+        digits.push(char::from_digit(digit, radix as u32).unwrap_or('?'));
+        magnitude /= radix as u64;
+        if magnitude == 0 {
+            break;
+        }
+    }
+    if value < 0 {
+        digits.push('-');
+    }
+    Ok(LiteralValue::StringValue(
+        digits.into_iter().rev().collect(),
+    ))
+}
+
+// Past what an f64 can tell apart, and small enough not to allocate much
+const MAX_FIXED_PRECISION: usize = 100;
+
+// Rounds the exact value of the float, so `print_fixed(2.675, 2)` is "2.67"
+// since 2.675 is stored as slightly less
+fn print_fixed_impl(args: &[LiteralValue]) -> Result<LiteralValue, String> {
+    let value = number_arg("print_fixed", &args[0])?;
+    let precision = count_arg("print_fixed", &args[1])?;
+    if precision > MAX_FIXED_PRECISION {
+        return Err(format!(
+            "print_fixed expected a precision of at most {MAX_FIXED_PRECISION} got {precision}"
+        ));
+    }
+
+    Ok(LiteralValue::StringValue(format!("{value:.precision$}")))
+}
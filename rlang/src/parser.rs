@@ -1,22 +1,99 @@
 use crate::expr::{Expr, LiteralValue};
 use crate::lexer::{Token, TokenType};
-use crate::stmt::Stmt;
+use crate::stmt::{Stmt, SwitchCase};
 
 #[derive(Debug, Clone)]
 pub struct Parser {
     tokens: Vec<Token>,
     current: usize,
+    // Labels of the loops enclosing the current statement, innermost last
+    loops: Vec<Option<String>>,
+    // How many switches enclose the current statement, `break` also ends those
+    switches: usize,
+    warnings: Vec<String>,
+    // How deeply the rule being parsed is nested, see `nested`
+    depth: usize,
+    max_depth: usize,
 }
 
+// Lower than the 256 one might expect: in a debug build a level of
+// parentheses goes through a dozen parser frames, about 38KB of stack, and
+// `rl check` on 256 levels overflows the 8MB main thread stack at around 230.
+// 128 levels stay under 5MB.
+pub const DEFAULT_MAX_DEPTH: usize = 128;
+
 #[derive(Debug)]
 enum FunctionKind {
     Function,
+    #[allow(dead_code)]
     Method,
 }
 
 impl Parser {
     pub fn new(tokens: Vec<Token>) -> Self {
-        Self { tokens, current: 0 }
+        Self {
+            tokens,
+            current: 0,
+            loops: vec![],
+            switches: 0,
+            warnings: vec![],
+            depth: 0,
+            max_depth: DEFAULT_MAX_DEPTH,
+        }
+    }
+
+    // How many expressions, statements or function bodies may nest inside
+    // each other before parsing gives up
+    pub fn set_max_depth(&mut self, max_depth: usize) {
+        self.max_depth = max_depth;
+    }
+
+    // Parses the tokens as exactly one expression, with no trailing `;`
+    pub fn parse_expression(&mut self) -> Result<Expr, String> {
+        let expr = self.expression()?;
+        if !self.is_end() {
+            let token = self.peek();
+            return Err(format!(
+                "Line {}: unexpected '{}' after expression",
+                token.line_number, token.lexme
+            ));
+        }
+
+        Ok(expr)
+    }
+
+    // A program, or else a lone expression such as `6 * 7` wrapped in a
+    // `print` so running it shows the value. Errors are the program's when
+    // the input isn't an expression either.
+    pub fn parse_program_or_expr(&mut self) -> Result<Vec<Box<Stmt>>, String> {
+        let warnings = self.warnings.len();
+        let err = match self.parse() {
+            Ok(stmts) => return Ok(stmts),
+            Err(err) => err,
+        };
+
+        self.current = 0;
+        self.loops.clear();
+        self.switches = 0;
+        self.depth = 0;
+        self.warnings.truncate(warnings);
+        match self.parse_expression() {
+            Ok(expression) => Ok(vec![Box::new(Stmt::Print { expression })]),
+            Err(_) => Err(err),
+        }
+    }
+
+    pub fn warnings(&self) -> &[String] {
+        &self.warnings
+    }
+
+    fn warn_assignment_condition(&mut self, condition: &Expr, statement: &str) {
+        if let Expr::Assign { name, value: _ } = condition {
+            self.warnings.push(format!(
+                "Line {}: assignment to '{}' used as {} condition, did you mean '=='? Wrap it in parentheses to silence this",
+                name.line_number, name.lexme, statement
+            ));
+        }
     }
 
     pub fn parse(&mut self) -> Result<Vec<Box<Stmt>>, String> {
@@ -24,12 +101,17 @@ impl Parser {
         let mut errs = vec![];
 
         while !self.is_end() {
-            let stmt = self.declaration();
+            let start = self.current;
+            let stmt = if self.match_token(&TokenType::Import) {
+                self.import_statement()
+            } else {
+                self.declaration()
+            };
             match stmt {
                 Ok(s) => stmts.push(s),
                 Err(msg) => {
                     errs.push(msg);
-                    self.synchronize();
+                    self.synchronize(start);
                 }
             }
         }
@@ -45,7 +127,9 @@ impl Parser {
         if self.match_token(&TokenType::Var) {
             self.var_declaration()
         } else if self.match_token(&TokenType::Fn) {
-            self.function(FunctionKind::Function)
+            self.nested("Statement", |parser| {
+                parser.function(FunctionKind::Function)
+            })
         } else {
             self.statement()
         }
@@ -58,7 +142,7 @@ impl Parser {
             &format!("Expected '(' after {kind:?} name"),
         )?;
 
-        let mut params: Vec<Token> = vec![];
+        let mut params: Vec<(Token, Option<Expr>)> = vec![];
         if !self.check(TokenType::RightParen) {
             loop {
                 if params.len() >= 255 {
@@ -69,7 +153,19 @@ impl Parser {
                 }
 
                 let param = self.consume(TokenType::Identifier, "Expected paramter name")?;
-                params.push(param);
+                let default = if self.match_token(&TokenType::Equal) {
+                    Some(self.expression()?)
+                } else {
+                    None
+                };
+
+                if default.is_none() && params.iter().any(|(_, d)| d.is_some()) {
+                    return Err(format!(
+                        "Line {}: Parameter '{}' without default follows a parameter with a default",
+                        param.line_number, param.lexme
+                    ));
+                }
+                params.push((param, default));
 
                 if !self.match_token(&TokenType::Comma) {
                     break;
@@ -83,34 +179,104 @@ impl Parser {
             &format!("Expected '{{' {kind:?} body."),
         )?;
 
-        let body = match self.block_statement()? {
+        let body = match self.function_body()? {
             Stmt::Block { statements } => statements,
             _ => panic!("Block statement parsed something that was not a block"),
         };
 
-        Ok(Stmt::Function { name, params, body })
+        Ok(Stmt::Function {
+            name,
+            params: params.into(),
+            body: body.into(),
+        })
     }
 
     fn var_declaration(&mut self) -> Result<Stmt, String> {
-        let token = self.consume(TokenType::Identifier, "Expected variable name")?;
-        let initializer;
-        if self.match_token(&TokenType::Equal) {
-            initializer = self.expression()?;
-        } else {
-            initializer = Expr::Literal {
-                value: LiteralValue::Nil,
+        let mut declarations = vec![];
+
+        loop {
+            if self.match_token(&TokenType::LeftBracket) {
+                declarations.push(self.var_array()?);
+                if !self.match_token(&TokenType::Comma) {
+                    break;
+                }
+                continue;
+            }
+
+            let token = self.consume(TokenType::Identifier, "Expected variable name")?;
+
+            let initializer = if self.match_token(&TokenType::Equal) {
+                self.expression()?
+            } else {
+                Expr::Literal {
+                    value: LiteralValue::Nil,
+                    span: None,
+                }
             };
+
+            declarations.push(Stmt::Var {
+                name: token,
+                initializer,
+            });
+
+            if !self.match_token(&TokenType::Comma) {
+                break;
+            }
         }
 
         self.consume(TokenType::Semicolon, "Expected a ';' after variable name")?;
 
-        Ok(Stmt::Var {
-            name: token,
+        if declarations.len() == 1 {
+            Ok(declarations.remove(0))
+        } else {
+            Ok(Stmt::VarGroup {
+                declarations: declarations.into_iter().map(Box::new).collect(),
+            })
+        }
+    }
+
+    // The names of `var [a, b, ...rest] = value`, after the '['
+    fn var_array(&mut self) -> Result<Stmt, String> {
+        let bracket = self.previous();
+        let mut names = vec![];
+        let mut rest = None;
+
+        if !self.check(TokenType::RightBracket) {
+            loop {
+                if self.match_token(&TokenType::DotDotDot) {
+                    rest =
+                        Some(self.consume(TokenType::Identifier, "Expected a name after '...'")?);
+                    break;
+                }
+                names.push(self.consume(TokenType::Identifier, "Expected variable name")?);
+                if !self.match_token(&TokenType::Comma) {
+                    break;
+                }
+            }
+        }
+        self.consume(
+            TokenType::RightBracket,
+            "Expected ']' after the names, '...rest' can only come last",
+        )?;
+        self.consume(
+            TokenType::Equal,
+            "Expected '=' after a destructuring pattern",
+        )?;
+        let initializer = self.expression()?;
+
+        Ok(Stmt::VarArray {
+            bracket,
+            names,
+            rest,
             initializer,
         })
     }
 
     fn statement(&mut self) -> Result<Stmt, String> {
+        self.nested("Statement", Self::any_statement)
+    }
+
+    fn any_statement(&mut self) -> Result<Stmt, String> {
         if self.match_token(&TokenType::Print) {
             self.print_statement()
         } else if self.match_token(&TokenType::LeftBrace) {
@@ -118,11 +284,26 @@ impl Parser {
         } else if self.match_token(&TokenType::If) {
             self.if_statement()
         } else if self.match_token(&TokenType::While) {
-            self.while_statement()
+            self.while_statement(None)
         } else if self.match_token(&TokenType::For) {
-            self.for_statement()
+            self.for_statement(None)
+        } else if self.check(TokenType::Identifier) && self.check_next(TokenType::Colon) {
+            self.labeled_statement()
         } else if self.match_token(&TokenType::Return) {
             self.return_statement()
+        } else if self.match_token(&TokenType::Switch) {
+            self.switch_statement()
+        } else if self.match_token(&TokenType::Try) {
+            self.try_statement()
+        } else if self.match_token(&TokenType::Throw) {
+            self.throw_statement()
+        } else if self.match_tokens(&[TokenType::Break, TokenType::Continue]) {
+            self.loop_control_statement()
+        } else if self.check(TokenType::Import) {
+            Err(format!(
+                "Line {}: 'import' is only allowed at the top level",
+                self.peek().line_number
+            ))
         } else {
             self.expression_statement()
         }
@@ -130,76 +311,234 @@ impl Parser {
 
     fn return_statement(&mut self) -> Result<Stmt, String> {
         let keyword = self.previous();
-        let value;
 
-        if !self.check(TokenType::Semicolon) {
-            value = Some(self.expression()?);
+        let value = if !self.check(TokenType::Semicolon) {
+            Some(self.expression()?)
         } else {
-            value = None;
-        }
+            None
+        };
 
         self.consume(TokenType::Semicolon, "Expected ';' after return value")?;
 
         Ok(Stmt::ReturnStmt { keyword, value })
     }
 
-    fn for_statement(&mut self) -> Result<Stmt, String> {
+    fn switch_statement(&mut self) -> Result<Stmt, String> {
+        self.consume(TokenType::LeftParen, "Expected '(' after 'switch'")?;
+        let subject = self.expression()?;
+        self.consume(TokenType::RightParen, "Expected ')' after switch value")?;
+        self.consume(TokenType::LeftBrace, "Expected '{' before switch cases")?;
+
+        self.switches += 1;
+        let cases = self.switch_cases();
+        self.switches -= 1;
+        let cases = cases?;
+
+        self.consume(TokenType::RightBrace, "Expected '}' after switch cases")?;
+        Ok(Stmt::SwitchStmt { subject, cases })
+    }
+
+    fn switch_cases(&mut self) -> Result<Vec<SwitchCase>, String> {
+        let mut cases: Vec<SwitchCase> = vec![];
+        while !self.check(TokenType::RightBrace) && !self.is_end() {
+            let value = if self.match_token(&TokenType::Case) {
+                Some(self.expression()?)
+            } else if self.match_token(&TokenType::Default) {
+                let keyword = self.previous();
+                if cases.iter().any(|(value, _)| value.is_none()) {
+                    return Err(format!(
+                        "Line {}: a switch can only have one 'default'",
+                        keyword.line_number
+                    ));
+                }
+                None
+            } else {
+                return Err(format!(
+                    "Line {}: expected 'case' or 'default' in switch",
+                    self.peek().line_number
+                ));
+            };
+            self.consume(TokenType::Colon, "Expected ':' after case")?;
+
+            let mut statements = vec![];
+            while !self.check(TokenType::Case)
+                && !self.check(TokenType::Default)
+                && !self.check(TokenType::RightBrace)
+                && !self.is_end()
+            {
+                statements.push(Box::new(self.declaration()?));
+            }
+            cases.push((value, statements));
+        }
+
+        Ok(cases)
+    }
+
+    fn try_statement(&mut self) -> Result<Stmt, String> {
+        self.consume(TokenType::LeftBrace, "Expected '{' after 'try'")?;
+        let body = self.block_statement()?;
+
+        self.consume(TokenType::Catch, "Expected 'catch' after try block")?;
+        self.consume(TokenType::LeftParen, "Expected '(' after 'catch'")?;
+        let name = self.consume(TokenType::Identifier, "Expected error name in catch")?;
+        self.consume(TokenType::RightParen, "Expected ')' after error name")?;
+        self.consume(TokenType::LeftBrace, "Expected '{' after catch")?;
+        let catch = self.block_statement()?;
+
+        Ok(Stmt::TryStmt {
+            body: Box::new(body),
+            name,
+            catch: Box::new(catch),
+        })
+    }
+
+    fn throw_statement(&mut self) -> Result<Stmt, String> {
+        let keyword = self.previous();
+        let value = self.expression()?;
+        self.consume(TokenType::Semicolon, "Expected ';' after thrown value")?;
+
+        Ok(Stmt::ThrowStmt { keyword, value })
+    }
+
+    fn labeled_statement(&mut self) -> Result<Stmt, String> {
+        let label = self.advance();
+        self.advance();
+
+        if self.loops.contains(&Some(label.lexme.clone())) {
+            return Err(format!(
+                "Line {}: label '{}' is already used by an enclosing loop",
+                label.line_number, label.lexme
+            ));
+        }
+
+        if self.match_token(&TokenType::While) {
+            self.while_statement(Some(label))
+        } else if self.match_token(&TokenType::For) {
+            self.for_statement(Some(label))
+        } else {
+            Err(format!(
+                "Line {}: expected a loop after label '{}'",
+                label.line_number, label.lexme
+            ))
+        }
+    }
+
+    fn loop_control_statement(&mut self) -> Result<Stmt, String> {
+        let keyword = self.previous();
+        let is_break = keyword.token_t == TokenType::Break;
+        if is_break && self.loops.is_empty() && self.switches == 0 {
+            return Err(format!(
+                "Line {}: 'break' outside of a loop or switch",
+                keyword.line_number
+            ));
+        }
+        if !is_break && self.loops.is_empty() {
+            return Err(format!(
+                "Line {}: 'continue' outside of a loop",
+                keyword.line_number
+            ));
+        }
+
+        let label = if self.check(TokenType::Identifier) {
+            let label = self.advance();
+            if !self.loops.contains(&Some(label.lexme.clone())) {
+                return Err(format!(
+                    "Line {}: no enclosing loop labeled '{}'",
+                    label.line_number, label.lexme
+                ));
+            }
+            Some(label)
+        } else {
+            None
+        };
+
+        self.consume(
+            TokenType::Semicolon,
+            &format!("Expected ';' after '{}'", keyword.lexme),
+        )?;
+
+        if is_break {
+            Ok(Stmt::BreakStmt { keyword, label })
+        } else {
+            Ok(Stmt::ContinueStmt { keyword, label })
+        }
+    }
+
+    fn loop_body(&mut self, label: &Option<Token>) -> Result<Stmt, String> {
+        self.loops
+            .push(label.as_ref().map(|label| label.lexme.clone()));
+        let body = self.statement();
+        self.loops.pop();
+        body
+    }
+
+    fn loop_else(&mut self) -> Result<Option<Box<Stmt>>, String> {
+        if self.match_token(&TokenType::Else) {
+            Ok(Some(Box::new(self.statement()?)))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn function_body(&mut self) -> Result<Stmt, String> {
+        // A loop around a function definition doesn't make `break` valid inside it
+        let loops = std::mem::take(&mut self.loops);
+        let switches = std::mem::take(&mut self.switches);
+        let body = self.block_statement();
+        self.loops = loops;
+        self.switches = switches;
+        body
+    }
+
+    fn for_statement(&mut self, label: Option<Token>) -> Result<Stmt, String> {
         self.consume(TokenType::LeftParen, "Expected '(' after for")?;
 
-        let initializer: Option<Stmt>;
-        if self.match_token(&TokenType::Semicolon) {
-            initializer = None;
+        let initializer: Option<Stmt> = if self.match_token(&TokenType::Semicolon) {
+            None
         } else if self.match_token(&TokenType::Var) {
             let var_decl = self.var_declaration()?;
-            initializer = Some(var_decl);
+            Some(var_decl)
         } else {
             let expr = self.expression_statement()?;
-            initializer = Some(expr);
-        }
+            Some(expr)
+        };
 
-        let condition: Option<Expr>;
-        if !self.check(TokenType::Semicolon) {
+        let condition: Option<Expr> = if !self.check(TokenType::Semicolon) {
             let expr = self.expression()?;
-            condition = Some(expr);
+            self.warn_assignment_condition(&expr, "for");
+            Some(expr)
         } else {
-            condition = None;
-        }
+            None
+        };
 
         self.consume(TokenType::Semicolon, "Expected ';' after loop condition")?;
 
-        let increment: Option<Expr>;
-        if !self.check(TokenType::RightParen) {
+        let increment: Option<Expr> = if !self.check(TokenType::RightParen) {
             let expr = self.expression()?;
-            increment = Some(expr);
+            Some(expr)
         } else {
-            increment = None;
-        }
+            None
+        };
 
         self.consume(TokenType::RightParen, "Expected ')' after for clauses")?;
 
-        let mut body = self.statement()?;
-        if let Some(then) = increment {
-            body = Stmt::Block {
-                statements: vec![
-                    Box::new(body),
-                    Box::new(Stmt::Expression { expression: then }),
-                ],
-            }
-        }
+        let body = self.loop_body(&label)?;
+        let r#else = self.loop_else()?;
 
-        let cond;
-        match condition {
-            None => {
-                cond = Expr::Literal {
-                    value: LiteralValue::True,
-                }
-            }
-            Some(c) => cond = c,
-        }
+        let cond = match condition {
+            None => Expr::Literal {
+                value: LiteralValue::True,
+                span: None,
+            },
+            Some(c) => c,
+        };
 
-        body = Stmt::WhileStmt {
+        let mut body = Stmt::WhileStmt {
             condition: cond,
             body: Box::new(body),
+            increment: increment.map(Box::new),
+            r#else,
+            label,
         };
 
         if let Some(init) = initializer {
@@ -211,21 +550,27 @@ impl Parser {
         Ok(body)
     }
 
-    fn while_statement(&mut self) -> Result<Stmt, String> {
+    fn while_statement(&mut self, label: Option<Token>) -> Result<Stmt, String> {
         self.consume(TokenType::LeftParen, "Expected '(' after while")?;
         let condition = self.expression()?;
+        self.warn_assignment_condition(&condition, "while");
         self.consume(TokenType::RightParen, "Expected ')' after condition")?;
-        let body = self.statement()?;
+        let body = self.loop_body(&label)?;
+        let r#else = self.loop_else()?;
 
         Ok(Stmt::WhileStmt {
             condition,
             body: Box::new(body),
+            increment: None,
+            r#else,
+            label,
         })
     }
 
     fn if_statement(&mut self) -> Result<Stmt, String> {
         self.consume(TokenType::LeftParen, "Expected '(' after if-statement")?;
         let predicate = self.expression()?;
+        self.warn_assignment_condition(&predicate, "if");
         self.consume(TokenType::RightParen, "Expected ')' after if-predicate")?;
         let then = Box::new(self.statement()?);
         let r#else = if self.match_token(&TokenType::Else) {
@@ -263,10 +608,53 @@ impl Parser {
         self.peek().token_t == ty
     }
 
+    fn check_next(&mut self, ty: TokenType) -> bool {
+        self.tokens
+            .get(self.current + 1)
+            .is_some_and(|token| token.token_t == ty)
+    }
+
+    fn import_statement(&mut self) -> Result<Stmt, String> {
+        let keyword = self.previous();
+        let path = self.consume(TokenType::String, "Expected a file path after 'import'")?;
+        self.consume(TokenType::Semicolon, "Expected ';' after import path")?;
+        Ok(Stmt::Import {
+            keyword,
+            path: LiteralValue::from(path).to_string(),
+        })
+    }
+
     fn print_statement(&mut self) -> Result<Stmt, String> {
+        let keyword = self.previous();
         let value = self.expression()?;
-        self.consume(TokenType::Semicolon, "Expected ';' after value")?;
-        Ok(Stmt::Print { expression: value })
+        if !self.match_token(&TokenType::Comma) {
+            self.consume(TokenType::Semicolon, "Expected ';' after value")?;
+            return Ok(Stmt::Print { expression: value });
+        }
+
+        let format = match value {
+            Expr::Literal {
+                value: LiteralValue::StringValue(format),
+                ..
+            } => format,
+            _ => {
+                return Err(format!(
+                    "Line {}: print arguments need a format string first",
+                    keyword.line_number
+                ));
+            }
+        };
+        let mut arguments = vec![self.expression()?];
+        while self.match_token(&TokenType::Comma) {
+            arguments.push(self.expression()?);
+        }
+        self.consume(TokenType::Semicolon, "Expected ';' after print arguments")?;
+
+        Ok(Stmt::PrintFormat {
+            keyword,
+            format,
+            arguments,
+        })
     }
 
     fn expression_statement(&mut self) -> Result<Stmt, String> {
@@ -276,12 +664,7 @@ impl Parser {
     }
 
     fn expression(&mut self) -> Result<Expr, String> {
-        // if self.match_token(&TokenType::Fn) {
-        //     self.function_expression()
-        // } else {
-        //     self.assignment()
-        // }
-        self.assignment()
+        self.nested("Expression", Self::assignment)
     }
 
     fn function_expression(&mut self) -> Result<Expr, String> {
@@ -317,7 +700,7 @@ impl Parser {
             "Expected '{' after anonymous function decleration",
         )?;
 
-        let body = match self.block_statement()? {
+        let body = match self.function_body()? {
             Stmt::Block { statements } => statements,
             _ => panic!(
                 "Drink iced coffee panic attack (Block statement parsed something that was not a block)"
@@ -327,24 +710,62 @@ impl Parser {
         Ok(Expr::AnonFunction {
             paren,
             arguments: parameters,
-            body,
+            body: body.into(),
         })
     }
 
     fn assignment(&mut self) -> Result<Expr, String> {
         let expr = self.or()?;
 
-        if self.match_token(&TokenType::Equal) {
-            let value = self.assignment()?;
+        if self.match_tokens(&[
+            TokenType::Equal,
+            TokenType::PlusEqual,
+            TokenType::MinusEqual,
+            TokenType::StarEqual,
+            TokenType::SlashEqual,
+        ]) {
+            let equals = self.previous();
+            let value = self.nested("Expression", Self::assignment)?;
+
+            // `a += b` applies `+` to the old value of the target
+            let operator = match equals.token_t {
+                TokenType::PlusEqual => Some((TokenType::Plus, "+")),
+                TokenType::MinusEqual => Some((TokenType::Minus, "-")),
+                TokenType::StarEqual => Some((TokenType::Star, "*")),
+                TokenType::SlashEqual => Some((TokenType::Slash, "/")),
+                _ => None,
+            }
+            .map(|(token_t, lexme)| {
+                Token::new(token_t, lexme.into(), None, equals.line_number, equals.span)
+            });
 
             match expr {
                 Expr::Variable { name } => {
-                    return Ok(Expr::Assign {
+                    let value = match operator {
+                        Some(operator) => Expr::Binary {
+                            left: Box::new(Expr::Variable { name: name.clone() }),
+                            operator,
+                            right: Box::new(value),
+                        },
+                        None => value,
+                    };
+                    Ok(Expr::Assign {
                         name,
                         value: Box::new(value),
-                    });
+                    })
                 }
-                _ => return Err("Invalid assignment target.".into()),
+                Expr::Index {
+                    object,
+                    bracket,
+                    index,
+                } => Ok(Expr::SetIndex {
+                    object,
+                    bracket,
+                    index,
+                    operator,
+                    value: Box::new(value),
+                }),
+                _ => Err("Invalid assignment target.".into()),
             }
         } else {
             Ok(expr)
@@ -459,24 +880,58 @@ impl Parser {
     }
 
     fn unary(&mut self) -> Result<Expr, String> {
-        if self.match_tokens(&[TokenType::Bang, TokenType::Minus]) {
+        if self.match_tokens(&[TokenType::Bang, TokenType::Minus, TokenType::Typeof]) {
             let op = self.previous();
-            let rhs = self.unary()?;
+            let rhs = self.nested("Expression", Self::unary)?;
             Ok(Expr::Unary {
                 operator: op,
                 right: Box::from(rhs),
             })
         } else {
-            self.call()
+            self.power()
         }
     }
 
+    // `**` binds tighter than unary minus, so `-2 ** 2` is `-(2 ** 2)`, and
+    // groups to the right, so `2 ** 3 ** 2` is `2 ** (3 ** 2)`. The exponent
+    // is parsed as a unary so `2 ** -1` works.
+    fn power(&mut self) -> Result<Expr, String> {
+        let base = self.call()?;
+        if self.match_token(&TokenType::StarStar) {
+            let op = self.previous();
+            let exponent = self.nested("Expression", Self::unary)?;
+            return Ok(Expr::Binary {
+                left: Box::from(base),
+                operator: op,
+                right: Box::from(exponent),
+            });
+        }
+
+        Ok(base)
+    }
+
     fn call(&mut self) -> Result<Expr, String> {
         let mut expr = self.primary()?;
 
         loop {
             if self.match_token(&TokenType::LeftParen) {
                 expr = self.finish_call(expr)?;
+            } else if self.match_token(&TokenType::LeftBracket) {
+                let bracket = self.previous();
+                let index = self.expression()?;
+                self.consume(TokenType::RightBracket, "Expected ']' after index")?;
+                expr = Expr::Index {
+                    object: Box::new(expr),
+                    bracket,
+                    index: Box::new(index),
+                };
+            } else if self.match_token(&TokenType::Dot) {
+                let name =
+                    self.consume(TokenType::Identifier, "Expected property name after '.'")?;
+                expr = Expr::Get {
+                    object: Box::new(expr),
+                    name,
+                };
             } else {
                 break;
             }
@@ -513,77 +968,173 @@ impl Parser {
         })
     }
 
+    fn array_literal(&mut self) -> Result<Expr, String> {
+        let mut elements = vec![];
+
+        if !self.check(TokenType::RightBracket) {
+            loop {
+                elements.push(self.expression()?);
+
+                if !self.match_token(&TokenType::Comma) {
+                    break;
+                }
+            }
+        }
+
+        self.consume(TokenType::RightBracket, "Expected ']' after array elements")?;
+        Ok(Expr::Array { elements })
+    }
+
+    fn map_literal(&mut self) -> Result<Expr, String> {
+        let mut entries = vec![];
+
+        if !self.check(TokenType::RightBrace) {
+            loop {
+                let key = self.expression()?;
+                self.consume(TokenType::Colon, "Expected ':' after map key")?;
+                let value = self.expression()?;
+                entries.push((key, value));
+
+                if !self.match_token(&TokenType::Comma) {
+                    break;
+                }
+            }
+        }
+
+        self.consume(TokenType::RightBrace, "Expected '}' after map entries")?;
+        Ok(Expr::Map { entries })
+    }
+
     fn consume(&mut self, token_t: TokenType, msg: &str) -> Result<Token, String> {
         let token = self.peek();
         if token.token_t == token_t {
             self.advance();
             let token = self.previous();
             Ok(token)
+        } else if let (TokenType::Eof, Some(closing)) = (token.token_t, self.unclosed_delimiter()) {
+            Err(format!("Unexpected end of file, expected '{closing}'"))
         } else {
             Err(msg.into())
         }
     }
 
+    // The closing token of the innermost bracket still open before the current token
+    fn unclosed_delimiter(&self) -> Option<&'static str> {
+        let mut open = vec![];
+        for token in &self.tokens[..self.current] {
+            match token.token_t {
+                TokenType::LeftParen => open.push(")"),
+                TokenType::LeftBracket => open.push("]"),
+                TokenType::LeftBrace => open.push("}"),
+                TokenType::RightParen | TokenType::RightBracket | TokenType::RightBrace => {
+                    open.pop();
+                }
+                _ => (),
+            }
+        }
+        open.pop()
+    }
+
     fn primary(&mut self) -> Result<Expr, String> {
         let token = self.peek();
-        let result: Expr;
-        match token.token_t {
+
+        let result: Expr = match token.token_t {
             TokenType::LeftParen => {
                 self.advance();
                 let expr = self.expression()?;
                 self.consume(TokenType::RightParen, "Expected ')'")?;
-                result = Expr::Grouping {
+                Expr::Grouping {
                     expression: Box::from(expr),
                 }
             }
-            TokenType::False
-            | TokenType::True
-            | TokenType::Nil
-            | TokenType::Number
-            | TokenType::String => {
+            ty if ty.is_literal() => {
                 self.advance();
-                result = Expr::Literal {
+                Expr::Literal {
+                    span: Some(token.span),
                     value: LiteralValue::from(token),
                 }
             }
+            TokenType::LeftBracket => {
+                self.advance();
+                self.array_literal()?
+            }
+            TokenType::LeftBrace => {
+                self.advance();
+                self.map_literal()?
+            }
             TokenType::Fn => {
                 self.advance();
-                result = self.function_expression()?;
+                self.function_expression()?
             }
             TokenType::Identifier => {
                 self.advance();
-                result = Expr::Variable {
+                Expr::Variable {
                     name: self.previous(),
-                };
+                }
             }
-            _ => return Err("Expected expression".into()),
-        }
+            TokenType::Print | TokenType::Return | TokenType::Throw => {
+                return Err(format!(
+                    "'{}' is a statement and cannot be used in an expression",
+                    token.lexme
+                ));
+            }
+            TokenType::Eof => {
+                return Err(match self.unclosed_delimiter() {
+                    Some(closing) => format!("Unexpected end of file, expected '{closing}'"),
+                    None => "Unexpected end of file, expected expression".into(),
+                });
+            }
+            _ => {
+                return Err(format!(
+                    "Line {}: Expected expression, got {} '{}'",
+                    token.line_number, token.token_t, token.lexme
+                ));
+            }
+        };
 
         Ok(result)
-        // if self.match_token(&TokenType::LeftParen) {
-        // } else if self.match_token(&TokenType::False) {
-        //     let token = self.peek();
-        //     self.advance();
-        //     Ok(Expr::Literal {
-        //         value: LiteralValue::from(token),
-        //     })
-        // }
+    }
+
+    // Recursive descent uses a stack frame per level, so input nested past
+    // `max_depth` is an error rather than a stack overflow
+    fn nested<T>(
+        &mut self,
+        kind: &str,
+        parse: impl FnOnce(&mut Self) -> Result<T, String>,
+    ) -> Result<T, String> {
+        if self.depth >= self.max_depth {
+            return Err(format!(
+                "Line {}: {kind} nesting too deep",
+                self.peek().line_number
+            ));
+        }
+        self.depth += 1;
+        let result = parse(self);
+        self.depth -= 1;
+        result
     }
 
     fn peek(&mut self) -> Token {
         self.tokens[self.current].clone()
     }
 
+    // The first token before anything has been consumed, so error paths that
+    // look back can't underflow
     fn previous(&mut self) -> Token {
-        self.tokens[self.current - 1].clone()
+        self.tokens[self.current.saturating_sub(1)].clone()
     }
 
     fn is_end(&mut self) -> bool {
         self.peek().token_t == TokenType::Eof
     }
 
-    fn synchronize(&mut self) {
-        self.advance();
+    // Skips to the start of the next statement after an error in the one that
+    // started at `start`. That statement's first token is always skipped, so
+    // one that fails right away can't be retried forever.
+    fn synchronize(&mut self, start: usize) {
+        if self.current == start {
+            self.advance();
+        }
         while !self.is_end() {
             if self.previous().token_t == TokenType::Semicolon {
                 return;
@@ -597,7 +1148,12 @@ impl Parser {
                 | TokenType::If
                 | TokenType::While
                 | TokenType::Print
-                | TokenType::Return => return,
+                | TokenType::Return
+                | TokenType::Switch
+                | TokenType::Try
+                | TokenType::Throw
+                | TokenType::Break
+                | TokenType::Continue => return,
                 _ => (),
             }
 
@@ -631,7 +1187,7 @@ impl Parser {
 
 #[cfg(test)]
 mod tests {
-    use crate::lexer::{self, Lexer};
+    use crate::lexer::{self, Lexer, Span};
 
     use super::*;
 
@@ -642,30 +1198,35 @@ mod tests {
             lexme: "1".to_string(),
             literal: Some(lexer::LiteralValue::FloatValue(1.0)),
             line_number: 0,
+            span: Span::default(),
         };
         let plus = Token {
             token_t: TokenType::Plus,
             lexme: "+".to_string(),
             literal: None,
             line_number: 0,
+            span: Span::default(),
         };
         let two = Token {
             token_t: TokenType::Number,
             lexme: "2".to_string(),
             literal: Some(lexer::LiteralValue::FloatValue(2.0)),
             line_number: 0,
+            span: Span::default(),
         };
         let semi = Token {
             token_t: TokenType::Semicolon,
             lexme: ";".to_string(),
             literal: None,
             line_number: 0,
+            span: Span::default(),
         };
         let eof = Token {
             token_t: TokenType::Eof,
             lexme: "".to_string(),
             literal: None,
             line_number: 0,
+            span: Span::default(),
         };
 
         let tokens = vec![one, plus, two, semi, eof];
@@ -679,7 +1240,7 @@ mod tests {
     #[test]
     fn test_comparison() {
         let source = "1 + 2 == 5 + 7;";
-        let mut lexer = Lexer::new(&source);
+        let mut lexer = Lexer::new(source);
         let tokens = lexer.scan_tokens().unwrap();
         let mut parser = Parser::new(tokens.to_vec());
         let parsed_expr = parser.parse().unwrap();
@@ -690,11 +1251,266 @@ mod tests {
     #[test]
     fn test_eq_with_paren() {
         let source = "1 == (2 + 2);";
-        let mut lexer = Lexer::new(&source);
+        let mut lexer = Lexer::new(source);
         let tokens = lexer.scan_tokens().unwrap();
         let mut parser = Parser::new(tokens.to_vec());
         let parsed_expr = parser.parse().unwrap();
         let str_expr = parsed_expr[0].to_string();
         assert_eq!(str_expr, "(== 1 (group (+ 2 2)))");
     }
+
+    #[test]
+    fn test_power() {
+        let cases = [
+            ("2 ** 3 ** 2;", "(** 2 (** 3 2))"),
+            ("-2 ** 2;", "(- (** 2 2))"),
+            ("2 ** -1;", "(** 2 (- 1))"),
+            ("2 * 3 ** 2;", "(* 2 (** 3 2))"),
+        ];
+        for (source, expected) in cases {
+            let mut lexer = Lexer::new(source);
+            let tokens = lexer.scan_tokens().unwrap();
+            let mut parser = Parser::new(tokens.to_vec());
+            let parsed_expr = parser.parse().unwrap();
+            assert_eq!(parsed_expr[0].to_string(), expected, "Source: {source}");
+        }
+    }
+
+    #[test]
+    fn test_parse_expression() {
+        let tokens = Lexer::new("1 + 2 * 3").scan_tokens().unwrap();
+        let expr = Parser::new(tokens.to_vec()).parse_expression().unwrap();
+        assert_eq!(expr.to_string(), "(+ 1 (* 2 3))");
+
+        let tokens = Lexer::new("1 4").scan_tokens().unwrap();
+        let err = Parser::new(tokens.to_vec()).parse_expression().unwrap_err();
+        assert_eq!(err, "Line 1: unexpected '4' after expression");
+
+        let tokens = Lexer::new("1;").scan_tokens().unwrap();
+        assert!(Parser::new(tokens.to_vec()).parse_expression().is_err());
+    }
+
+    #[test]
+    fn test_postfix_chain() {
+        let tokens = Lexer::new("a.b()[0].c").scan_tokens().unwrap();
+        let expr = Parser::new(tokens.to_vec()).parse_expression().unwrap();
+        assert_eq!(
+            expr.to_string(),
+            "(get (index ((get (var Identifier a None) b) []) 0) c)"
+        );
+
+        let tokens = Lexer::new("a.1").scan_tokens().unwrap();
+        let err = Parser::new(tokens.to_vec()).parse_expression().unwrap_err();
+        assert!(err.contains("Expected property name after '.'"), "{err}");
+    }
+
+    #[test]
+    fn test_error_names_token() {
+        let tokens = Lexer::new("var x = ;").scan_tokens().unwrap();
+        let err = Parser::new(tokens.to_vec()).parse().unwrap_err();
+        assert_eq!(err, "Line 1: Expected expression, got Semicolon ';'");
+
+        let tokens = Lexer::new("print 1 + ,;").scan_tokens().unwrap();
+        let err = Parser::new(tokens.to_vec()).parse().unwrap_err();
+        assert!(err.contains("Comma ','"), "{err}");
+    }
+
+    #[test]
+    fn test_synchronize_keeps_next_statement() {
+        // The missing ';' is noticed at `print`, which must still be parsed
+        let source = "var x = 1\nprint );\nvar y = ;\nclass";
+        let tokens = Lexer::new(source).scan_tokens().unwrap();
+        let err = Parser::new(tokens.to_vec()).parse().unwrap_err();
+        let errors: Vec<&str> = err.lines().collect();
+        assert_eq!(errors.len(), 4, "{err}");
+        assert!(errors[1].contains("Line 2: Expected expression, got RightParen ')'"));
+        assert!(errors[2].contains("Line 3: Expected expression, got Semicolon ';'"));
+        assert!(errors[3].contains("Class 'class'"));
+    }
+
+    #[test]
+    fn test_structural_equality() {
+        let parse = |source: &str| {
+            let tokens = Lexer::new(source).scan_tokens().unwrap();
+            Parser::new(tokens.to_vec()).parse().unwrap()
+        };
+
+        let program = "var x = 1 + 2 * y;\nfn f(a) { return [a, x]; }\nprint f(x);";
+        assert_eq!(parse(program), parse(program));
+        // Trailing comments don't move any token
+        assert_eq!(
+            parse(&format!("{program} // one")),
+            parse(&format!("{program} // two"))
+        );
+        assert_ne!(parse("print 1 + x;"), parse("print 1 - x;"));
+        assert_ne!(parse("print (1 + x);"), parse("print 1 + x;"));
+
+        // Copies compare equal, so Expr equality is no longer identity
+        let stmts = parse(program);
+        assert_eq!(stmts.clone(), stmts);
+    }
+
+    #[test]
+    fn test_nesting_depth_limit() {
+        // Test threads get less stack than the main thread the limit is sized for
+        std::thread::Builder::new()
+            .stack_size(8 << 20)
+            .spawn(check_nesting_depth_limit)
+            .unwrap()
+            .join()
+            .unwrap();
+    }
+
+    fn check_nesting_depth_limit() {
+        let parse = |source: &str| {
+            let tokens = Lexer::new(source).scan_tokens().unwrap();
+            Parser::new(tokens.to_vec()).parse()
+        };
+        let nest = |open: &str, inner: &str, close: &str, n: usize| {
+            format!("{}{inner}{}", open.repeat(n), close.repeat(n))
+        };
+
+        for source in [
+            format!("print {};", nest("(", "1", ")", 10_000)),
+            format!("print {};", nest("[", "1", "]", 10_000)),
+            format!("print {}1;", "-".repeat(10_000)),
+            format!("var a = {}1;", "a = ".repeat(10_000)),
+            format!("print 2{};", " ** 2".repeat(10_000)),
+            nest("{", "", "}", 10_000),
+            format!("{}print 1;", "if (true) ".repeat(10_000)),
+            nest("fn f() {", "", "}", 10_000),
+        ] {
+            let err = parse(&source).unwrap_err();
+            assert!(err.contains("nesting too deep"), "Error: '{}'", err);
+        }
+        let err = parse(&format!("print {};", nest("(", "1", ")", 200))).unwrap_err();
+        assert!(err.contains("Line 1: Expression nesting too deep"), "{err}");
+
+        // Reasonable nesting still parses, and the limit can be changed
+        parse(&format!("print {};", nest("(", "1", ")", 60))).unwrap();
+        parse(&nest("{", "", "}", 120)).unwrap();
+        let tokens = Lexer::new(&nest("{", "", "}", 20)).scan_tokens().unwrap();
+        let mut parser = Parser::new(tokens.to_vec());
+        parser.set_max_depth(10);
+        assert!(parser.parse().is_err());
+    }
+
+    #[test]
+    fn test_malformed_leading_tokens() {
+        for source in [
+            ")", "}", "]", ";", ",", "= 1;", "else {}", ". x", "... x", "==",
+        ] {
+            let tokens = Lexer::new(source).scan_tokens().unwrap();
+            assert!(
+                Parser::new(tokens.to_vec()).parse().is_err(),
+                "Source: {:?}",
+                source
+            );
+            let tokens = Lexer::new(source).scan_tokens().unwrap();
+            assert!(Parser::new(tokens.to_vec()).parse_expression().is_err());
+        }
+    }
+
+    #[test]
+    fn test_print_in_expression() {
+        let source = "var x = print 1;";
+        let mut lexer = Lexer::new(source);
+        let tokens = lexer.scan_tokens().unwrap();
+        let mut parser = Parser::new(tokens.to_vec());
+        let err = parser.parse().unwrap_err();
+        assert_eq!(
+            err,
+            "'print' is a statement and cannot be used in an expression"
+        );
+    }
+
+    #[test]
+    fn test_return_in_expression() {
+        let source = "var x = 1 + return 2;";
+        let mut lexer = Lexer::new(source);
+        let tokens = lexer.scan_tokens().unwrap();
+        let mut parser = Parser::new(tokens.to_vec());
+        let err = parser.parse().unwrap_err();
+        assert_eq!(
+            err,
+            "'return' is a statement and cannot be used in an expression"
+        );
+    }
+
+    #[test]
+    fn test_var_group() {
+        let source = "var a = 1, b = 2, c;";
+        let mut lexer = Lexer::new(source);
+        let tokens = lexer.scan_tokens().unwrap();
+        let mut parser = Parser::new(tokens.to_vec());
+        let parsed = parser.parse().unwrap();
+        assert_eq!(parsed.len(), 1);
+        assert_eq!(parsed[0].to_string(), "(var a) (var b) (var c)");
+    }
+
+    #[test]
+    fn test_typeof() {
+        let source = "typeof -5 == \"Number\";";
+        let mut lexer = Lexer::new(source);
+        let tokens = lexer.scan_tokens().unwrap();
+        let mut parser = Parser::new(tokens.to_vec());
+        let parsed = parser.parse().unwrap();
+        assert_eq!(parsed[0].to_string(), "(== (typeof (- 5)) Number)");
+    }
+
+    #[test]
+    fn test_compound_index_assignment() {
+        let source = "a[i] += 1;";
+        let mut lexer = Lexer::new(source);
+        let tokens = lexer.scan_tokens().unwrap();
+        let mut parser = Parser::new(tokens.to_vec());
+        let parsed = parser.parse().unwrap();
+        assert_eq!(
+            parsed[0].to_string(),
+            "((index (var Identifier a None) (var Identifier i None)) += 1)"
+        );
+    }
+
+    #[test]
+    fn test_assignment_condition_warning() {
+        let source = "var x; if (x = 5) print x; while ((x = 0)) {}";
+        let mut lexer = Lexer::new(source);
+        let tokens = lexer.scan_tokens().unwrap();
+        let mut parser = Parser::new(tokens.to_vec());
+        parser.parse().unwrap();
+
+        assert_eq!(parser.warnings().len(), 1);
+        assert!(parser.warnings()[0].contains("did you mean '=='?"));
+
+        let source = "for (var i = 0; i = 3; ) {} for (var i = 0; (i = 3); ) {}";
+        let tokens = Lexer::new(source).scan_tokens().unwrap();
+        let mut parser = Parser::new(tokens.to_vec());
+        parser.parse().unwrap();
+
+        assert_eq!(parser.warnings().len(), 1);
+        assert!(parser.warnings()[0].contains("assignment to 'i' used as for condition"));
+    }
+
+    #[test]
+    fn test_truncated_input() {
+        let cases = [
+            ("{ print 1;", "expected '}'"),
+            ("fn f() { if (true) { print 1; }", "expected '}'"),
+            ("print (1 + 2", "expected ')'"),
+            ("f(1,", "expected ')'"),
+            ("var a = [1, 2", "expected ']'"),
+            ("print 1 +", "expected expression"),
+        ];
+        for (source, expected) in cases {
+            let mut lexer = Lexer::new(source);
+            let tokens = lexer.scan_tokens().unwrap();
+            let mut parser = Parser::new(tokens.to_vec());
+            let err = parser.parse().unwrap_err();
+            assert_eq!(
+                err,
+                format!("Unexpected end of file, {expected}"),
+                "Source: {source}"
+            );
+        }
+    }
 }
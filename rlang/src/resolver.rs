@@ -1,236 +1,477 @@
-use crate::{expr::Expr, interpreter::Interpreter, lexer::Token, stmt::Stmt};
-use std::collections::HashMap;
+use crate::{
+    environment::RunConfig,
+    expr::{Expr, ExprVisitor, LiteralValue},
+    interpreter::Interpreter,
+    lexer::Token,
+    stmt::{Stmt, StmtVisitor, SwitchCase},
+};
+use std::{
+    collections::{HashMap, HashSet},
+    rc::Rc,
+};
 
-#[allow(dead_code)]
+#[derive(Copy, Clone, PartialEq)]
+enum FunctionType {
+    None,
+    Function,
+}
 
 pub struct Resolver {
-    interpreter: Interpreter,
+    // Where the variables known to be globals are recorded
+    config: Rc<RunConfig>,
     scopes: Vec<HashMap<String, bool>>,
+    // Every name each scope declares, including the declarations further
+    // down it, or None when an `import` in it can declare anything
+    declared: Vec<Option<HashSet<String>>>,
+    current_function: FunctionType,
 }
 
 impl Resolver {
     pub fn new() -> Self {
+        Self::with_config(Rc::default())
+    }
+
+    // Resolves code for `interpreter`, so its lookups of globals can skip the
+    // scopes in between
+    pub fn for_interpreter(interpreter: &Interpreter) -> Self {
+        Self::with_config(interpreter.config())
+    }
+
+    fn with_config(config: Rc<RunConfig>) -> Self {
         Self {
-            interpreter: Interpreter::new(),
+            config,
             scopes: vec![],
+            declared: vec![],
+            current_function: FunctionType::None,
         }
     }
 
     pub fn resolve(&mut self, stmt: &Stmt) -> Result<(), String> {
-        match stmt {
-            Stmt::Block { statements: _ } => self.resolve_block(stmt),
-            Stmt::Var {
-                name: _,
-                initializer: _,
-            } => self.resolve_var(stmt),
-            Stmt::Function {
-                name: _,
-                params: _,
-                body: _,
-            } => self.resolve_function(stmt),
-            Stmt::Expression { expression } => self.resolve_expr(expression),
-            Stmt::IfStmt {
-                predicate: _,
-                then: _,
-                r#else: _,
-            } => Ok(self.resolve_if_stmt(stmt)?),
-            Stmt::Print { expression } => self.resolve_expr(expression),
-            Stmt::ReturnStmt {
-                keyword: _,
-                value: None,
-            } => Ok(()),
-            Stmt::ReturnStmt {
-                keyword: _,
-                value: Some(value),
-            } => self.resolve_expr(value),
-            Stmt::WhileStmt { condition, body } => {
-                self.resolve_expr(condition)?;
-                self.resolve(body)
-            }
-        }
+        stmt.accept(self)
     }
 
     fn resolve_expr(&mut self, expr: &Expr) -> Result<(), String> {
-        match expr {
-            Expr::Variable { name: _ } => self.resolve_expr_var(expr),
-            Expr::Assign { name: _, value: _ } => self.resolve_expr_assign(expr),
-            Expr::Binary {
-                left,
-                operator: _,
-                right,
-            } => {
-                self.resolve_expr(left)?;
-                self.resolve_expr(right)
-            }
-            Expr::Call {
-                callee,
-                paren: _,
-                arguments,
-            } => {
-                self.resolve_expr(callee)?;
-                for arg in arguments {
-                    self.resolve_expr(arg)?;
-                }
-
-                Ok(())
-            }
-            Expr::Grouping { expression } => self.resolve_expr(&expression),
-            Expr::Literal { value: _ } => Ok(()),
-            Expr::Logical {
-                left,
-                operator: _,
-                right,
-            } => {
-                self.resolve_expr(left)?;
-                self.resolve_expr(right)
-            }
-            Expr::Unary { operator: _, right } => self.resolve_expr(right),
-            Expr::AnonFunction {
-                paren: _,
-                arguments,
-                body,
-            } => self.resolve_function_helper(arguments, body),
+        expr.accept(self)
+    }
+
+    // Records `expr` as a global when no scope around it declares `name`,
+    // even further down, where a function could see it once it runs
+    fn resolve_local(&mut self, expr: &Expr, name: &Token) -> Result<(), String> {
+        if self.scopes.is_empty()
+            || self
+                .scopes
+                .iter()
+                .any(|scope| scope.contains_key(&name.lexme))
+        {
+            return Ok(());
+        }
+
+        let global = self.declared.iter().all(|declared| {
+            declared
+                .as_ref()
+                .is_some_and(|declared| !declared.contains(&name.lexme))
+        });
+        if global {
+            self.config.record_global(expr, self.scopes.len());
         }
+
+        Ok(())
     }
 
-    fn resolve_expr_var(&mut self, expr: &Expr) -> Result<(), String> {
-        if let Expr::Variable { name } = expr {
-            if !self.scopes.is_empty()
-                && *self.scopes[self.scopes.len() - 1].get(&name.lexme).unwrap() == false
-            {
-                return Err("Can't read local variable in its own initializer".into());
-            }
+    // Defaults are evaluated in the function's scope once the parameters are
+    // defined, so they resolve there too
+    fn resolve_function_helper(
+        &mut self,
+        params: &[Token],
+        defaults: &[&Expr],
+        body: &[Box<Stmt>],
+    ) -> Result<(), String> {
+        let enclosing_function = self.current_function;
+        self.current_function = FunctionType::Function;
 
-            self.resolve_local(expr, name)?;
-            Ok(())
-        } else {
-            panic!("Wrong type in resolve_expr_var");
+        let declared = declared_in(body.iter().map(|stmt| stmt.as_ref())).map(|mut declared| {
+            declared.extend(params.iter().map(|param| param.lexme.clone()));
+            declared
+        });
+        self.begin_scope(declared);
+        for param in params {
+            self.declare(param);
+            self.define(param);
         }
+        let result = defaults
+            .iter()
+            .try_for_each(|default| self.resolve_expr(default))
+            .and_then(|_| self.resolve_many(body));
+        self.end_scope();
+
+        self.current_function = enclosing_function;
+        result
+    }
+
+    fn declare(&mut self, name: &Token) {
+        if self.scopes.is_empty() {
+            return;
+        }
+
+        let scope_len = self.scopes.len();
+        self.scopes[scope_len - 1].insert(name.lexme.clone(), false);
     }
 
-    fn resolve_expr_assign(&mut self, expr: &Expr) -> Result<(), String> {
-        if let Expr::Assign { name, value } = expr {
-            self.resolve_expr(value.as_ref())?;
-            self.resolve_local(expr, name)?;
-        } else {
-            panic!("Wrong type in resolve assign");
+    fn define(&mut self, name: &Token) {
+        if self.scopes.is_empty() {
+            return;
         }
 
+        let scope_len = self.scopes.len();
+        self.scopes[scope_len - 1].insert(name.lexme.clone(), true);
+    }
+
+    fn resolve_many(&mut self, stmts: &[Box<Stmt>]) -> Result<(), String> {
+        for stmt in stmts {
+            self.resolve(stmt)?;
+        }
         Ok(())
     }
 
-    fn resolve_local(&mut self, expr: &Expr, name: &Token) -> Result<(), String> {
-        let size = self.scopes.len();
-        for i in (0..=(size - 1)).rev() {
-            let scope = &self.scopes[i];
-            if scope.contains_key(&name.lexme) {
-                self.interpreter.resolve(expr, size - 1 - i)?;
-                return Ok(());
+    fn begin_scope(&mut self, declared: Option<HashSet<String>>) {
+        self.scopes.push(HashMap::new());
+        self.declared.push(declared);
+    }
+
+    fn end_scope(&mut self) {
+        self.scopes.pop().expect("Stack underflow");
+        self.declared.pop();
+    }
+}
+
+// The names `stmts` declare in the scope they run in, None if one of them
+// imports a file
+fn declared_in<'a>(stmts: impl IntoIterator<Item = &'a Stmt>) -> Option<HashSet<String>> {
+    let mut declared = HashSet::new();
+    for stmt in stmts {
+        collect_declared(stmt, &mut declared)?;
+    }
+    Some(declared)
+}
+
+// Blocks, switches and functions run in scopes of their own, but the bodies
+// of `if`, `while` and `try` that aren't blocks declare into this one
+fn collect_declared(stmt: &Stmt, declared: &mut HashSet<String>) -> Option<()> {
+    match stmt {
+        Stmt::Var { name, .. } | Stmt::Function { name, .. } => {
+            declared.insert(name.lexme.clone());
+        }
+        Stmt::VarArray { names, rest, .. } => {
+            declared.extend(names.iter().chain(rest).map(|name| name.lexme.clone()));
+        }
+        Stmt::VarGroup { declarations } => {
+            for declaration in declarations {
+                collect_declared(declaration, declared)?;
+            }
+        }
+        Stmt::IfStmt { then, r#else, .. } => {
+            collect_declared(then, declared)?;
+            if let Some(r#else) = r#else {
+                collect_declared(r#else, declared)?;
             }
         }
+        Stmt::WhileStmt { body, r#else, .. } => {
+            collect_declared(body, declared)?;
+            if let Some(r#else) = r#else {
+                collect_declared(r#else, declared)?;
+            }
+        }
+        Stmt::TryStmt { body, .. } => collect_declared(body, declared)?,
+        Stmt::Import { .. } => return None,
+        _ => (),
+    }
+    Some(())
+}
+
+impl StmtVisitor<Result<(), String>> for Resolver {
+    fn visit_expression(&mut self, expression: &Expr) -> Result<(), String> {
+        self.resolve_expr(expression)
+    }
+
+    fn visit_print(&mut self, expression: &Expr) -> Result<(), String> {
+        self.resolve_expr(expression)
+    }
+
+    fn visit_print_format(
+        &mut self,
+        _keyword: &Token,
+        _format: &str,
+        arguments: &[Expr],
+    ) -> Result<(), String> {
+        for arg in arguments {
+            self.resolve_expr(arg)?;
+        }
+
+        Ok(())
+    }
 
+    fn visit_var(&mut self, name: &Token, initializer: &Expr) -> Result<(), String> {
+        self.declare(name);
+        self.resolve_expr(initializer)?;
+        self.define(name);
         Ok(())
     }
 
-    fn resolve_function(&mut self, stmt: &Stmt) -> Result<(), String> {
-        if let Stmt::Function { name, params, body } = stmt {
+    fn visit_var_array(
+        &mut self,
+        _bracket: &Token,
+        names: &[Token],
+        rest: Option<&Token>,
+        initializer: &Expr,
+    ) -> Result<(), String> {
+        for name in names.iter().chain(rest) {
             self.declare(name);
+        }
+        self.resolve_expr(initializer)?;
+        for name in names.iter().chain(rest) {
             self.define(name);
-
-            self.resolve_function_helper(params, body)
-        } else {
-            panic!("Wrong type in resolve function");
         }
+        Ok(())
     }
 
-    fn resolve_if_stmt(&mut self, stmt: &Stmt) -> Result<(), String> {
-        if let Stmt::IfStmt {
-            predicate,
-            then,
-            r#else,
-        } = stmt
-        {
-            self.resolve_expr(predicate)?;
-            self.resolve(&then)?;
-            if let Some(r#else) = r#else {
-                self.resolve(r#else.as_ref())?;
-            }
+    fn visit_var_group(&mut self, declarations: &[Box<Stmt>]) -> Result<(), String> {
+        self.resolve_many(declarations)
+    }
+
+    fn visit_block(&mut self, statements: &[Box<Stmt>]) -> Result<(), String> {
+        self.begin_scope(declared_in(statements.iter().map(|stmt| stmt.as_ref())));
+        let result = self.resolve_many(statements);
+        self.end_scope();
+        result
+    }
 
-            Ok(())
-        } else {
-            panic!("Wrong type in resolve if stmt");
+    fn visit_if(
+        &mut self,
+        predicate: &Expr,
+        then: &Stmt,
+        r#else: Option<&Stmt>,
+    ) -> Result<(), String> {
+        self.resolve_expr(predicate)?;
+        self.resolve(then)?;
+        if let Some(r#else) = r#else {
+            self.resolve(r#else)?;
         }
+
+        Ok(())
     }
 
-    fn resolve_function_helper(
+    fn visit_while(
         &mut self,
-        params: &Vec<Token>,
-        body: &Vec<Box<Stmt>>,
+        condition: &Expr,
+        body: &Stmt,
+        increment: Option<&Expr>,
+        r#else: Option<&Stmt>,
+        _label: Option<&Token>,
     ) -> Result<(), String> {
-        self.begin_scope();
-        for param in params {
-            self.declare(param);
-            self.define(param);
+        self.resolve_expr(condition)?;
+        self.resolve(body)?;
+        if let Some(increment) = increment {
+            self.resolve_expr(increment)?;
+        }
+        if let Some(r#else) = r#else {
+            self.resolve(r#else)?;
         }
-        self.resolve_many(body)?;
-        self.end_scope();
 
         Ok(())
     }
 
-    fn declare(&mut self, name: &Token) {
-        if self.scopes.is_empty() {
-            return;
+    fn visit_break(&mut self, _keyword: &Token, _label: Option<&Token>) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn visit_continue(&mut self, _keyword: &Token, _label: Option<&Token>) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn visit_function(
+        &mut self,
+        name: &Token,
+        params: &[(Token, Option<Expr>)],
+        body: &[Box<Stmt>],
+    ) -> Result<(), String> {
+        self.declare(name);
+        self.define(name);
+
+        let defaults: Vec<&Expr> = params
+            .iter()
+            .filter_map(|(_, default)| default.as_ref())
+            .collect();
+        let params: Vec<Token> = params.iter().map(|(param, _)| param.clone()).collect();
+        self.resolve_function_helper(&params, &defaults, body)
+    }
+
+    fn visit_return(&mut self, keyword: &Token, value: Option<&Expr>) -> Result<(), String> {
+        if self.current_function == FunctionType::None {
+            return Err(format!(
+                "Can't return from top-level code at line {}",
+                keyword.line_number
+            ));
         }
 
-        let scope_len = self.scopes.len();
-        self.scopes[scope_len - 1].insert(name.lexme.clone(), false);
+        match value {
+            Some(value) => self.resolve_expr(value),
+            None => Ok(()),
+        }
     }
 
-    fn define(&mut self, name: &Token) {
-        if self.scopes.is_empty() {
-            return;
+    fn visit_switch(&mut self, subject: &Expr, cases: &[SwitchCase]) -> Result<(), String> {
+        self.resolve_expr(subject)?;
+        for value in cases.iter().filter_map(|(value, _)| value.as_ref()) {
+            self.resolve_expr(value)?;
         }
 
-        let scope_len = self.scopes.len();
-        self.scopes[scope_len - 1].insert(name.lexme.clone(), true);
+        self.begin_scope(declared_in(
+            cases
+                .iter()
+                .flat_map(|(_, stmts)| stmts.iter().map(|stmt| stmt.as_ref())),
+        ));
+        let result = cases
+            .iter()
+            .try_for_each(|(_, stmts)| self.resolve_many(stmts));
+        self.end_scope();
+        result
     }
 
-    fn resolve_var(&mut self, stmt: &Stmt) -> Result<(), String> {
-        if let Stmt::Var { name, initializer } = stmt {
-            self.declare(name);
-            self.resolve_expr(initializer)?;
-            self.define(name);
-            Ok(())
-        } else {
-            panic!("Wrong type in resolve var");
+    fn visit_try(&mut self, body: &Stmt, name: &Token, catch: &Stmt) -> Result<(), String> {
+        self.resolve(body)?;
+
+        let declared = declared_in([catch]).map(|mut declared| {
+            declared.insert(name.lexme.clone());
+            declared
+        });
+        self.begin_scope(declared);
+        self.declare(name);
+        self.define(name);
+        let result = self.resolve(catch);
+        self.end_scope();
+        result
+    }
+
+    fn visit_throw(&mut self, _keyword: &Token, value: &Expr) -> Result<(), String> {
+        self.resolve_expr(value)
+    }
+
+    // Imported files are resolved when they run
+    fn visit_import(&mut self, _keyword: &Token, _path: &str) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+impl ExprVisitor<Result<(), String>> for Resolver {
+    fn visit_anon_function(
+        &mut self,
+        _paren: &Token,
+        arguments: &[Token],
+        body: &[Box<Stmt>],
+    ) -> Result<(), String> {
+        self.resolve_function_helper(arguments, &[], body)
+    }
+
+    fn visit_array(&mut self, elements: &[Expr]) -> Result<(), String> {
+        for element in elements {
+            self.resolve_expr(element)?;
         }
+
+        Ok(())
     }
 
-    fn resolve_many(&mut self, stmts: &Vec<Box<Stmt>>) -> Result<(), String> {
-        for stmt in stmts {
-            self.resolve(&stmt)?;
+    fn visit_assign(&mut self, expr: &Expr, name: &Token, value: &Expr) -> Result<(), String> {
+        self.resolve_expr(value)?;
+        self.resolve_local(expr, name)
+    }
+
+    fn visit_binary(&mut self, left: &Expr, _operator: &Token, right: &Expr) -> Result<(), String> {
+        self.resolve_expr(left)?;
+        self.resolve_expr(right)
+    }
+
+    fn visit_call(
+        &mut self,
+        callee: &Expr,
+        _paren: &Token,
+        arguments: &[Expr],
+    ) -> Result<(), String> {
+        self.resolve_expr(callee)?;
+        for arg in arguments {
+            self.resolve_expr(arg)?;
         }
+
         Ok(())
     }
 
-    fn resolve_block(&mut self, stmt: &Stmt) -> Result<(), String> {
-        match stmt {
-            Stmt::Block { statements } => {
-                self.begin_scope();
-                self.resolve_many(statements)?;
-                self.end_scope();
-                Ok(())
-            }
-            _ => panic!("Wrong type"),
+    fn visit_get(&mut self, object: &Expr, _name: &Token) -> Result<(), String> {
+        self.resolve_expr(object)
+    }
+
+    fn visit_grouping(&mut self, expression: &Expr) -> Result<(), String> {
+        self.resolve_expr(expression)
+    }
+
+    fn visit_index(&mut self, object: &Expr, _bracket: &Token, index: &Expr) -> Result<(), String> {
+        self.resolve_expr(object)?;
+        self.resolve_expr(index)
+    }
+
+    fn visit_set_index(
+        &mut self,
+        object: &Expr,
+        _bracket: &Token,
+        index: &Expr,
+        _operator: Option<&Token>,
+        value: &Expr,
+    ) -> Result<(), String> {
+        self.resolve_expr(object)?;
+        self.resolve_expr(index)?;
+        self.resolve_expr(value)
+    }
+
+    fn visit_logical(
+        &mut self,
+        left: &Expr,
+        _operator: &Token,
+        right: &Expr,
+    ) -> Result<(), String> {
+        self.resolve_expr(left)?;
+        self.resolve_expr(right)
+    }
+
+    fn visit_literal(&mut self, _value: &LiteralValue) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn visit_map(&mut self, entries: &[(Expr, Expr)]) -> Result<(), String> {
+        for (key, value) in entries {
+            self.resolve_expr(key)?;
+            self.resolve_expr(value)?;
         }
+
+        Ok(())
     }
 
-    fn begin_scope(&mut self) {
-        self.scopes.push(HashMap::new());
+    fn visit_unary(&mut self, _operator: &Token, right: &Expr) -> Result<(), String> {
+        self.resolve_expr(right)
     }
 
-    fn end_scope(&mut self) {
-        self.scopes.pop().expect("Stack underflow");
+    fn visit_variable(&mut self, expr: &Expr, name: &Token) -> Result<(), String> {
+        if self
+            .scopes
+            .last()
+            .is_some_and(|scope| scope.get(&name.lexme) == Some(&false))
+        {
+            return Err("Can't read local variable in its own initializer".into());
+        }
+
+        self.resolve_local(expr, name)
+    }
+}
+
+impl Default for Resolver {
+    fn default() -> Self {
+        Self::new()
     }
 }
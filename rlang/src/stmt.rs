@@ -1,6 +1,7 @@
 use crate::{expr::Expr, lexer::Token};
+use std::rc::Rc;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Stmt {
     Expression {
         expression: Expr,
@@ -8,10 +9,27 @@ pub enum Stmt {
     Print {
         expression: Expr,
     },
+    // `print "x = {}", x;`, every `{}` in `format` is replaced by the next
+    // argument
+    PrintFormat {
+        keyword: Token,
+        format: String,
+        arguments: Vec<Expr>,
+    },
     Var {
         name: Token,
         initializer: Expr,
     },
+    // `var [a, b, ...rest] = initializer;`
+    VarArray {
+        bracket: Token,
+        names: Vec<Token>,
+        rest: Option<Token>,
+        initializer: Expr,
+    },
+    VarGroup {
+        declarations: Vec<Box<Stmt>>,
+    },
     Block {
         statements: Vec<Box<Stmt>>,
     },
@@ -23,16 +41,146 @@ pub enum Stmt {
     WhileStmt {
         condition: Expr,
         body: Box<Stmt>,
+        // Run after every iteration, even when the body hits `continue`
+        increment: Option<Box<Expr>>,
+        // Run when the loop finishes without hitting `break`
+        r#else: Option<Box<Stmt>>,
+        label: Option<Token>,
+    },
+    BreakStmt {
+        keyword: Token,
+        label: Option<Token>,
     },
+    ContinueStmt {
+        keyword: Token,
+        label: Option<Token>,
+    },
+    // The parameters and body are shared with the function values made from
+    // them, so calls run the very statements the resolver saw
     Function {
         name: Token,
-        params: Vec<Token>,
-        body: Vec<Box<Stmt>>,
+        params: Rc<[(Token, Option<Expr>)]>,
+        body: Rc<[Box<Stmt>]>,
     },
     ReturnStmt {
         keyword: Token,
         value: Option<Expr>,
     },
+    // Runs the statements from the first matching case, or `default`, through
+    // the following cases until a `break`
+    SwitchStmt {
+        subject: Expr,
+        cases: Vec<SwitchCase>,
+    },
+    // Runs `catch` with the error message bound to `name` when `body` fails
+    TryStmt {
+        body: Box<Stmt>,
+        name: Token,
+        catch: Box<Stmt>,
+    },
+    ThrowStmt {
+        keyword: Token,
+        value: Expr,
+    },
+    // Runs another file in the global environment, `path` is relative to the
+    // importing file
+    Import {
+        keyword: Token,
+        path: String,
+    },
+}
+
+// A `case` value, `None` for `default`, and the statements that follow it
+pub type SwitchCase = (Option<Expr>, Vec<Box<Stmt>>);
+
+// The statement counterpart of `ExprVisitor`
+pub trait StmtVisitor<T> {
+    fn visit_expression(&mut self, expression: &Expr) -> T;
+    fn visit_print(&mut self, expression: &Expr) -> T;
+    fn visit_print_format(&mut self, keyword: &Token, format: &str, arguments: &[Expr]) -> T;
+    fn visit_var(&mut self, name: &Token, initializer: &Expr) -> T;
+    fn visit_var_array(
+        &mut self,
+        bracket: &Token,
+        names: &[Token],
+        rest: Option<&Token>,
+        initializer: &Expr,
+    ) -> T;
+    fn visit_var_group(&mut self, declarations: &[Box<Stmt>]) -> T;
+    fn visit_block(&mut self, statements: &[Box<Stmt>]) -> T;
+    fn visit_if(&mut self, predicate: &Expr, then: &Stmt, r#else: Option<&Stmt>) -> T;
+    fn visit_while(
+        &mut self,
+        condition: &Expr,
+        body: &Stmt,
+        increment: Option<&Expr>,
+        r#else: Option<&Stmt>,
+        label: Option<&Token>,
+    ) -> T;
+    fn visit_break(&mut self, keyword: &Token, label: Option<&Token>) -> T;
+    fn visit_continue(&mut self, keyword: &Token, label: Option<&Token>) -> T;
+    fn visit_function(
+        &mut self,
+        name: &Token,
+        params: &[(Token, Option<Expr>)],
+        body: &[Box<Stmt>],
+    ) -> T;
+    fn visit_return(&mut self, keyword: &Token, value: Option<&Expr>) -> T;
+    fn visit_switch(&mut self, subject: &Expr, cases: &[SwitchCase]) -> T;
+    fn visit_try(&mut self, body: &Stmt, name: &Token, catch: &Stmt) -> T;
+    fn visit_throw(&mut self, keyword: &Token, value: &Expr) -> T;
+    fn visit_import(&mut self, keyword: &Token, path: &str) -> T;
+}
+
+impl Stmt {
+    pub fn accept<T>(&self, visitor: &mut dyn StmtVisitor<T>) -> T {
+        match self {
+            Stmt::Expression { expression } => visitor.visit_expression(expression),
+            Stmt::Print { expression } => visitor.visit_print(expression),
+            Stmt::PrintFormat {
+                keyword,
+                format,
+                arguments,
+            } => visitor.visit_print_format(keyword, format, arguments),
+            Stmt::Var { name, initializer } => visitor.visit_var(name, initializer),
+            Stmt::VarArray {
+                bracket,
+                names,
+                rest,
+                initializer,
+            } => visitor.visit_var_array(bracket, names, rest.as_ref(), initializer),
+            Stmt::VarGroup { declarations } => visitor.visit_var_group(declarations),
+            Stmt::Block { statements } => visitor.visit_block(statements),
+            Stmt::IfStmt {
+                predicate,
+                then,
+                r#else,
+            } => visitor.visit_if(predicate, then, r#else.as_deref()),
+            Stmt::WhileStmt {
+                condition,
+                body,
+                increment,
+                r#else,
+                label,
+            } => visitor.visit_while(
+                condition,
+                body,
+                increment.as_deref(),
+                r#else.as_deref(),
+                label.as_ref(),
+            ),
+            Stmt::BreakStmt { keyword, label } => visitor.visit_break(keyword, label.as_ref()),
+            Stmt::ContinueStmt { keyword, label } => {
+                visitor.visit_continue(keyword, label.as_ref())
+            }
+            Stmt::Function { name, params, body } => visitor.visit_function(name, params, body),
+            Stmt::ReturnStmt { keyword, value } => visitor.visit_return(keyword, value.as_ref()),
+            Stmt::SwitchStmt { subject, cases } => visitor.visit_switch(subject, cases),
+            Stmt::TryStmt { body, name, catch } => visitor.visit_try(body, name, catch),
+            Stmt::ThrowStmt { keyword, value } => visitor.visit_throw(keyword, value),
+            Stmt::Import { keyword, path } => visitor.visit_import(keyword, path),
+        }
+    }
 }
 
 impl std::fmt::Display for Stmt {
@@ -47,15 +195,23 @@ impl std::fmt::Display for Stmt {
                 params: _,
                 body: _,
             } => todo!(),
-            Self::WhileStmt {
-                condition: _,
-                body: _,
-            } => todo!(),
+            Self::WhileStmt { .. } => todo!(),
+            Self::BreakStmt { keyword: _, label } => match label {
+                Some(label) => format!("(break {})", label.lexme),
+                None => "(break)".to_string(),
+            },
+            Self::ContinueStmt { keyword: _, label } => match label {
+                Some(label) => format!("(continue {})", label.lexme),
+                None => "(continue)".to_string(),
+            },
             Self::IfStmt {
-                predicate: _,
-                then: _,
-                r#else: _,
-            } => todo!(),
+                predicate,
+                then,
+                r#else,
+            } => match r#else {
+                Some(r#else) => format!("(if {} {} {})", predicate, then, r#else),
+                None => format!("(if {} {})", predicate, then),
+            },
             Self::Block { statements } => {
                 format!(
                     "(block {})",
@@ -66,12 +222,64 @@ impl std::fmt::Display for Stmt {
                         .join(",")
                 )
             }
+            Self::SwitchStmt { subject, cases } => {
+                let cases = cases
+                    .iter()
+                    .map(|(value, stmts)| {
+                        let stmts = stmts
+                            .iter()
+                            .map(|stmt| stmt.to_string())
+                            .collect::<Vec<String>>()
+                            .join(",");
+                        match value {
+                            Some(value) => format!("(case {} {})", value, stmts),
+                            None => format!("(default {})", stmts),
+                        }
+                    })
+                    .collect::<Vec<String>>()
+                    .join(" ");
+                format!("(switch {} {})", subject, cases)
+            }
+            Self::TryStmt { body, name, catch } => {
+                format!("(try {} catch {} {})", body, name.lexme, catch)
+            }
+            Self::ThrowStmt { keyword: _, value } => format!("(throw {})", value),
+            Self::Import { keyword: _, path } => format!("(import {path})"),
             Self::Expression { expression } => expression.to_string(),
-            Self::Print { expression } => format!("(print {})", expression.to_string()),
+            Self::Print { expression } => format!("(print {})", expression),
+            Self::PrintFormat {
+                keyword: _,
+                format,
+                arguments,
+            } => {
+                let arguments = arguments
+                    .iter()
+                    .map(|arg| arg.to_string())
+                    .collect::<Vec<String>>()
+                    .join(" ");
+                format!("(print {:?} {})", format, arguments)
+            }
             Self::Var {
                 name,
                 initializer: _,
             } => format!("(var {})", name.lexme),
+            Self::VarArray {
+                bracket: _,
+                names,
+                rest,
+                initializer: _,
+            } => {
+                let mut names: Vec<String> = names.iter().map(|name| name.lexme.clone()).collect();
+                if let Some(rest) = rest {
+                    names.push(format!("...{}", rest.lexme));
+                }
+                format!("(var [{}])", names.join(" "))
+            }
+            Self::VarGroup { declarations } => declarations
+                .iter()
+                .map(|stmt| stmt.to_string())
+                .collect::<Vec<String>>()
+                .join(" "),
         };
         write!(f, "{}", s)
     }
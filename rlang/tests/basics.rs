@@ -172,7 +172,7 @@ mod tests {
         for _ in 0..21 {
             fibo.push(a);
             temp = b;
-            b = a + b;
+            b += a;
             a = temp;
         }
 
@@ -292,6 +292,29 @@ mod tests {
         assert_eq!(lines[1], "3");
     }
 
+    #[test]
+    fn interpret_fn_nested_capture() {
+        let path = Path::new("cases/fn_nested_capture.rl");
+        let output = Command::new("cargo")
+            .args(["run", "-p", "rl", "--", &path.display().to_string()])
+            .output()
+            .unwrap();
+
+        let lines = std::str::from_utf8(output.stdout.as_slice())
+            .unwrap()
+            .split('\n')
+            .collect::<Vec<&str>>();
+
+        assert_eq!(lines.len(), 4, "Output: '{}'", lines.join("\n"));
+        assert_eq!(lines[0], "10");
+        assert_eq!(lines[1], "6");
+        assert_eq!(lines[2], "12");
+
+        // The captures also have to hold up under static resolution
+        let source = std::fs::read_to_string(path).unwrap();
+        assert_eq!(rlang::check(&source), Ok(()));
+    }
+
     #[test]
     fn interpret_fn_closure() {
         let path = Path::new("cases/fn_closure.rl");
@@ -312,6 +335,28 @@ mod tests {
         assert_eq!(lines[3], "2");
     }
 
+    #[test]
+    fn interpret_fn_closure_shared() {
+        let path = Path::new("cases/fn_closure_shared.rl");
+        let output = Command::new("cargo")
+            .args(["run", "-p", "rl", "--", &path.display().to_string()])
+            .output()
+            .unwrap();
+
+        let lines = std::str::from_utf8(output.stdout.as_slice())
+            .unwrap()
+            .split('\n')
+            .collect::<Vec<&str>>();
+
+        assert_eq!(lines.len(), 7, "Output: '{}'", lines.join("\n"));
+        assert_eq!(lines[0], "1");
+        assert_eq!(lines[1], "11");
+        assert_eq!(lines[2], "12");
+        assert_eq!(lines[3], "17");
+        assert_eq!(lines[4], "1");
+        assert_eq!(lines[5], "18");
+    }
+
     #[test]
     fn interpret_fn_anon() {
         let path = Path::new("cases/fn_anon.rl");
@@ -347,4 +392,1770 @@ mod tests {
         assert_eq!(lines.len(), 2, "Output: '{}'", lines.join("\n"));
         assert_eq!(lines[0], "1");
     }
+    #[test]
+    fn run_string_assert_eq() {
+        assert!(rlang::run_string("assert_eq(1 + 1, 2);").is_ok());
+
+        let err = rlang::run_string("assert_eq(1 + 1, 3);").unwrap_err();
+        assert!(err.contains("expected 3 got 2"), "Error: '{}'", err);
+    }
+
+    #[test]
+    fn interpret_map_keys() {
+        let path = Path::new("cases/map_keys.rl");
+        let output = Command::new("cargo")
+            .args(["run", "-p", "rl", "--", &path.display().to_string()])
+            .output()
+            .unwrap();
+
+        let lines = std::str::from_utf8(output.stdout.as_slice())
+            .unwrap()
+            .split('\n')
+            .collect::<Vec<&str>>();
+
+        assert_eq!(lines.len(), 4, "Output: '{}'", lines.join("\n"));
+        assert_eq!(lines[0], "[b, a, c]");
+        assert_eq!(lines[1], "[2, 1, 3]");
+        assert_eq!(lines[2], "1");
+    }
+
+    #[test]
+    fn run_string_resolved_globals() {
+        let source = r#"
+            var count = 0;
+            var x = "global";
+            {
+                fn bump() { count = count + 1; }
+                bump();
+                bump();
+
+                fn show() { return x; }
+                var x = "block";
+                assert_eq(show(), "block");
+
+                fn pick(value = x) { return value; }
+                assert_eq(pick(), "block");
+            }
+            assert_eq(count, 2);
+            assert_eq(x, "global");
+            fn late() { return y; }
+            var y = 3;
+            assert_eq(late(), 3);
+        "#;
+        assert!(
+            rlang::run_string(source).is_ok(),
+            "{:?}",
+            rlang::run_string(source)
+        );
+    }
+
+    #[test]
+    fn run_string_keys_non_map() {
+        let err = rlang::run_string("keys(1);").unwrap_err();
+        assert!(
+            err.contains("keys expected a Map got Number"),
+            "Error: '{}'",
+            err
+        );
+    }
+
+    #[test]
+    fn interpret_var_group() {
+        let path = Path::new("cases/var_group.rl");
+        let output = Command::new("cargo")
+            .args(["run", "-p", "rl", "--", &path.display().to_string()])
+            .output()
+            .unwrap();
+
+        let lines = std::str::from_utf8(output.stdout.as_slice())
+            .unwrap()
+            .split('\n')
+            .collect::<Vec<&str>>();
+
+        assert_eq!(lines.len(), 4, "Output: '{}'", lines.join("\n"));
+        assert_eq!(lines[0], "1");
+        assert_eq!(lines[1], "2");
+        assert_eq!(lines[2], "nil");
+    }
+
+    #[test]
+    fn interpret_collection_truthy() {
+        let path = Path::new("cases/collection_truthy.rl");
+        let output = Command::new("cargo")
+            .args(["run", "-p", "rl", "--", &path.display().to_string()])
+            .output()
+            .unwrap();
+
+        let lines = std::str::from_utf8(output.stdout.as_slice())
+            .unwrap()
+            .split('\n')
+            .collect::<Vec<&str>>();
+
+        assert_eq!(lines.len(), 6, "Output: '{}'", lines.join("\n"));
+        assert_eq!(lines[0], "falsy");
+        assert_eq!(lines[1], "truthy");
+        assert_eq!(lines[2], "falsy");
+        assert_eq!(lines[3], "truthy");
+        assert_eq!(lines[4], "true");
+    }
+
+    #[test]
+    fn interpret_reduce() {
+        let path = Path::new("cases/reduce.rl");
+        let output = Command::new("cargo")
+            .args(["run", "-p", "rl", "--", &path.display().to_string()])
+            .output()
+            .unwrap();
+
+        let lines = std::str::from_utf8(output.stdout.as_slice())
+            .unwrap()
+            .split('\n')
+            .collect::<Vec<&str>>();
+
+        assert_eq!(lines.len(), 3, "Output: '{}'", lines.join("\n"));
+        assert_eq!(lines[0], "15");
+        assert_eq!(lines[1], "120");
+    }
+
+    #[test]
+    fn run_string_reduce_errors() {
+        let err = rlang::run_string("reduce([1], 0, fn (x) { return x; });").unwrap_err();
+        assert!(err.contains("2 arguments"), "Error: '{}'", err);
+
+        let err = rlang::run_string("reduce([1], 0, fn (acc, x) { return acc + missing; });")
+            .unwrap_err();
+        assert!(err.contains("missing"), "Error: '{}'", err);
+    }
+
+    #[test]
+    fn interpret_fn_default() {
+        let path = Path::new("cases/fn_default.rl");
+        let output = Command::new("cargo")
+            .args(["run", "-p", "rl", "--", &path.display().to_string()])
+            .output()
+            .unwrap();
+
+        let lines = std::str::from_utf8(output.stdout.as_slice())
+            .unwrap()
+            .split('\n')
+            .collect::<Vec<&str>>();
+
+        assert_eq!(lines.len(), 4, "Output: '{}'", lines.join("\n"));
+        assert_eq!(lines[0], "Hello, x Hello!");
+        assert_eq!(lines[1], "Hi, x Hi!");
+        assert_eq!(lines[2], "Hi, x ?");
+    }
+
+    #[test]
+    fn run_string_fn_default_arity() {
+        let err = rlang::run_string("fn f(a, b = 1) {} f();").unwrap_err();
+        assert!(
+            err.contains("expected 1 to 2 arguments got 0"),
+            "Error: '{}'",
+            err
+        );
+
+        let err = rlang::run_string("fn f(a = 1, b) {}").unwrap_err();
+        assert!(err.contains("without default"), "Error: '{}'", err);
+    }
+
+    #[test]
+    fn interpret_loop_else() {
+        let path = Path::new("cases/loop_else.rl");
+        let output = Command::new("cargo")
+            .args(["run", "-p", "rl", "--", &path.display().to_string()])
+            .output()
+            .unwrap();
+
+        let lines = std::str::from_utf8(output.stdout.as_slice())
+            .unwrap()
+            .split('\n')
+            .collect::<Vec<&str>>();
+
+        assert_eq!(lines.len(), 7, "Output: '{}'", lines.join("\n"));
+        assert_eq!(lines[0], "completed");
+        assert_eq!(lines[1], "0");
+        assert_eq!(lines[2], "1");
+        assert_eq!(lines[3], "0");
+        assert_eq!(lines[4], "2");
+        assert_eq!(lines[5], "done");
+    }
+
+    #[test]
+    fn run_string_break_outside_loop() {
+        let err = rlang::run_string("break;").unwrap_err();
+        assert!(
+            err.contains("'break' outside of a loop"),
+            "Error: '{}'",
+            err
+        );
+
+        let err = rlang::run_string("while (true) { fn f() { continue; } }").unwrap_err();
+        assert!(
+            err.contains("'continue' outside of a loop"),
+            "Error: '{}'",
+            err
+        );
+    }
+
+    #[test]
+    fn interpret_typeof() {
+        let path = Path::new("cases/typeof.rl");
+        let output = Command::new("cargo")
+            .args(["run", "-p", "rl", "--", &path.display().to_string()])
+            .output()
+            .unwrap();
+
+        let lines = std::str::from_utf8(output.stdout.as_slice())
+            .unwrap()
+            .split('\n')
+            .collect::<Vec<&str>>();
+
+        assert_eq!(lines.len(), 6, "Output: '{}'", lines.join("\n"));
+        assert_eq!(lines[0], "Number");
+        assert_eq!(lines[1], "String");
+        assert_eq!(lines[2], "nil");
+        assert_eq!(lines[3], "Array!");
+        assert_eq!(lines[4], "Callable");
+    }
+
+    #[test]
+    fn interpret_compound_index() {
+        let path = Path::new("cases/compound_index.rl");
+        let output = Command::new("cargo")
+            .args(["run", "-p", "rl", "--", &path.display().to_string()])
+            .output()
+            .unwrap();
+
+        let lines = std::str::from_utf8(output.stdout.as_slice())
+            .unwrap()
+            .split('\n')
+            .collect::<Vec<&str>>();
+
+        assert_eq!(lines.len(), 5, "Output: '{}'", lines.join("\n"));
+        assert_eq!(lines[0], "[11, 2]");
+        assert_eq!(lines[1], "1");
+        assert_eq!(lines[2], "{k: 2, new: 5}");
+        assert_eq!(lines[3], "4");
+    }
+
+    #[test]
+    fn interpreter_prelude() {
+        let mut interpreter = rlang::interpreter::Interpreter::new();
+        interpreter
+            .run_prelude("fn square(x) { return x * x; }")
+            .unwrap();
+        interpreter.define_global("base", rlang::expr::LiteralValue::Number(3.0));
+
+        rlang::run(&mut interpreter, "assert_eq(square(base), 9);").unwrap();
+    }
+
+    #[test]
+    fn interpreter_reset() {
+        let mut interpreter = rlang::interpreter::Interpreter::new();
+        rlang::run(&mut interpreter, "var a = 1; fn f() { return a; }").unwrap();
+        interpreter.reset();
+
+        let err = rlang::run(&mut interpreter, "print a;").unwrap_err();
+        assert!(
+            err.contains("'a' has not been declared"),
+            "Error: '{}'",
+            err
+        );
+        assert!(rlang::run(&mut interpreter, "f();").is_err());
+        rlang::run(&mut interpreter, "var start = clock(); assert(start > 0);").unwrap();
+    }
+
+    #[test]
+    fn run_string_chained_assignment() {
+        let source = "
+            var a;
+            var b;
+            var c = a = b = 5;
+            assert_eq(a, 5);
+            assert_eq(b, 5);
+            assert_eq(c, 5);
+
+            fn set_b() {
+                b = 10;
+                return 1;
+            }
+            a = b = set_b();
+            assert_eq(b, 1);
+            assert_eq(a, 1);
+        ";
+        rlang::run_string(source).unwrap();
+    }
+
+    #[test]
+    fn interpret_labeled_break() {
+        let path = Path::new("cases/labeled_break.rl");
+        let output = Command::new("cargo")
+            .args(["run", "-p", "rl", "--", &path.display().to_string()])
+            .output()
+            .unwrap();
+
+        let lines = std::str::from_utf8(output.stdout.as_slice())
+            .unwrap()
+            .split('\n')
+            .collect::<Vec<&str>>();
+
+        assert_eq!(lines.len(), 4, "Output: '{}'", lines.join("\n"));
+        assert_eq!(lines[0], "0");
+        assert_eq!(lines[1], "1");
+        assert_eq!(lines[2], "3");
+    }
+
+    #[test]
+    fn run_string_unknown_label() {
+        let err = rlang::run_string("while (true) { break outer; }").unwrap_err();
+        assert!(
+            err.contains("no enclosing loop labeled 'outer'"),
+            "Error: '{}'",
+            err
+        );
+    }
+
+    #[test]
+    fn check_top_level_return() {
+        let source = std::fs::read_to_string("cases/top_level_return.rl").unwrap();
+        let err = rlang::check(&source).unwrap_err();
+        assert!(
+            err.contains("Can't return from top-level code"),
+            "Error: '{}'",
+            err
+        );
+    }
+
+    #[test]
+    fn check_does_not_run() {
+        let source = std::fs::read_to_string("cases/fn_default.rl").unwrap();
+        assert!(rlang::check(&source).is_ok());
+        assert!(rlang::check("fn f() { var a = a; }").is_err());
+    }
+
+    #[test]
+    fn run_string_special_numbers() {
+        let source = "
+            assert(is_nan(0 / 0));
+            assert(is_nan(nan()));
+            assert(!is_nan(1));
+            assert(!is_finite(inf()));
+            assert(!is_finite(-inf()));
+            assert(is_finite(1 / 3));
+            assert(nan() != nan());
+        ";
+        rlang::run_string(source).unwrap();
+
+        let err = rlang::run_string("is_nan(\"a\");").unwrap_err();
+        assert!(
+            err.contains("is_nan expected a Number got String"),
+            "Error: '{}'",
+            err
+        );
+    }
+
+    #[test]
+    fn run_string_clamp_sign() {
+        let source = "
+            assert_eq(clamp(5, 0, 10), 5);
+            assert_eq(clamp(-5, 0, 10), 0);
+            assert_eq(clamp(15, 0, 10), 10);
+            assert_eq(clamp(3, 3, 3), 3);
+            assert_eq(sign(-2.5), -1);
+            assert_eq(sign(0), 0);
+            assert_eq(sign(7), 1);
+        ";
+        rlang::run_string(source).unwrap();
+
+        let err = rlang::run_string("clamp(1, 10, 0);").unwrap_err();
+        assert!(
+            err.contains("clamp expected lo <= hi got 10 and 0"),
+            "Error: '{}'",
+            err
+        );
+    }
+
+    #[test]
+    fn run_string_trigonometry() {
+        let source = "
+            assert_eq(sin(0), 0);
+            assert_eq(cos(0), 1);
+            assert_eq(tan(0), 0);
+            assert_eq(atan2(0, 1), 0);
+            assert_eq(atan2(1, 0), pi() / 2);
+            assert(cos(pi()) < -0.999);
+        ";
+        rlang::run_string(source).unwrap();
+
+        let err = rlang::run_string("sin(\"0\");").unwrap_err();
+        assert!(
+            err.contains("sin expected a Number got String"),
+            "Error: '{}'",
+            err
+        );
+    }
+
+    #[test]
+    fn run_string_blank_programs() {
+        for source in [
+            "",
+            "   \n\t\n",
+            "// hi",
+            "// hi\n// there\n",
+            "\n  // hi\n\n",
+        ] {
+            assert_eq!(rlang::run_string(source), Ok(()), "Source: {:?}", source);
+            assert_eq!(rlang::check(source), Ok(()), "Source: {:?}", source);
+        }
+    }
+
+    #[test]
+    fn run_last_value() {
+        let mut interpreter = rlang::interpreter::Interpreter::new();
+        let last = rlang::run_last(&mut interpreter, "1 + 1; var a = 3;").unwrap();
+        assert!(last == Some(rlang::expr::LiteralValue::Number(2.0)));
+
+        let last = rlang::run_last(&mut interpreter, "print a;").unwrap();
+        assert!(last.is_none());
+    }
+
+    #[test]
+    fn run_string_not_callable() {
+        let err = rlang::run_string("var a = 1;\n\na();").unwrap_err();
+        assert!(
+            err.contains("Line 3: value of type Number is not callable"),
+            "Error: '{}'",
+            err
+        );
+
+        let err = rlang::run_string("\"text\"(1);").unwrap_err();
+        assert!(
+            err.contains("Line 1: value of type String is not callable"),
+            "Error: '{}'",
+            err
+        );
+    }
+
+    #[test]
+    fn feed_without_semicolon() {
+        use rlang::expr::LiteralValue;
+
+        let mut interpreter = rlang::interpreter::Interpreter::new();
+        let value = rlang::feed(&mut interpreter, "1 + 1\n").unwrap();
+        assert!(value == Some(LiteralValue::Number(2.0)));
+
+        rlang::feed(&mut interpreter, "var a = 3").unwrap();
+        let value = rlang::feed(&mut interpreter, "a * 2;").unwrap();
+        assert!(value == Some(LiteralValue::Number(6.0)));
+
+        let Err(err) = rlang::feed(&mut interpreter, "1 +") else {
+            panic!("Expected an error");
+        };
+        assert!(err.contains("expected expression"), "Error: '{}'", err);
+        assert!(rlang::run_string("1 + 1").is_err());
+    }
+
+    #[test]
+    fn interpret_int_exact() {
+        let path = Path::new("cases/int_exact.rl");
+        let output = Command::new("cargo")
+            .args(["run", "-p", "rl", "--", &path.display().to_string()])
+            .output()
+            .unwrap();
+
+        let lines = std::str::from_utf8(output.stdout.as_slice())
+            .unwrap()
+            .split('\n')
+            .collect::<Vec<&str>>();
+
+        assert_eq!(lines.len(), 7, "Output: '{}'", lines.join("\n"));
+        assert_eq!(lines[0], "9007199254740993");
+        assert_eq!(lines[1], "9007199254740993");
+        assert_eq!(lines[2], "3.5");
+        assert_eq!(lines[3], "2");
+        assert_eq!(lines[4], "1.5");
+        assert_eq!(lines[5], "9223372036854776000");
+    }
+
+    #[test]
+    fn run_string_arity_names_params() {
+        let err = rlang::run_string("fn add(a, b) { return a + b; } add(1);").unwrap_err();
+        assert!(
+            err.contains("Callable add expected 2 arguments got 1 (parameters: a, b)"),
+            "Error: '{}'",
+            err
+        );
+
+        let err = rlang::run_string("fn f(a, b = 1) {} f();").unwrap_err();
+        assert!(
+            err.contains("Callable f expected 1 to 2 arguments got 0 (parameters: a, b)"),
+            "Error: '{}'",
+            err
+        );
+
+        let err = rlang::run_string("clock(1);").unwrap_err();
+        assert!(
+            err.ends_with("Callable clock expected 0 arguments got 1"),
+            "Error: '{}'",
+            err
+        );
+    }
+
+    #[test]
+    fn run_string_json_round_trip() {
+        let source = r#"
+            var config = {"name": "rl", "version": 1, "tags": ["a", "b"], "debug": false};
+            assert_eq(from_json(to_json(config)), config);
+            assert_eq(to_json([1, 2.5, nil, true]), "[1,2.5,null,true]");
+            assert_eq(from_json("[1, 2.5, null, true]"), [1, 2.5, nil, true]);
+            assert_eq(keys(from_json(to_json(config))), keys(config));
+        "#;
+        rlang::run_string(source).unwrap();
+
+        let err = rlang::run_string("from_json(\"[1,\");").unwrap_err();
+        assert!(err.contains("Invalid JSON"), "Error: '{}'", err);
+    }
+
+    #[test]
+    fn interpret_import() {
+        let path = Path::new("cases/import/main.rl");
+        let output = Command::new("cargo")
+            .args(["run", "-p", "rl", "--", &path.display().to_string()])
+            .output()
+            .unwrap();
+
+        let lines = std::str::from_utf8(output.stdout.as_slice())
+            .unwrap()
+            .split('\n')
+            .collect::<Vec<&str>>();
+
+        assert_eq!(lines.len(), 4, "Output: '{}'", lines.join("\n"));
+        assert_eq!(lines[0], "16");
+        assert_eq!(lines[1], "18");
+        assert_eq!(lines[2], "hello from helpers");
+    }
+
+    #[test]
+    fn run_file_import_errors() {
+        let err = rlang::run_file("cases/import/cycle_a.rl")
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.contains("import cycle, 'cycle_a.rl' is already being imported"),
+            "Error: '{}'",
+            err
+        );
+
+        let err = rlang::run_string("import \"missing.rl\";").unwrap_err();
+        assert!(
+            err.contains("could not import 'missing.rl'"),
+            "Error: '{}'",
+            err
+        );
+
+        let err = rlang::run_string("fn f() { import \"a.rl\"; }").unwrap_err();
+        assert!(
+            err.contains("'import' is only allowed at the top level"),
+            "Error: '{}'",
+            err
+        );
+    }
+
+    #[test]
+    fn run_file_import_error_names_file() {
+        let err = rlang::run_file("cases/import/broken/main.rl")
+            .unwrap_err()
+            .to_string();
+        assert_eq!(err, "lib.rl:5: index 5 out of range for array of length 2");
+
+        let err = rlang::run_file("cases/import/cycle_a.rl")
+            .unwrap_err()
+            .to_string();
+        assert!(err.starts_with("cycle_b.rl:1: "), "Error: '{}'", err);
+    }
+
+    #[test]
+    fn interpret_try_catch() {
+        let path = Path::new("cases/try_catch.rl");
+        let output = Command::new("cargo")
+            .args(["run", "-p", "rl", "--", &path.display().to_string()])
+            .output()
+            .unwrap();
+
+        let lines = std::str::from_utf8(output.stdout.as_slice())
+            .unwrap()
+            .split('\n')
+            .collect::<Vec<&str>>();
+
+        assert_eq!(lines.len(), 6, "Output: '{}'", lines.join("\n"));
+        assert_eq!(lines[0], "boom");
+        assert_eq!(lines[1], "2");
+        assert_eq!(lines[2], "negative -1");
+        assert_eq!(lines[3], "caught index error");
+        assert_eq!(lines[4], "done");
+    }
+
+    #[test]
+    fn run_string_uncaught_throw() {
+        let err = rlang::run_string("throw 42;").unwrap_err();
+        assert_eq!(err, "42");
+    }
+
+    #[test]
+    fn run_string_throw_from_functions() {
+        let source = r#"
+            fn f() { throw "boom"; }
+            try { f(); } catch (e) { assert_eq(e, "boom"); }
+            var g = fn () { throw "x"; };
+            try { g(); } catch (e) { assert_eq(e, "x"); }
+            try { [1][2]; } catch (e) { assert(e != "boom"); }
+        "#;
+        assert!(
+            rlang::run_string(source).is_ok(),
+            "{:?}",
+            rlang::run_string(source)
+        );
+
+        // Uncaught, they still say where they came from
+        let err = rlang::run_string(r#"fn f() { throw "boom"; } f();"#).unwrap_err();
+        assert_eq!(err, "Evaluating failed inside f: boom");
+    }
+
+    #[test]
+    fn interpret_switch_fallthrough() {
+        let path = Path::new("cases/switch_fallthrough.rl");
+        let output = Command::new("cargo")
+            .args(["run", "-p", "rl", "--", &path.display().to_string()])
+            .output()
+            .unwrap();
+
+        let lines = std::str::from_utf8(output.stdout.as_slice())
+            .unwrap()
+            .split('\n')
+            .collect::<Vec<&str>>();
+
+        assert_eq!(lines.len(), 8, "Output: '{}'", lines.join("\n"));
+        assert_eq!(lines[0], "small");
+        assert_eq!(lines[1], "small");
+        assert_eq!(lines[2], "three, medium");
+        assert_eq!(lines[3], "medium");
+        assert_eq!(lines[4], "large");
+        assert_eq!(lines[5], "0");
+        assert_eq!(lines[6], "2");
+    }
+
+    #[test]
+    fn run_string_switch_errors() {
+        let err =
+            rlang::run_string("switch (1) { default: print 1; default: print 2; }").unwrap_err();
+        assert!(
+            err.contains("a switch can only have one 'default'"),
+            "Error: '{}'",
+            err
+        );
+
+        let err = rlang::run_string("switch (1) { case 1: continue; }").unwrap_err();
+        assert!(
+            err.contains("'continue' outside of a loop"),
+            "Error: '{}'",
+            err
+        );
+    }
+
+    #[test]
+    fn run_string_utf8_slicing() {
+        rlang::run_string(
+            "
+            var s = \"héllo\";
+            assert_eq(substr(s, 0, 3), \"hél\");
+            assert_eq(substr(s, 1, 1), \"é\");
+            assert_eq(substr(s, 5, 0), \"\");
+            assert_eq(s[1], \"é\");
+            assert_eq(s[4], \"o\");
+            ",
+        )
+        .unwrap();
+
+        let err = rlang::run_string("substr(\"héllo\", 2, 4);").unwrap_err();
+        assert!(
+            err.contains("substr index out of range"),
+            "Error: '{}'",
+            err
+        );
+
+        let err = rlang::run_string("print \"héllo\"[5];").unwrap_err();
+        assert!(
+            err.contains("index 5 out of range for string of length 5"),
+            "Error: '{}'",
+            err
+        );
+    }
+
+    #[test]
+    fn is_complete_input() {
+        for source in [
+            "",
+            "print 1;",
+            "fn f() { return [1, (2)]; }",
+            "// {",
+            "print \"{\";",
+        ] {
+            assert!(rlang::is_complete(source), "Source: {:?}", source);
+        }
+        for source in ["fn f() {", "print (1 +", "var a = [1,\n2", "print \"open"] {
+            assert!(!rlang::is_complete(source), "Source: {:?}", source);
+        }
+        // More lines can't fix these, so the parser gets to report them
+        assert!(rlang::is_complete("print 1; }"));
+        assert!(rlang::is_complete("print @;"));
+    }
+
+    #[test]
+    fn interpret_pretty() {
+        let path = Path::new("cases/pretty.rl");
+        let output = Command::new("cargo")
+            .args(["run", "-p", "rl", "--", &path.display().to_string()])
+            .output()
+            .unwrap();
+
+        let lines = std::str::from_utf8(output.stdout.as_slice())
+            .unwrap()
+            .split('\n')
+            .collect::<Vec<&str>>();
+
+        assert_eq!(lines.len(), 17, "Output: '{}'", lines.join("\n"));
+        assert_eq!(
+            lines[..11].join("\n"),
+            "{
+  name: rl,
+  tags: [
+    a,
+    b
+  ],
+  nested: {
+    ok: false,
+    empty: []
+  }
+}"
+        );
+        assert_eq!(lines[11..15].join("\n"), "[\n  1,\n  [...]\n]");
+        assert_eq!(lines[15], "3");
+    }
+
+    #[test]
+    fn run_string_power() {
+        rlang::run_string(
+            "
+            assert_eq(2 ** 10, 1024);
+            assert_eq(2 ** 3 ** 2, 512);
+            assert_eq(-2 ** 2, -4);
+            assert_eq((-2) ** 2, 4);
+            assert_eq(2 ** -1, 0.5);
+            assert_eq(4 ** 0.5, 2);
+            assert_eq(typeof(2 ** 3), \"Number\");
+            assert_eq(2 ** 64, 18446744073709551616.0);
+            ",
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn run_block_error_restores_globals() {
+        let mut interpreter = rlang::interpreter::Interpreter::new();
+        rlang::run(&mut interpreter, "var a = 1;").unwrap();
+
+        let err = rlang::run(&mut interpreter, "{ var a = 2; var b = 3; missing(); }").unwrap_err();
+        assert!(err.contains("missing"), "Error: '{}'", err);
+        let err = rlang::run(
+            &mut interpreter,
+            "switch (1) { case 1: var a = 4; missing(); }",
+        )
+        .unwrap_err();
+        assert!(err.contains("missing"), "Error: '{}'", err);
+
+        rlang::run(
+            &mut interpreter,
+            "
+            assert_eq(a, 1);
+            var c = 5;
+            ",
+        )
+        .unwrap();
+        let err = rlang::run(&mut interpreter, "print b;").unwrap_err();
+        assert!(
+            err.contains("'b' has not been declared"),
+            "Error: '{}'",
+            err
+        );
+        assert!(interpreter.environment.borrow().enclosing.is_none());
+    }
+
+    #[test]
+    fn run_string_unicode_identifiers() {
+        rlang::run_string(
+            "
+            var café = 1;
+            fn größe(x) { return x * 2; }
+            assert_eq(größe(café), 2);
+            ",
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn run_string_immediately_invoked_fn() {
+        rlang::run_string(
+            "
+            assert_eq((fn(x) { return x; })(5), 5);
+            assert_eq(fn(x) { return x * 2; }(4), 8);
+            assert_eq((fn(a) { return fn(b) { return a + b; }; })(1)(2), 3);
+            assert_eq((fn() { return [1, 2]; })()[1], 2);
+            ",
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn run_num() {
+        use rlang::expr::LiteralValue;
+
+        let mut interpreter = rlang::interpreter::Interpreter::new();
+        let mut parse =
+            |text: &str| rlang::run_last(&mut interpreter, &format!("num(\"{text}\");"));
+
+        assert!(matches!(parse("42"), Ok(Some(LiteralValue::Int(42)))));
+        assert!(matches!(parse(" -7 "), Ok(Some(LiteralValue::Int(-7)))));
+        assert!(matches!(parse("42.0"), Ok(Some(LiteralValue::Number(x))) if x == 42.0));
+        assert!(matches!(parse("2.5e3"), Ok(Some(LiteralValue::Number(x))) if x == 2500.0));
+        assert!(matches!(
+            parse("99999999999999999999"),
+            Ok(Some(LiteralValue::Number(x))) if x == 1e20
+        ));
+
+        for text in ["", "abc", "4 2", "inf", "nan", "1.2.3", "--1"] {
+            let Err(err) = parse(text) else {
+                panic!("Parsed {:?}", text);
+            };
+            assert!(err.contains("num could not parse"), "Error: '{}'", err);
+        }
+    }
+
+    #[test]
+    fn run_string_zip() {
+        rlang::run_string(
+            "
+            var pairs = zip([1, 2, 3], [\"a\", \"b\"]);
+            assert_eq(to_json(pairs), to_json([[1, \"a\"], [2, \"b\"]]));
+            assert_eq(to_json(zip([], [1])), \"[]\");
+            ",
+        )
+        .unwrap();
+
+        let err = rlang::run_string("zip([1], 2);").unwrap_err();
+        assert!(
+            err.contains("zip expected an Array got Number"),
+            "Error: '{}'",
+            err
+        );
+    }
+
+    #[test]
+    fn run_string_sort() {
+        rlang::run_string(
+            "
+            var numbers = [3, 1.5, 2, -4];
+            assert_eq(to_json(sort(numbers)), \"[-4,1.5,2,3]\");
+            assert_eq(to_json(numbers), \"[3,1.5,2,-4]\");
+            assert_eq(to_json(sort([\"b\", \"c\", \"a\"])), to_json([\"a\", \"b\", \"c\"]));
+            assert_eq(to_json(sort([])), \"[]\");
+
+            var descending = sort(numbers, fn(a, b) { return b - a; });
+            assert_eq(to_json(descending), \"[3,2,1.5,-4]\");
+
+            // Ties keep their order
+            var pairs = [[1, \"x\"], [0, \"y\"], [1, \"z\"]];
+            var by_first = sort(pairs, fn(a, b) { return a[0] - b[0]; });
+            assert_eq(to_json(by_first), to_json([[0, \"y\"], [1, \"x\"], [1, \"z\"]]));
+            ",
+        )
+        .unwrap();
+
+        let err = rlang::run_string("sort([1, \"a\"]);").unwrap_err();
+        assert!(
+            err.contains("sort cannot compare Number with String"),
+            "Error: '{}'",
+            err
+        );
+        let err = rlang::run_string("sort([1, 2], fn(a, b) { return true; });").unwrap_err();
+        assert!(
+            err.contains("comparator to return a Number got true"),
+            "Error: '{}'",
+            err
+        );
+    }
+
+    #[test]
+    fn run_string_reverse() {
+        rlang::run_string(
+            "
+            assert_eq(reverse(\"héllo\"), \"olléh\");
+            assert_eq(reverse(\"\"), \"\");
+            var numbers = [1, 2, 3];
+            assert_eq(to_json(reverse(numbers)), \"[3,2,1]\");
+            assert_eq(to_json(numbers), \"[1,2,3]\");
+            ",
+        )
+        .unwrap();
+
+        let err = rlang::run_string("reverse(12);").unwrap_err();
+        assert!(
+            err.contains("reverse expected a String or Array got Number"),
+            "Error: '{}'",
+            err
+        );
+    }
+
+    #[test]
+    fn run_string_join() {
+        rlang::run_string(
+            "
+            assert_eq(join([1, 2, 3], \"-\"), \"1-2-3\");
+            assert_eq(join([], \", \"), \"\");
+            assert_eq(join([\"a\", nil, true, [1]], \" \"), \"a nil true [1]\");
+            ",
+        )
+        .unwrap();
+
+        let err = rlang::run_string("join(\"abc\", \"-\");").unwrap_err();
+        assert!(
+            err.contains("join expected an Array got String"),
+            "Error: '{}'",
+            err
+        );
+    }
+
+    #[test]
+    fn run_string_copy() {
+        rlang::run_string(
+            "
+            var original = {\"list\": [1, 2], \"name\": \"a\"};
+            var snapshot = copy(original);
+            snapshot[\"list\"][0] = 9;
+            snapshot[\"name\"] = \"b\";
+            assert_eq(to_json(original), to_json({\"list\": [1, 2], \"name\": \"a\"}));
+            assert_eq(to_json(snapshot), to_json({\"list\": [9, 2], \"name\": \"b\"}));
+
+            var looped = [1, 2];
+            looped[1] = looped;
+            var looped_copy = copy(looped);
+            looped_copy[0] = 5;
+            assert_eq(looped[0], 1);
+            assert_eq(looped_copy[1][1][0], 5);
+            ",
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn run_int_overflow_modes() {
+        use rlang::interpreter::{Interpreter, OverflowMode};
+
+        let mut interpreter = Interpreter::new();
+        rlang::run(
+            &mut interpreter,
+            "
+            assert_eq(9223372036854775807 + 1, 9223372036854775808.0);
+            assert_eq(-(-9223372036854775807 - 1), 9223372036854775808.0);
+            assert_eq(7 / 2, 3.5);
+            assert_eq(2 ** -1, 0.5);
+            ",
+        )
+        .unwrap();
+
+        interpreter.set_int_overflow(OverflowMode::Error);
+        let err = rlang::run(&mut interpreter, "print -(-9223372036854775807 - 1);").unwrap_err();
+        assert!(
+            err.contains("Line 1: integer overflow in -(-9223372036854775808)"),
+            "Error: '{}'",
+            err
+        );
+        let err = rlang::run(&mut interpreter, "print 9223372036854775807 + 1;").unwrap_err();
+        assert!(
+            err.contains("Line 1: integer overflow in 9223372036854775807 + 1"),
+            "Error: '{}'",
+            err
+        );
+        let err =
+            rlang::run(&mut interpreter, "fn f(x) { return x * x; } f(4294967296);").unwrap_err();
+        assert!(err.contains("integer overflow"), "Error: '{}'", err);
+
+        // Results that were never whole numbers aren't overflows
+        rlang::run(
+            &mut interpreter,
+            "
+            assert_eq(7 / 2, 3.5);
+            assert_eq(2 ** -1, 0.5);
+            assert_eq(1 ** 9999999999, 1);
+            assert_eq(9223372036854775807 + 1.0, 9223372036854775808.0);
+            ",
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn interpret_print_format() {
+        let path = Path::new("cases/print_format.rl");
+        let output = Command::new("cargo")
+            .args(["run", "-p", "rl", "--", &path.display().to_string()])
+            .output()
+            .unwrap();
+
+        let lines = std::str::from_utf8(output.stdout.as_slice())
+            .unwrap()
+            .split('\n')
+            .collect::<Vec<&str>>();
+
+        assert_eq!(lines.len(), 6, "Output: '{}'", lines.join("\n"));
+        assert_eq!(lines[0], "x = 3");
+        assert_eq!(lines[1], "1 + 2 = 3");
+        assert_eq!(lines[2], "hello rl!");
+        assert_eq!(lines[3], "[1, 2]nil");
+        assert_eq!(lines[4], "no placeholders");
+    }
+
+    #[test]
+    fn run_string_print_format_mismatch() {
+        let err = rlang::run_string("print \"{} {}\", 1;").unwrap_err();
+        assert!(
+            err.contains("Line 1: print format has 2 placeholders but got 1 arguments"),
+            "Error: '{}'",
+            err
+        );
+
+        let err = rlang::run_string("print \"{}\", 1, 2;").unwrap_err();
+        assert!(
+            err.contains("print format has 1 placeholders but got 2 arguments"),
+            "Error: '{}'",
+            err
+        );
+
+        let err = rlang::run_string("var f = \"{}\"; print f, 1;").unwrap_err();
+        assert!(err.contains("need a format string"), "Error: '{}'", err);
+    }
+
+    #[test]
+    fn interpret_dump_env() {
+        let path = Path::new("cases/dump_env.rl");
+        let output = Command::new("cargo")
+            .args(["run", "-p", "rl", "--", &path.display().to_string()])
+            .output()
+            .unwrap();
+
+        let lines = std::str::from_utf8(output.stdout.as_slice())
+            .unwrap()
+            .split('\n')
+            .collect::<Vec<&str>>();
+
+        assert_eq!(lines.len(), 9, "Output: '{}'", lines.join("\n"));
+        assert_eq!(lines[0], "-- scope 2");
+        assert_eq!(lines[1], "-- scope 1");
+        assert_eq!(lines[2], "a = 1");
+        assert_eq!(lines[3], "b = 2");
+        assert_eq!(lines[4], "inner = inner0");
+        assert_eq!(lines[5], "-- scope 0");
+        assert_eq!(lines[6], "greeting = hi");
+        assert_eq!(lines[7], "outer = outer1");
+    }
+
+    #[test]
+    fn interpret_map_hashable_keys() {
+        let path = Path::new("cases/map_hashable_keys.rl");
+        let output = Command::new("cargo")
+            .args(["run", "-p", "rl", "--", &path.display().to_string()])
+            .output()
+            .unwrap();
+
+        let lines = std::str::from_utf8(output.stdout.as_slice())
+            .unwrap()
+            .split('\n')
+            .collect::<Vec<&str>>();
+
+        assert_eq!(lines.len(), 8, "Output: '{}'", lines.join("\n"));
+        assert_eq!(lines[0], "16");
+        assert_eq!(lines[1], "5");
+        assert_eq!(lines[2], "[1, 2, 3, 4]");
+        assert_eq!(lines[3], "on");
+        assert_eq!(lines[4], "half");
+        assert_eq!(lines[5], "string one");
+        assert_eq!(lines[6], "{true: on, false: off, 0.5: half, 1: string one}");
+    }
+
+    #[test]
+    fn run_string_whole_number_map_keys() {
+        let source = "var m = {1: \"a\"}; m[2.0] = \"b\"; m[1.0] += \"!\";
+            assert_eq(m[1], \"a!\"); assert_eq(m[2], \"b\"); assert_eq(keys(m), [1, 2]);";
+        rlang::run_string(source).unwrap();
+    }
+
+    #[test]
+    fn run_string_unhashable_map_key() {
+        let err = rlang::run_string("var m = {}; m[clock] = 1;").unwrap_err();
+        assert!(
+            err.contains("Line 1: Map keys must be a Number, String or Bool, got Callable"),
+            "Error: '{}'",
+            err
+        );
+
+        let err = rlang::run_string("var m = {nil: 1};").unwrap_err();
+        assert!(err.contains("got nil"), "Error: '{}'", err);
+
+        let err = rlang::run_string("print to_json({1: 2});").unwrap_err();
+        assert!(err.contains("Cannot encode map key 1"), "Error: '{}'", err);
+    }
+
+    #[test]
+    fn run_string_callable_identity() {
+        rlang::run_string(
+            "
+            fn make() {
+                fn same() { return 1; }
+                return same;
+            }
+            var a = make();
+            var b = make();
+            var c = a;
+            assert(a != b);
+            assert(a == a);
+            assert(a == c);
+            assert([a] == [c]);
+            assert(clock == clock);
+            assert(clock != make);
+            ",
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn run_string_lines() {
+        rlang::run_string(
+            "
+            assert_eq(lines(\"a\nb\"), [\"a\", \"b\"]);
+            assert_eq(lines(\"a\nb\n\"), [\"a\", \"b\"]);
+            assert_eq(lines(\"a\r\nb\r\n\"), [\"a\", \"b\"]);
+            assert_eq(lines(\"a\n\nb\"), [\"a\", \"\", \"b\"]);
+            assert_eq(lines(\"\"), []);
+            ",
+        )
+        .unwrap();
+
+        let err = rlang::run_string("lines(1);").unwrap_err();
+        assert!(
+            err.contains("lines expected a String got Number"),
+            "Error: '{}'",
+            err
+        );
+    }
+
+    #[test]
+    fn run_string_string_natives() {
+        rlang::run_string(
+            "
+            assert_eq(trim(\"  padded \n\"), \"padded\");
+            assert_eq(trim(\"\"), \"\");
+            assert(starts_with(\"rlang\", \"rl\"));
+            assert(!starts_with(\"rlang\", \"lang\"));
+            assert(ends_with(\"main.rl\", \".rl\"));
+            assert(!ends_with(\"main.rl\", \"main\"));
+            assert_eq(replace(\"aaa\", \"a\", \"b\"), \"bbb\");
+            assert_eq(replace(\"aaaa\", \"aa\", \"a\"), \"aa\");
+            assert_eq(replace(\"hello world\", \"o\", \"\"), \"hell wrld\");
+            ",
+        )
+        .unwrap();
+
+        let err = rlang::run_string("trim(1);").unwrap_err();
+        assert!(
+            err.contains("trim expected a String got"),
+            "Error: '{}'",
+            err
+        );
+        let err = rlang::run_string("starts_with(\"a\", nil);").unwrap_err();
+        assert!(
+            err.contains("starts_with expected a String got"),
+            "Error: '{}'",
+            err
+        );
+        let err = rlang::run_string("ends_with([], \"a\");").unwrap_err();
+        assert!(
+            err.contains("ends_with expected a String got"),
+            "Error: '{}'",
+            err
+        );
+        let err = rlang::run_string("replace(\"a\", \"a\", 1);").unwrap_err();
+        assert!(
+            err.contains("replace expected a String got"),
+            "Error: '{}'",
+            err
+        );
+        let err = rlang::run_string("replace(\"a\", \"\", \"b\");").unwrap_err();
+        assert!(err.contains("non-empty"), "Error: '{}'", err);
+    }
+
+    #[test]
+    fn run_string_binary_type_error() {
+        let err = rlang::run_string("fn f() {} fn g() {} print f + g;").unwrap_err();
+        assert!(
+            err.contains("Line 1: + is not defined for operands of type Callable and Callable"),
+            "Error: '{}'",
+            err
+        );
+
+        let err = rlang::run_string("print [1] - nil;").unwrap_err();
+        assert!(
+            err.contains("- is not defined for operands of type Array and nil"),
+            "Error: '{}'",
+            err
+        );
+
+        let err = rlang::run_string("print 1 + \"a\";").unwrap_err();
+        assert!(
+            err.contains("Line 1: + is not defined for operands of type Number and String"),
+            "Error: '{}'",
+            err
+        );
+        assert!(rlang::run_string("assert_eq(1 == \"a\", false); assert(1 != \"a\");").is_ok());
+    }
+
+    #[test]
+    fn interpret_postfix_chain() {
+        let path = Path::new("cases/postfix_chain.rl");
+        let output = Command::new("cargo")
+            .args(["run", "-p", "rl", "--", &path.display().to_string()])
+            .output()
+            .unwrap();
+
+        let lines = std::str::from_utf8(output.stdout.as_slice())
+            .unwrap()
+            .split('\n')
+            .collect::<Vec<&str>>();
+
+        assert_eq!(lines.len(), 6, "Output: '{}'", lines.join("\n"));
+        assert_eq!(lines[0], "2");
+        assert_eq!(lines[1], "10");
+        assert_eq!(lines[2], "6");
+        assert_eq!(lines[3], "20");
+        assert_eq!(lines[4], "4");
+
+        let err = rlang::run_string("var a = [1]; print a.len;").unwrap_err();
+        assert!(
+            err.contains("Line 1: cannot get 'len' from Array, only maps have properties"),
+            "Error: '{}'",
+            err
+        );
+    }
+
+    #[test]
+    fn interpret_eval_order() {
+        let path = Path::new("cases/eval_order.rl");
+        let output = Command::new("cargo")
+            .args(["run", "-p", "rl", "--", &path.display().to_string()])
+            .output()
+            .unwrap();
+
+        let lines = std::str::from_utf8(output.stdout.as_slice())
+            .unwrap()
+            .split('\n')
+            .collect::<Vec<&str>>();
+
+        assert_eq!(lines.len(), 2, "Output: '{}'", lines.join("\n"));
+        assert_eq!(lines[0], "[g, h, f, callee, h, g, f]");
+    }
+
+    #[test]
+    fn run_log_routes_to_sink() {
+        use rlang::{environment::LogLevel, interpreter::Interpreter};
+        use std::{cell::RefCell, rc::Rc};
+
+        let logs = Rc::new(RefCell::new(vec![]));
+        let sink = logs.clone();
+        let mut interpreter = Interpreter::new();
+        interpreter.set_log_sink(Rc::new(move |level, msg| {
+            sink.borrow_mut().push((level, msg.to_string()))
+        }));
+
+        rlang::run(
+            &mut interpreter,
+            "
+            log(\"info\", \"starting\");
+            fn fail(n) { log(\"error\", \"failed with \" + n); }
+            fail(3);
+            log(\"message\", [1, 2]);
+            ",
+        )
+        .unwrap();
+
+        assert!(
+            *logs.borrow()
+                == vec![
+                    (LogLevel::Info, "starting".to_string()),
+                    (LogLevel::Error, "failed with 3".to_string()),
+                    (LogLevel::Message, "[1, 2]".to_string()),
+                ]
+        );
+
+        let err = rlang::run(&mut interpreter, "log(\"warn\", \"x\");").unwrap_err();
+        assert!(err.contains("log expected a level of"), "Error: '{}'", err);
+    }
+
+    #[test]
+    fn run_records_last_print() {
+        use rlang::{expr::LiteralValue, interpreter::Interpreter};
+
+        let mut interpreter = Interpreter::with_output(std::io::sink());
+        assert!(interpreter.last_print().is_none());
+
+        rlang::run(&mut interpreter, "print 5; print 6;").unwrap();
+        assert!(interpreter.last_print() == Some(LiteralValue::Int(6)));
+
+        rlang::run(&mut interpreter, "print \"x = {}\", 7;").unwrap();
+        assert!(interpreter.last_print() == Some(LiteralValue::StringValue("x = 7".into())));
+
+        rlang::run(&mut interpreter, "fn f() { print 1; } f();").unwrap();
+        assert!(interpreter.last_print() == Some(LiteralValue::Int(1)));
+
+        interpreter.reset();
+        assert!(interpreter.last_print().is_none());
+
+        let mut interpreter = Interpreter::with_output(std::io::sink());
+        interpreter.set_print_function();
+        rlang::run(&mut interpreter, "fn f() { print(2); } f();").unwrap();
+        assert!(interpreter.last_print() == Some(LiteralValue::Int(2)));
+        rlang::run(&mut interpreter, "print(\"a\", 3);").unwrap();
+        assert!(interpreter.last_print() == Some(LiteralValue::StringValue("a 3".into())));
+    }
+
+    #[test]
+    fn interpret_var_destructure() {
+        let path = Path::new("cases/var_destructure.rl");
+        let output = Command::new("cargo")
+            .args(["run", "-p", "rl", "--", &path.display().to_string()])
+            .output()
+            .unwrap();
+
+        let lines = std::str::from_utf8(output.stdout.as_slice())
+            .unwrap()
+            .split('\n')
+            .collect::<Vec<&str>>();
+
+        assert_eq!(lines.len(), 7, "Output: '{}'", lines.join("\n"));
+        assert_eq!(lines[0], "25");
+        assert_eq!(lines[1], "1");
+        assert_eq!(lines[2], "[2, 3]");
+        assert_eq!(lines[3], "[]");
+        assert_eq!(lines[4], "[2, 1]");
+        assert_eq!(lines[5], "6");
+    }
+
+    #[test]
+    fn run_string_destructure_mismatch() {
+        let err = rlang::run_string("var [a, b, c] = [1, 2];").unwrap_err();
+        assert!(
+            err.contains("Line 1: expected an Array of length 3 to destructure, got length 2"),
+            "Error: '{}'",
+            err
+        );
+
+        let err = rlang::run_string("var [a, b] = [1, 2, 3];").unwrap_err();
+        assert!(err.contains("length 2 to destructure"), "Error: '{}'", err);
+
+        let err = rlang::run_string("var [a, b, ...c] = [1];").unwrap_err();
+        assert!(err.contains("length at least 2"), "Error: '{}'", err);
+
+        let err = rlang::run_string("var [a] = \"a\";").unwrap_err();
+        assert!(
+            err.contains("cannot destructure String"),
+            "Error: '{}'",
+            err
+        );
+
+        let err = rlang::run_string("var [...a, b] = [1, 2];").unwrap_err();
+        assert!(
+            err.contains("'...rest' can only come last"),
+            "Error: '{}'",
+            err
+        );
+    }
+
+    #[test]
+    fn run_string_to_radix() {
+        rlang::run_string(
+            "
+            assert_eq(to_radix(255, 16), \"ff\");
+            assert_eq(to_radix(10, 2), \"1010\");
+            assert_eq(to_radix(0, 2), \"0\");
+            assert_eq(to_radix(-8, 8), \"-10\");
+            assert_eq(to_radix(35, 36), \"z\");
+            assert_eq(to_radix(100.0, 10), \"100\");
+            assert_eq(to_radix(-9223372036854775807 - 1, 16), \"-8000000000000000\");
+            ",
+        )
+        .unwrap();
+
+        let err = rlang::run_string("to_radix(10, 1);").unwrap_err();
+        assert!(
+            err.contains("to_radix expected a radix between 2 and 36 got 1"),
+            "Error: '{}'",
+            err
+        );
+        let err = rlang::run_string("to_radix(10, 37);").unwrap_err();
+        assert!(err.contains("between 2 and 36 got 37"), "Error: '{}'", err);
+        let err = rlang::run_string("to_radix(1.5, 2);").unwrap_err();
+        assert!(
+            err.contains("to_radix expected a whole Number got 1.5"),
+            "Error: '{}'",
+            err
+        );
+        let err = rlang::run_string("to_radix(\"1\", 2);").unwrap_err();
+        assert!(
+            err.contains("to_radix expected a whole Number"),
+            "Error: '{}'",
+            err
+        );
+    }
+
+    #[test]
+    fn run_string_while_condition_scope() {
+        // The condition runs in the scope around the loop, so it can't see
+        // variables the body declares
+        let err = rlang::run_string("while (!done) { var done = true; }").unwrap_err();
+        assert!(
+            err.contains("Variable 'done' has not been declared"),
+            "Error: '{}'",
+            err
+        );
+
+        // A body variable shadowing an outer one leaves the condition's alone
+        rlang::run_string(
+            "
+            var stop = false;
+            var i = 0;
+            while (!stop and i < 3) {
+                var stop = true;
+                i = i + 1;
+            }
+            assert_eq(i, 3);
+            assert_eq(stop, false);
+            ",
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn print_as_function() {
+        use rlang::interpreter::Interpreter;
+        use std::{cell::RefCell, io::Write, rc::Rc};
+
+        struct Shared(Rc<RefCell<Vec<u8>>>);
+        impl Write for Shared {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                self.0.borrow_mut().write(buf)
+            }
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let buffer = Rc::new(RefCell::new(vec![]));
+        let mut interpreter = Interpreter::with_output(Shared(buffer.clone()));
+        interpreter.set_print_function();
+        rlang::run(
+            &mut interpreter,
+            "print(\"hi\"); print(\"a\", 1, [2]); print(); var p = print; p(nil);",
+        )
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(buffer.borrow().clone()).unwrap(),
+            "hi\na 1 [2]\n\nnil\n"
+        );
+
+        let err = rlang::run(&mut interpreter, "print \"hi\";").unwrap_err();
+        assert!(err.contains("Expected"), "Error: '{}'", err);
+
+        // Still a function after a reset, while plain interpreters keep the statement
+        interpreter.reset();
+        rlang::run(&mut interpreter, "print(1);").unwrap();
+        assert!(rlang::run_string("var print = 1;").is_err());
+    }
+
+    #[test]
+    fn binary_error_spans() {
+        let source = "var a = 1; var b = \"x\";\nprint a + b * a;";
+        let err = rlang::run_string(source).unwrap_err();
+        assert!(
+            err.contains("Line 2: * is not defined for operands of type String and Number"),
+            "Error: '{}'",
+            err
+        );
+        assert!(
+            err.contains("operator at bytes 36..37 of the expression at 34..39"),
+            "Error: '{}'",
+            err
+        );
+        assert_eq!(&source[36..37], "*");
+        assert_eq!(&source[34..39], "b * a");
+
+        let source = "print 1 + \"a\";";
+        let err = rlang::run_string(source).unwrap_err();
+        assert!(
+            err.contains("operator at bytes 8..9 of the expression at 6..13"),
+            "Error: '{}'",
+            err
+        );
+        assert_eq!(&source[6..13], "1 + \"a\"");
+    }
+
+    #[test]
+    fn run_string_for_initializer_scope() {
+        // The initializer and loop share a block, so `i` is gone after the loop
+        let err = rlang::run_string("for (var i = 0; i < 3; i = i + 1) {} print i;").unwrap_err();
+        assert!(
+            err.contains("Variable 'i' has not been declared"),
+            "Error: '{}'",
+            err
+        );
+
+        // `continue` still runs the increment, and an outer `i` is left alone
+        rlang::run_string(
+            "
+            var i = 10;
+            var seen = 0;
+            for (var i = 0; i < 4; i = i + 1) {
+                if (i == 1) continue;
+                seen = seen + i;
+            }
+            assert_eq(seen, 5);
+            assert_eq(i, 10);
+            ",
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn run_string_print_fixed() {
+        rlang::run_string(
+            "
+            assert_eq(print_fixed(3.14159, 2), \"3.14\");
+            assert_eq(print_fixed(2.675, 2), \"2.67\");
+            assert_eq(print_fixed(2.5, 0), \"2\");
+            assert_eq(print_fixed(0.125, 2), \"0.12\");
+            assert_eq(print_fixed(1.005, 2), \"1.00\");
+            assert_eq(print_fixed(-1.25, 1), \"-1.2\");
+            assert_eq(print_fixed(7, 3), \"7.000\");
+            ",
+        )
+        .unwrap();
+
+        let err = rlang::run_string("print_fixed(1.5, -1);").unwrap_err();
+        assert!(
+            err.contains("print_fixed expected a whole non-negative Number got -1"),
+            "Error: '{}'",
+            err
+        );
+        let err = rlang::run_string("print_fixed(1.5, 101);").unwrap_err();
+        assert!(err.contains("at most 100 got 101"), "Error: '{}'", err);
+        let err = rlang::run_string("print_fixed(\"1\", 2);").unwrap_err();
+        assert!(
+            err.contains("print_fixed expected a Number got String"),
+            "Error: '{}'",
+            err
+        );
+    }
+
+    #[test]
+    fn run_strict_bool_modes() {
+        use rlang::interpreter::Interpreter;
+
+        let mut interpreter = Interpreter::new();
+        rlang::run(
+            &mut interpreter,
+            "
+            assert_eq(1 and 2, 2);
+            assert_eq(nil or \"x\", \"x\");
+            assert_eq(0 and 2, false);
+            ",
+        )
+        .unwrap();
+
+        interpreter.set_strict_bool(true);
+        rlang::run(
+            &mut interpreter,
+            "
+            assert_eq(1 and 2, true);
+            assert_eq(nil or \"x\", true);
+            assert_eq(\"a\" or 0, true);
+            assert_eq(1 and nil, false);
+            fn f() { return 3 and 4; }
+            assert_eq(f(), true);
+            var calls = 0;
+            fn g() { calls = calls + 1; return 1; }
+            g() or g();
+            assert_eq(calls, 1);
+            ",
+        )
+        .unwrap();
+
+        interpreter.reset();
+        rlang::run(&mut interpreter, "assert_eq(1 and 2, true);").unwrap();
+        interpreter.set_strict_bool(false);
+        rlang::run(&mut interpreter, "assert_eq(1 and 2, 2);").unwrap();
+    }
+
+    #[test]
+    fn run_collect_reports() {
+        use rlang::interpreter::Interpreter;
+
+        let mut interpreter = Interpreter::with_output(std::io::sink());
+        let report = rlang::run_collect(
+            &mut interpreter,
+            "
+            var x = 1;
+            fn twice(n) { return n * 2; }
+            print twice(x);
+            if (x > 0) { print \"yes\"; }
+            ",
+        )
+        .unwrap();
+        // 4 top-level statements, the return inside `twice`, then the if's
+        // block and its print
+        assert_eq!(report.statements, 7);
+        assert_eq!(report.functions, 1);
+        assert_eq!(report.output, "2\nyes\n");
+
+        // Counts start over, while definitions stay in the interpreter
+        let report = rlang::run_collect(
+            &mut interpreter,
+            "for (var i = 0; i < 3; i = i + 1) { print twice(i); }",
+        )
+        .unwrap();
+        assert_eq!(report.output, "0\n2\n4\n");
+        assert_eq!(report.functions, 0);
+        // The for block, its var and while, then per pass the body block, its
+        // print and the return
+        assert_eq!(report.statements, 3 + 3 * 3);
+
+        let err = rlang::run_collect(&mut interpreter, "print nope;").unwrap_err();
+        assert!(err.contains("nope"), "Error: '{}'", err);
+    }
+
+    #[test]
+    fn parse_bare_expression() {
+        use rlang::{expr::LiteralValue, interpreter::Interpreter, parser::Parser};
+
+        let parse = |source: &str| {
+            let tokens = rlang::lex(source).unwrap();
+            Parser::new(tokens.to_vec()).parse_program_or_expr()
+        };
+
+        let stmts = parse("6 * 7").unwrap();
+        let mut interpreter = Interpreter::with_output(std::io::sink());
+        interpreter
+            .interpret(stmts.iter().map(|b| b.as_ref()).collect())
+            .unwrap();
+        assert!(interpreter.last_print() == Some(LiteralValue::Int(42)));
+
+        // Programs keep their meaning and their errors
+        assert_eq!(parse("{}").unwrap()[0].to_string(), "(block )");
+        let err = parse("var x = 6 * 7").unwrap_err();
+        assert!(err.contains("Expected a ';'"), "Error: '{}'", err);
+        let err = parse("6 * 7; 8").unwrap_err();
+        assert!(err.contains("Expected ';'"), "Error: '{}'", err);
+    }
+
+    #[test]
+    fn interpret_bare_expression() {
+        let path = Path::new("cases/bare_expression.rl");
+        let output = Command::new("cargo")
+            .args(["run", "-p", "rl", "--", &path.display().to_string()])
+            .output()
+            .unwrap();
+
+        let lines = std::str::from_utf8(output.stdout.as_slice())
+            .unwrap()
+            .split('\n')
+            .collect::<Vec<&str>>();
+
+        assert_eq!(lines.len(), 2, "Output: '{}'", lines.join("\n"));
+        assert_eq!(lines[0], "42");
+    }
+
+    #[test]
+    fn run_string_nan_order() {
+        rlang::run_string(
+            "
+            var sorted = sort([3, nan(), 1]);
+            assert_eq(sorted[0], 1);
+            assert_eq(sorted[1], 3);
+            assert(is_nan(sorted[2]));
+
+            var mixed = sort([nan(), inf(), -inf(), nan(), 0]);
+            assert_eq(mixed[0], -inf());
+            assert_eq(mixed[1], 0);
+            assert_eq(mixed[2], inf());
+            assert(is_nan(mixed[3]) and is_nan(mixed[4]));
+
+            assert_eq(min([3, nan(), 1]), 1);
+            assert(is_nan(max([3, nan(), 1])));
+            assert(is_nan(min([nan(), nan()])));
+            assert_eq(max([2, 7.5, -1]), 7.5);
+            assert_eq(min([\"b\", \"a\"]), \"a\");
+            ",
+        )
+        .unwrap();
+
+        let err = rlang::run_string("min([]);").unwrap_err();
+        assert!(
+            err.contains("min expected a non-empty Array"),
+            "Error: '{}'",
+            err
+        );
+        let err = rlang::run_string("max([1, \"a\"]);").unwrap_err();
+        assert!(
+            err.contains("max cannot compare String with Number"),
+            "Error: '{}'",
+            err
+        );
+    }
+
+    #[test]
+    fn run_with_globals() {
+        use rlang::{expr::LiteralValue, interpreter::Interpreter};
+        use std::collections::HashMap;
+
+        let mut interpreter = Interpreter::with_globals(HashMap::from([
+            ("name".to_string(), LiteralValue::StringValue("rl".into())),
+            ("retries".to_string(), LiteralValue::Int(3)),
+        ]));
+        rlang::run(
+            &mut interpreter,
+            "
+            assert_eq(\"hello \" + name, \"hello rl\");
+            fn twice() { return retries * 2; }
+            assert_eq(twice(), 6);
+            retries = retries + 1;
+            assert_eq(clock() > 0, true);
+            ",
+        )
+        .unwrap();
+        let value = rlang::run_last(&mut interpreter, "retries;").unwrap();
+        assert!(value == Some(LiteralValue::Int(4)));
+
+        interpreter.reset();
+        let err = rlang::run(&mut interpreter, "print name;").unwrap_err();
+        assert!(
+            err.contains("Variable 'name' has not been declared"),
+            "Error: '{}'",
+            err
+        );
+    }
 }